@@ -20,7 +20,50 @@ CREATE TABLE IF NOT EXISTS artefact (
     blake3  text,
     entropy float,
     mime text,
-    metadata jsonb
+    metadata jsonb,
+    parent text,
+    run_id text,
+    ssdeep text,
+    tlsh text,
+    md5 text,
+    sha1 text,
+    known text,
+    known_label text,
+    entropy_min float,
+    entropy_max float,
+    entropy_mean float,
+    entropy_stddev float,
+    chi_square float,
+    entropy_class text,
+    ext_mismatch boolean,
+    mismatch_reason text,
+    inode bigint,
+    dev bigint,
+    uid bigint,
+    gid bigint,
+    user_name text,
+    group_name text,
+    mode integer,
+    permissions text,
+    nlink bigint,
+    changed timestamp,
+    xattrs jsonb,
+    link_target text,
+    link_resolved text,
+    link_dangling boolean,
+    hardlink_of text,
+    created_ns bigint,
+    accessed_ns bigint,
+    modified_ns bigint,
+    changed_ns bigint,
+    allocation text,
+    extents jsonb,
+    deleted timestamp,
+    sequence integer,
+    fn_created timestamp,
+    fn_modified timestamp,
+    fn_accessed timestamp,
+    fn_changed timestamp
 );
 
 -- set comments on table
@@ -29,17 +72,122 @@ COMMENT ON COLUMN artefact.name is 'The file or directory name';
 COMMENT ON COLUMN artefact.ext is 'The file extension';
 COMMENT ON COLUMN artefact.type is 'The artefact type: "F" for file, "D" for directory, "S" for a symbolic link, "U" for unknown';
 COMMENT ON COLUMN artefact.len is 'The file size in bytes';
+COMMENT ON COLUMN artefact.parent is 'For archive members, the path of the containing archive';
+COMMENT ON COLUMN artefact.run_id is 'The identifier of the run which produced this artefact';
+COMMENT ON COLUMN artefact.ssdeep is 'The ssdeep context triggered piecewise hash';
+COMMENT ON COLUMN artefact.tlsh is 'The TLSH locality sensitive hash, if the file is long and diverse enough';
+COMMENT ON COLUMN artefact.known is 'The hash sets match: "good", "bad" or "unknown"';
+COMMENT ON COLUMN artefact.known_label is 'The label of the matching hash set entry';
+COMMENT ON COLUMN artefact.entropy_min is 'The lowest Shannon entropy of the file blocks';
+COMMENT ON COLUMN artefact.entropy_max is 'The highest Shannon entropy of the file blocks';
+COMMENT ON COLUMN artefact.entropy_mean is 'The mean Shannon entropy of the file blocks';
+COMMENT ON COLUMN artefact.entropy_stddev is 'The standard deviation of the Shannon entropy of the file blocks';
+COMMENT ON COLUMN artefact.chi_square is 'The chi-square of the byte distribution, around 255 for random data';
+COMMENT ON COLUMN artefact.entropy_class is 'The content classification from its high entropy blocks, if any: "plain", "compressed" or "encrypted"';
+COMMENT ON COLUMN artefact.ext_mismatch is 'Whether the file extension disagrees with the type discovered from its content';
+COMMENT ON COLUMN artefact.inode is 'The inode number';
+COMMENT ON COLUMN artefact.dev is 'The device id of the filesystem containing the file';
+COMMENT ON COLUMN artefact.uid is 'The owner user id';
+COMMENT ON COLUMN artefact.gid is 'The owner group id';
+COMMENT ON COLUMN artefact.user_name is 'The owner user name, if it could be resolved';
+COMMENT ON COLUMN artefact.group_name is 'The owner group name, if it could be resolved';
+COMMENT ON COLUMN artefact.mode is 'The file type and permission bits (st_mode), including setuid, setgid and sticky bits';
+COMMENT ON COLUMN artefact.permissions is 'The permissions as displayed by ls -l, e.g. -rwsr-xr-x';
+COMMENT ON COLUMN artefact.nlink is 'The number of hard links';
+COMMENT ON COLUMN artefact.changed is 'The inode change time (ctime)';
+COMMENT ON COLUMN artefact.link_target is 'For symbolic links, the target as stored in the link';
+COMMENT ON COLUMN artefact.link_resolved is 'For symbolic links, the canonical path of the target if it exists';
+COMMENT ON COLUMN artefact.link_dangling is 'For symbolic links, whether the target doesn''t exist';
+COMMENT ON COLUMN artefact.hardlink_of is 'For hard links, the path of the first occurrence which was hashed instead of this one';
+COMMENT ON COLUMN artefact.created_ns is 'The creation (birth) time in nanoseconds since the epoch';
+COMMENT ON COLUMN artefact.accessed_ns is 'The last access time in nanoseconds since the epoch';
+COMMENT ON COLUMN artefact.modified_ns is 'The last modification time in nanoseconds since the epoch';
+COMMENT ON COLUMN artefact.changed_ns is 'The inode change time in nanoseconds since the epoch';
+COMMENT ON COLUMN artefact.xattrs is 'The extended attributes as text or hex, with decoded POSIX ACLs and file capabilities';
+COMMENT ON COLUMN artefact.mismatch_reason is 'Why the extension disagrees with the discovered type, e.g. "pe content with .jpg extension"';
+COMMENT ON COLUMN artefact.allocation is 'For files read from a disk image, "allocated" or "deleted"';
+COMMENT ON COLUMN artefact.extents is 'For files read from a disk image, their data location as byte ranges of the image, holes having no offset';
+COMMENT ON COLUMN artefact.deleted is 'For deleted inodes, the deletion time (ext dtime)';
+COMMENT ON COLUMN artefact.sequence is 'For files read from NTFS, the MFT record sequence number (the record number being in inode)';
+COMMENT ON COLUMN artefact.fn_created is 'For files read from NTFS, the $FILE_NAME created time';
+COMMENT ON COLUMN artefact.fn_modified is 'For files read from NTFS, the $FILE_NAME modified time';
+COMMENT ON COLUMN artefact.fn_accessed is 'For files read from NTFS, the $FILE_NAME accessed time';
+COMMENT ON COLUMN artefact.fn_changed is 'For files read from NTFS, the $FILE_NAME changed time';
 
 -- store the run history
 CREATE TABLE IF NOT EXISTS run_history (
+    run_id text PRIMARY KEY,
+    case_name text,
     start_time timestamp,
     end_time timestamp,
     elapsed text,
     nb_files bigint,
     args text,
-    tags text
+    tags text,
+    status text,
+    source text,
+    base_run text,
+    examiner text,
+    case_number text,
+    acquired timestamp,
+    image_md5 text,
+    image_sha1 text,
+    verified boolean
 );
 
+COMMENT ON COLUMN run_history.status is 'The run status: "running", "completed" or "interrupted"';
+COMMENT ON COLUMN run_history.source is 'The canonical path of the scanned directory';
+COMMENT ON COLUMN run_history.base_run is 'For incremental runs, the run used to skip unchanged files';
+COMMENT ON COLUMN run_history.examiner is 'For EWF images, the examiner name from the case metadata';
+COMMENT ON COLUMN run_history.case_number is 'For EWF images, the case number from the case metadata';
+COMMENT ON COLUMN run_history.acquired is 'For EWF images, the acquisition date';
+COMMENT ON COLUMN run_history.image_md5 is 'For EWF images, the MD5 of the acquired data stored in the image';
+COMMENT ON COLUMN run_history.image_sha1 is 'For EWF images, the SHA1 of the acquired data stored in the image';
+COMMENT ON COLUMN run_history.verified is 'For EWF images scanned with --verify, whether the acquired data matches the stored hashes';
+
+-- known-good and known-bad hashes
+CREATE TABLE IF NOT EXISTS hash_set (
+    hash text,
+    kind text,
+    label text,
+    source text
+);
+
+COMMENT ON COLUMN hash_set.hash is 'The MD5, SHA1 or SHA256 hash as a lowercase hex string';
+COMMENT ON COLUMN hash_set.kind is 'The hash set kind: "good" or "bad"';
+COMMENT ON COLUMN hash_set.label is 'A label for the hash, e.g. the file name or the malware family';
+COMMENT ON COLUMN hash_set.source is 'The canonical path of the file the hash was loaded from';
+
+-- partitions found in disk images
+CREATE TABLE IF NOT EXISTS partition (
+    run_id text,
+    image text,
+    number integer,
+    scheme text,
+    start bigint,
+    len bigint,
+    type_id text,
+    type_name text,
+    label text,
+    guid text,
+    filesystem text
+);
+
+COMMENT ON COLUMN partition.image is 'The disk image path';
+COMMENT ON COLUMN partition.number is 'The partition number, logical MBR partitions starting at 5, 0 for an image without partition table';
+COMMENT ON COLUMN partition.scheme is 'The partitioning scheme: "mbr", "gpt" or "none"';
+COMMENT ON COLUMN partition.start is 'The partition start in bytes';
+COMMENT ON COLUMN partition.len is 'The partition size in bytes';
+COMMENT ON COLUMN partition.type_id is 'The MBR partition type (e.g. 0x83) or the GPT partition type GUID';
+COMMENT ON COLUMN partition.type_name is 'The partition type name, if known';
+COMMENT ON COLUMN partition.label is 'The GPT partition name';
+COMMENT ON COLUMN partition.guid is 'The GPT unique partition GUID';
+COMMENT ON COLUMN partition.filesystem is 'The filesystem detected from the boot sector or superblock';
+
+CREATE INDEX IF NOT EXISTS artefact_run_id ON artefact (run_id);
+CREATE INDEX IF NOT EXISTS hash_set_hash ON hash_set (hash);
 
 ALTER TABLE artefact OWNER TO forensics;
-ALTER TABLE run_history OWNER TO forensics;
\ No newline at end of file
+ALTER TABLE run_history OWNER TO forensics;
+ALTER TABLE hash_set OWNER TO forensics;
+ALTER TABLE partition OWNER TO forensics;
\ No newline at end of file
@@ -0,0 +1,64 @@
+-- upgrade tables created by an earlier version of create_all.sql, adding the missing columns
+-- start with: psql -h 127.0.0.1 -p 5432 -U forensics -d forensics -f upgrade_all.sql
+-- tables added since (hash_set, partition) are created by running create_all.sql again
+ALTER TABLE artefact
+    ADD COLUMN IF NOT EXISTS parent text,
+    ADD COLUMN IF NOT EXISTS run_id text,
+    ADD COLUMN IF NOT EXISTS ssdeep text,
+    ADD COLUMN IF NOT EXISTS tlsh text,
+    ADD COLUMN IF NOT EXISTS md5 text,
+    ADD COLUMN IF NOT EXISTS sha1 text,
+    ADD COLUMN IF NOT EXISTS known text,
+    ADD COLUMN IF NOT EXISTS known_label text,
+    ADD COLUMN IF NOT EXISTS entropy_min float,
+    ADD COLUMN IF NOT EXISTS entropy_max float,
+    ADD COLUMN IF NOT EXISTS entropy_mean float,
+    ADD COLUMN IF NOT EXISTS entropy_stddev float,
+    ADD COLUMN IF NOT EXISTS chi_square float,
+    ADD COLUMN IF NOT EXISTS entropy_class text,
+    ADD COLUMN IF NOT EXISTS ext_mismatch boolean,
+    ADD COLUMN IF NOT EXISTS mismatch_reason text,
+    ADD COLUMN IF NOT EXISTS inode bigint,
+    ADD COLUMN IF NOT EXISTS dev bigint,
+    ADD COLUMN IF NOT EXISTS uid bigint,
+    ADD COLUMN IF NOT EXISTS gid bigint,
+    ADD COLUMN IF NOT EXISTS user_name text,
+    ADD COLUMN IF NOT EXISTS group_name text,
+    ADD COLUMN IF NOT EXISTS mode integer,
+    ADD COLUMN IF NOT EXISTS permissions text,
+    ADD COLUMN IF NOT EXISTS nlink bigint,
+    ADD COLUMN IF NOT EXISTS changed timestamp,
+    ADD COLUMN IF NOT EXISTS xattrs jsonb,
+    ADD COLUMN IF NOT EXISTS link_target text,
+    ADD COLUMN IF NOT EXISTS link_resolved text,
+    ADD COLUMN IF NOT EXISTS link_dangling boolean,
+    ADD COLUMN IF NOT EXISTS hardlink_of text,
+    ADD COLUMN IF NOT EXISTS created_ns bigint,
+    ADD COLUMN IF NOT EXISTS accessed_ns bigint,
+    ADD COLUMN IF NOT EXISTS modified_ns bigint,
+    ADD COLUMN IF NOT EXISTS changed_ns bigint,
+    ADD COLUMN IF NOT EXISTS allocation text,
+    ADD COLUMN IF NOT EXISTS extents jsonb,
+    ADD COLUMN IF NOT EXISTS deleted timestamp,
+    ADD COLUMN IF NOT EXISTS sequence integer,
+    ADD COLUMN IF NOT EXISTS fn_created timestamp,
+    ADD COLUMN IF NOT EXISTS fn_modified timestamp,
+    ADD COLUMN IF NOT EXISTS fn_accessed timestamp,
+    ADD COLUMN IF NOT EXISTS fn_changed timestamp;
+
+ALTER TABLE run_history
+    ADD COLUMN IF NOT EXISTS run_id text,
+    ADD COLUMN IF NOT EXISTS case_name text,
+    ADD COLUMN IF NOT EXISTS status text,
+    ADD COLUMN IF NOT EXISTS source text,
+    ADD COLUMN IF NOT EXISTS base_run text,
+    ADD COLUMN IF NOT EXISTS examiner text,
+    ADD COLUMN IF NOT EXISTS case_number text,
+    ADD COLUMN IF NOT EXISTS acquired timestamp,
+    ADD COLUMN IF NOT EXISTS image_md5 text,
+    ADD COLUMN IF NOT EXISTS image_sha1 text,
+    ADD COLUMN IF NOT EXISTS verified boolean;
+
+-- run_history.run_id is only a primary key for tables created by this version
+CREATE UNIQUE INDEX IF NOT EXISTS run_history_run_id ON run_history (run_id);
+CREATE INDEX IF NOT EXISTS artefact_run_id ON artefact (run_id);
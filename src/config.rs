@@ -1,18 +1,230 @@
 // manage configuration from the config.toml file
 
-use std::{collections::HashMap, path::Path, str::FromStr};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
+use anyhow::{bail, Context};
+use clap::{parser::ValueSource, ArgMatches};
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
-struct Config {
-    artfeact: HashMap<String, Artefact>,
+use crate::{args::Args, discoverer};
+
+// unknown keys are rejected to catch typos early
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // default scan options
+    options: Options,
+
+    // where to store artefacts
+    database: Database,
+
+    // settings per file extension
+    artefact: HashMap<String, Artefact>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Options {
+    threads: Option<usize>,
+    md5: bool,
+    sha1: bool,
+    blake3: bool,
+    sha256: bool,
+    ssdeep: bool,
+    tlsh: bool,
+    entropy: bool,
+    discover: bool,
+    xattrs: bool,
+    verify: bool,
+
+    // only used when not given on the command line, their defaults being there
+    entropy_block_size: Option<u64>,
+    mmap_max_size: Option<u64>,
+    archive_depth: Option<u8>,
+    archive_max_size: Option<u64>,
+
+    // hash sets, added to the command line ones
+    known_good: Vec<PathBuf>,
+    known_bad: Vec<PathBuf>,
+    skip_known_good: bool,
+
+    // when not empty, only discover files whose extension belongs to these categories
+    categories: Vec<String>,
+
+    // walker filters, added to the command line ones
+    include: Vec<String>,
+    exclude: Vec<String>,
+    max_depth: Option<usize>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    one_file_system: bool,
+    follow_symlinks: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Database {
+    // same as --output
+    url: Option<String>,
+    copy: bool,
+    batch_size: Option<usize>,
+
+    // e.g. "500ms" or "10s"
+    flush_interval: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct Artefact {
+    // run discoverers on files with this extension
+    #[serde(default = "enabled")]
     discover: bool,
+
+    // e.g. image, archive, executable
+    #[serde(default)]
     category: String,
+
+    // discovered types this extension is expected for, in addition to the built-in ones
+    #[serde(default)]
+    mime: Vec<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Config {
+    // file values are only defaults: flags and values given on the command line take precedence,
+    // flags set in the file being turned off with --no-<flag>
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches) -> anyhow::Result<()> {
+        let options = &self.options;
+        let flag = |cli: bool, off: bool, file: bool| cli || (!off && file);
+
+        // values having a default are only replaced if not given on the command line
+        let cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        args.threads = args.threads.or(options.threads);
+        args.md5 = flag(args.md5, args.no_md5, options.md5);
+        args.sha1 = flag(args.sha1, args.no_sha1, options.sha1);
+        args.blake3 = flag(args.blake3, args.no_blake3, options.blake3);
+        args.sha256 = flag(args.sha256, args.no_sha256, options.sha256);
+        args.ssdeep = flag(args.ssdeep, args.no_ssdeep, options.ssdeep);
+        args.tlsh = flag(args.tlsh, args.no_tlsh, options.tlsh);
+        args.known_good.extend(options.known_good.iter().cloned());
+        args.known_bad.extend(options.known_bad.iter().cloned());
+        args.skip_known_good = flag(
+            args.skip_known_good,
+            args.no_skip_known_good,
+            options.skip_known_good,
+        );
+        args.entropy = flag(args.entropy, args.no_entropy, options.entropy);
+        args.discover = flag(args.discover, args.no_discover, options.discover);
+        args.xattrs = flag(args.xattrs, args.no_xattrs, options.xattrs);
+        args.verify = flag(args.verify, args.no_verify, options.verify);
+
+        if let Some(size) = options
+            .entropy_block_size
+            .filter(|_| !cli("entropy_block_size"))
+        {
+            if size == 0 {
+                bail!("entropy_block_size must be at least 1");
+            }
+            args.entropy_block_size = size;
+        }
+        if let Some(size) = options.mmap_max_size.filter(|_| !cli("mmap_max_size")) {
+            args.mmap_max_size = size;
+        }
+        if let Some(depth) = options.archive_depth.filter(|_| !cli("archive_depth")) {
+            args.archive_depth = depth;
+        }
+        if let Some(size) = options
+            .archive_max_size
+            .filter(|_| !cli("archive_max_size"))
+        {
+            args.archive_max_size = size;
+        }
+
+        args.include.extend(options.include.iter().cloned());
+        args.exclude.extend(options.exclude.iter().cloned());
+        args.max_depth = args.max_depth.or(options.max_depth);
+        args.min_size = args.min_size.or(options.min_size);
+        args.max_size = args.max_size.or(options.max_size);
+        args.one_file_system = flag(
+            args.one_file_system,
+            args.no_one_file_system,
+            options.one_file_system,
+        );
+        args.follow_symlinks = flag(
+            args.follow_symlinks,
+            args.no_follow_symlinks,
+            options.follow_symlinks,
+        );
+
+        // --db given on the command line takes precedence too, the IAA_DB environment
+        // variable only being used if no URL is found at all
+        let database = &self.database;
+        if args.output.is_none() && args.db.is_none() {
+            args.output = database.url.clone();
+        }
+        args.copy = flag(args.copy, args.no_copy, database.copy);
+        if let Some(size) = database.batch_size.filter(|_| !cli("batch_size")) {
+            args.batch_size = size;
+        }
+        if let Some(interval) = database
+            .flush_interval
+            .as_ref()
+            .filter(|_| !cli("flush_interval"))
+        {
+            args.flush_interval = humantime::parse_duration(interval)
+                .with_context(|| format!("invalid flush_interval '{interval}'"))?;
+        }
+
+        Ok(())
+    }
+
+    // whether discoverers should run for a file extension
+    pub fn discover(&self, ext: &str) -> bool {
+        let artefact = self.artefact.get(&ext.to_lowercase());
+
+        if artefact.is_some_and(|a| !a.discover) {
+            return false;
+        }
+
+        let categories = &self.options.categories;
+        categories.is_empty() || artefact.is_some_and(|a| categories.contains(&a.category))
+    }
+
+    // why a file extension doesn't match its discovered type, None if it does or
+    // if nothing is expected for this type
+    pub fn mismatch(&self, ext: &str, mime: &str) -> Option<String> {
+        let ext = ext.to_lowercase();
+
+        // versions or rotations, e.g. libc.so.6 or access.log.1
+        if !ext.is_empty() && ext.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut expected: Vec<&str> = discoverer::extensions(mime).unwrap_or_default().to_vec();
+        expected.extend(
+            self.artefact
+                .iter()
+                .filter(|(_, a)| a.mime.iter().any(|m| m == mime))
+                .map(|(ext, _)| ext.as_str()),
+        );
+
+        if expected.is_empty() || expected.contains(&ext.as_str()) {
+            return None;
+        }
+
+        if ext.is_empty() {
+            Some(format!("{mime} content without extension"))
+        } else {
+            Some(format!("{mime} content with .{ext} extension"))
+        }
+    }
 }
 
 impl TryFrom<&Path> for Config {
@@ -20,11 +232,108 @@ impl TryFrom<&Path> for Config {
 
     fn try_from(path: &Path) -> Result<Self, Self::Error> {
         // load file as a string
-        let cfg = std::fs::read_to_string(path)?;
+        let cfg = std::fs::read_to_string(path)
+            .with_context(|| format!("unable to read config file '{}'", path.display()))?;
 
         // convert .toml to Config struct
-        let config = toml::from_str(&cfg)?;
+        let config = toml::from_str(&cfg)
+            .with_context(|| format!("invalid config file '{}'", path.display()))?;
 
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config() -> anyhow::Result<()> {
+        let config = Config::try_from(Path::new("config.toml"))?;
+        assert!(config.options.sha256);
+        assert!(config.discover("PNG"));
+        assert!(config.discover("unknown"));
+
+        let config: Config = toml::from_str(
+            "[options]\ncategories = [\"image\"]\n[artefact.png]\ncategory = \"image\"\n[artefact.exe]\ndiscover = false",
+        )?;
+        assert!(config.discover("png"));
+        assert!(!config.discover("exe"));
+        assert!(!config.discover("txt"));
+
+        let config: Config =
+            toml::from_str("[artefact.apk]\nmime = [\"zip\"]\n[artefact.pak]\nmime = [\"zip\"]")?;
+        assert_eq!(config.mismatch("JPG", "jpeg"), None);
+        assert_eq!(config.mismatch("pak", "zip"), None);
+        assert_eq!(config.mismatch("6", "elf"), None);
+        assert_eq!(config.mismatch("1", "pe"), None);
+        assert_eq!(
+            config.mismatch("jpg", "pe").as_deref(),
+            Some("pe content with .jpg extension")
+        );
+        assert_eq!(
+            config.mismatch("", "png").as_deref(),
+            Some("png content without extension")
+        );
+
+        assert!(toml::from_str::<Config>("[artfeact.png]\ndiscover = true").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn apply() -> anyhow::Result<()> {
+        use clap::{CommandFactory, FromArgMatches};
+
+        let config: Config = toml::from_str(
+            "[options]\nmd5 = true\nentropy = true\narchive_depth = 2\nentropy_block_size = 1024\n\
+            [database]\nurl = \"sqlite:file.db\"\nbatch_size = 10\nflush_interval = \"500ms\"",
+        )?;
+        let apply = |cmd: &[&str]| -> anyhow::Result<Args> {
+            let matches =
+                Args::command().try_get_matches_from(["iaa", "-d", "."].iter().chain(cmd))?;
+            let mut args = Args::from_arg_matches(&matches)?;
+            config.apply(&mut args, &matches)?;
+            Ok(args)
+        };
+
+        // file values
+        let args = apply(&[])?;
+        assert!(args.md5 && args.entropy && !args.sha1);
+        assert_eq!(args.archive_depth, 2);
+        assert_eq!(args.entropy_block_size, 1024);
+        assert_eq!(args.batch_size, 10);
+        assert_eq!(args.flush_interval, std::time::Duration::from_millis(500));
+        assert_eq!(args.output.as_deref(), Some("sqlite:file.db"));
+
+        // command line values win, flags being turned off with --no-<flag>
+        let args = apply(&[
+            "--no-md5",
+            "--sha1",
+            "--archive-depth",
+            "0",
+            "--batch-size",
+            "1000",
+            "--output",
+            "sqlite:cli.db",
+        ])?;
+        assert!(!args.md5 && args.entropy && args.sha1);
+        assert_eq!(args.archive_depth, 0);
+        assert_eq!(args.batch_size, 1000);
+        assert_eq!(args.output.as_deref(), Some("sqlite:cli.db"));
+
+        // the last one wins
+        assert!(apply(&["--no-entropy", "--entropy"])?.entropy);
+
+        // --db too, output then being resolved from it
+        let args = apply(&["--db", "postgres://localhost/iaa"])?;
+        assert_eq!(args.output, None);
+        assert_eq!(args.db.as_deref(), Some("postgres://localhost/iaa"));
+
+        let config: Config = toml::from_str("[options]\nentropy_block_size = 0")?;
+        let matches = Args::command().try_get_matches_from(["iaa", "-d", "."])?;
+        assert!(config
+            .apply(&mut Args::from_arg_matches(&matches)?, &matches)
+            .is_err());
+        Ok(())
+    }
+}
@@ -13,10 +13,12 @@ const SIGNATURE: FileSignature = FileSignature {
     header: b"BM",
     footer: None,
     mime: "bmp",
+    extensions: &["bmp", "dib"],
     endianness: Endianness::LittleEndian,
 };
 impl_discoverer!(BMP, SIGNATURE);
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Decode)]
 pub struct BitmapFileHeaderAndCore {
     #[serde(skip)]
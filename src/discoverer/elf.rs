@@ -0,0 +1,374 @@
+use serde::Serialize;
+
+use crate::{
+    discoverer::{Endianness, FileSignature},
+    impl_discoverer,
+};
+
+//-------------------------------------------------------------------------------------------
+// ELF
+//-------------------------------------------------------------------------------------------
+const SIGNATURE: FileSignature = FileSignature {
+    header: b"\x7FELF",
+    footer: None,
+    mime: "elf",
+    extensions: &["", "so", "o", "ko", "bin", "elf", "out", "axf", "prx"],
+    // only a default: real endianness is given by e_ident[EI_DATA]
+    endianness: Endianness::LittleEndian,
+};
+
+impl_discoverer!(ELF, SIGNATURE);
+
+// e_ident values
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+// section types
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOTE: u32 = 7;
+const SHT_DYNSYM: u32 = 11;
+
+// program header types
+const PT_INTERP: u32 = 3;
+
+// dynamic tags
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+
+// note types
+const NT_GNU_BUILD_ID: u32 = 3;
+
+// for some types, we'll not call the FileSignature trait's metadata()
+// but a custom one, as Rust doesn't yet support trait's method specialization
+#[derive(Debug, Default, Serialize)]
+pub struct ElfMeta {
+    // 32 or 64
+    class: u8,
+
+    // "little" or "big"
+    endianness: &'static str,
+
+    // target architecture
+    machine: String,
+
+    // exec, dyn, rel, core
+    r#type: &'static str,
+
+    // entry point virtual address
+    entry: u64,
+
+    // program interpreter (dynamic loader) if any
+    interpreter: Option<String>,
+
+    // list of sections
+    sections: Vec<ElfSection>,
+
+    // shared libraries needed
+    needed: Vec<String>,
+
+    // GNU build-id as an hex string
+    build_id: Option<String>,
+
+    // number of entries in .symtab
+    symbols: u64,
+
+    // number of entries in .dynsym
+    dynamic_symbols: u64,
+
+    // no .symtab section
+    stripped: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ElfSection {
+    name: String,
+    r#type: u32,
+    offset: u64,
+    size: u64,
+}
+
+// section header, whatever the class
+struct SectionHeader {
+    name: u32,
+    r#type: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+    entsize: u64,
+}
+
+// ELF file: class and endianness are only known when reading e_ident
+struct Elf<'a> {
+    data: &'a [u8],
+    endianness: Endianness,
+    is64: bool,
+}
+
+impl ELF {
+    // decode ELF header, sections and segments
+    pub fn elf(bytes: &[u8]) -> Option<serde_json::Value> {
+        let meta = ElfMeta::try_from(bytes).ok()?;
+        serde_json::to_value(meta).ok()
+    }
+}
+
+impl<'a> Elf<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let is64 = match *data.get(4)? {
+            ELFCLASS32 => false,
+            ELFCLASS64 => true,
+            _ => return None,
+        };
+        let endianness = match *data.get(5)? {
+            ELFDATA2LSB => Endianness::LittleEndian,
+            ELFDATA2MSB => Endianness::BigEndian,
+            _ => return None,
+        };
+
+        Some(Self {
+            data,
+            endianness,
+            is64,
+        })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        self.endianness.u16(self.data, offset)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        self.endianness.u32(self.data, offset)
+    }
+
+    // addresses and offsets depend on the class
+    fn word(&self, offset: usize) -> Option<u64> {
+        if self.is64 {
+            self.endianness.u64(self.data, offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    // slice of the file, checking bounds
+    fn slice(&self, offset: u64, size: u64) -> Option<&'a [u8]> {
+        let start = usize::try_from(offset).ok()?;
+        let end = start.checked_add(usize::try_from(size).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    fn sections(&self) -> Option<Vec<SectionHeader>> {
+        let (shoff, shentsize, shnum) = if self.is64 {
+            (self.word(40)?, self.u16(58)?, self.u16(60)?)
+        } else {
+            (self.word(32)?, self.u16(46)?, self.u16(48)?)
+        };
+
+        if shoff == 0 || shnum == 0 {
+            return Some(Vec::new());
+        }
+
+        let mut sections = Vec::with_capacity(shnum as usize);
+        for i in 0..shnum as u64 {
+            let pos = usize::try_from(shoff.checked_add(i * shentsize as u64)?).ok()?;
+            let sh = if self.is64 {
+                SectionHeader {
+                    name: self.u32(pos)?,
+                    r#type: self.u32(pos + 4)?,
+                    offset: self.word(pos + 24)?,
+                    size: self.word(pos + 32)?,
+                    link: self.u32(pos + 40)?,
+                    entsize: self.word(pos + 56)?,
+                }
+            } else {
+                SectionHeader {
+                    name: self.u32(pos)?,
+                    r#type: self.u32(pos + 4)?,
+                    offset: self.word(pos + 16)?,
+                    size: self.word(pos + 20)?,
+                    link: self.u32(pos + 24)?,
+                    entsize: self.word(pos + 36)?,
+                }
+            };
+            sections.push(sh);
+        }
+
+        Some(sections)
+    }
+
+    // look for the PT_INTERP segment
+    fn interpreter(&self) -> Option<String> {
+        let (phoff, phentsize, phnum) = if self.is64 {
+            (self.word(32)?, self.u16(54)?, self.u16(56)?)
+        } else {
+            (self.word(28)?, self.u16(42)?, self.u16(44)?)
+        };
+
+        for i in 0..phnum as u64 {
+            let pos = usize::try_from(phoff.checked_add(i * phentsize as u64)?).ok()?;
+            if self.u32(pos)? != PT_INTERP {
+                continue;
+            }
+
+            let (offset, size) = if self.is64 {
+                (self.word(pos + 8)?, self.word(pos + 32)?)
+            } else {
+                (self.word(pos + 4)?, self.word(pos + 16)?)
+            };
+            return Some(cstring(self.slice(offset, size)?, 0));
+        }
+
+        None
+    }
+
+    // DT_NEEDED entries of the dynamic section, names being in the linked string table
+    fn needed(&self, dynamic: &SectionHeader, sections: &[SectionHeader]) -> Vec<String> {
+        let strtab = sections
+            .get(dynamic.link as usize)
+            .and_then(|s| self.slice(s.offset, s.size));
+        let (Some(strtab), Some(data)) = (strtab, self.slice(dynamic.offset, dynamic.size)) else {
+            return Vec::new();
+        };
+
+        let entsize = if self.is64 { 16 } else { 8 };
+        let mut needed = Vec::new();
+
+        for entry in data.chunks_exact(entsize) {
+            let (tag, val) = if self.is64 {
+                (self.endianness.u64(entry, 0), self.endianness.u64(entry, 8))
+            } else {
+                (
+                    self.endianness.u32(entry, 0).map(u64::from),
+                    self.endianness.u32(entry, 4).map(u64::from),
+                )
+            };
+
+            match (tag, val) {
+                (Some(DT_NULL), _) | (None, _) => break,
+                (Some(DT_NEEDED), Some(val)) => needed.push(cstring(strtab, val as usize)),
+                _ => (),
+            }
+        }
+
+        needed
+    }
+
+    // walk through notes to find the GNU build-id
+    fn build_id(&self, note: &SectionHeader) -> Option<String> {
+        let data = self.slice(note.offset, note.size)?;
+        let mut pos = 0usize;
+
+        while pos + 12 <= data.len() {
+            let namesz = self.endianness.u32(data, pos)? as usize;
+            let descsz = self.endianness.u32(data, pos + 4)? as usize;
+            let r#type = self.endianness.u32(data, pos + 8)?;
+
+            let name_start = pos + 12;
+            let desc_start = name_start.checked_add(align4(namesz))?;
+            let name = data.get(name_start..name_start.checked_add(namesz)?)?;
+            let desc = data.get(desc_start..desc_start.checked_add(descsz)?)?;
+
+            if r#type == NT_GNU_BUILD_ID && name == b"GNU\0" {
+                return Some(desc.iter().map(|b| format!("{b:02x}")).collect());
+            }
+
+            pos = desc_start.checked_add(align4(descsz))?;
+        }
+
+        None
+    }
+}
+
+impl TryFrom<&[u8]> for ElfMeta {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let elf = Elf::new(bytes).ok_or(anyhow::anyhow!("invalid ELF ident"))?;
+        let truncated = || anyhow::anyhow!("truncated ELF header");
+
+        let mut meta = ElfMeta {
+            class: if elf.is64 { 64 } else { 32 },
+            endianness: match elf.endianness {
+                Endianness::LittleEndian => "little",
+                Endianness::BigEndian => "big",
+            },
+            machine: machine(elf.u16(18).ok_or_else(truncated)?),
+            r#type: match elf.u16(16).ok_or_else(truncated)? {
+                1 => "rel",
+                2 => "exec",
+                3 => "dyn",
+                4 => "core",
+                _ => "unknown",
+            },
+            entry: elf.word(24).ok_or_else(truncated)?,
+            interpreter: elf.interpreter(),
+            ..Default::default()
+        };
+
+        // section names are found in the section whose index is e_shstrndx
+        let sections = elf.sections().unwrap_or_default();
+        let shstrndx = if elf.is64 { elf.u16(62) } else { elf.u16(50) };
+        let shstrtab = shstrndx
+            .and_then(|i| sections.get(i as usize))
+            .and_then(|s| elf.slice(s.offset, s.size));
+
+        for sh in &sections {
+            match sh.r#type {
+                SHT_SYMTAB if sh.entsize != 0 => meta.symbols += sh.size / sh.entsize,
+                SHT_DYNSYM if sh.entsize != 0 => meta.dynamic_symbols += sh.size / sh.entsize,
+                SHT_DYNAMIC => meta.needed = elf.needed(sh, &sections),
+                SHT_NOTE if meta.build_id.is_none() => meta.build_id = elf.build_id(sh),
+                _ => (),
+            }
+
+            meta.sections.push(ElfSection {
+                name: shstrtab
+                    .map(|t| cstring(t, sh.name as usize))
+                    .unwrap_or_default(),
+                r#type: sh.r#type,
+                offset: sh.offset,
+                size: sh.size,
+            });
+        }
+
+        meta.stripped = !sections.iter().any(|s| s.r#type == SHT_SYMTAB);
+
+        Ok(meta)
+    }
+}
+
+// read a NUL terminated string starting at offset
+fn cstring(bytes: &[u8], offset: usize) -> String {
+    let s = bytes.get(offset..).unwrap_or_default();
+    let end = s.iter().position(|b| *b == 0).unwrap_or(s.len());
+    String::from_utf8_lossy(&s[..end]).into_owned()
+}
+
+// notes name and descriptor are 4-byte aligned
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+// most common e_machine values
+fn machine(value: u16) -> String {
+    match value {
+        2 => "SPARC",
+        3 => "x86",
+        8 => "MIPS",
+        20 => "PowerPC",
+        21 => "PowerPC64",
+        22 => "S390",
+        40 => "ARM",
+        42 => "SuperH",
+        43 => "SPARCv9",
+        50 => "IA-64",
+        62 => "x86-64",
+        183 => "AArch64",
+        243 => "RISC-V",
+        258 => "LoongArch",
+        _ => return format!("unknown({value})"),
+    }
+    .to_string()
+}
@@ -8,6 +8,7 @@ const SIGN_GIF87a: FileSignature = FileSignature {
     header: b"\x47\x49\x46\x38\x37\x61",
     footer: None,
     mime: "GIF87a",
+    extensions: &["gif"],
     endianness: Endianness::LittleEndian,
 };
 
@@ -16,6 +17,7 @@ const SIGN_GIF89a: FileSignature = FileSignature {
     header: b"\x47\x49\x46\x38\x39\x61",
     footer: None,
     mime: "GIF89a",
+    extensions: &["gif"],
     endianness: Endianness::LittleEndian,
 };
 
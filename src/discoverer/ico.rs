@@ -13,6 +13,7 @@ const SIGNATURE: FileSignature = FileSignature {
     header: b"\x00\x00\x01\x00",
     footer: None,
     mime: "ico",
+    extensions: &["ico", "cur"],
     endianness: Endianness::LittleEndian,
 };
 
@@ -0,0 +1,305 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::{
+    discoverer::{Endianness, FileSignature},
+    impl_discoverer,
+};
+
+//-------------------------------------------------------------------------------------------
+// JPEG
+//-------------------------------------------------------------------------------------------
+const SIGNATURE: FileSignature = FileSignature {
+    header: b"\xFF\xD8\xFF",
+    footer: None,
+    mime: "jpeg",
+    extensions: &["jpg", "jpeg", "jpe", "jfif"],
+    endianness: Endianness::BigEndian,
+};
+
+impl_discoverer!(JPEG, SIGNATURE);
+
+// JPEG markers we're interested in
+const APP1: u8 = 0xE1;
+const SOS: u8 = 0xDA;
+const EOI: u8 = 0xD9;
+
+// EXIF tags
+const TAG_MAKE: u16 = 0x010F;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_SOFTWARE: u16 = 0x0131;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATETIME_ORIGINAL: u16 = 0x9003;
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+
+// TIFF field types
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_RATIONAL: u16 = 5;
+
+// for some types, we'll not call the FileSignature trait's metadata()
+// but a custom one, as Rust doesn't yet support trait's method specialization
+#[derive(Debug, Default, Serialize)]
+pub struct ExifMeta {
+    // camera manufacturer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    make: Option<String>,
+
+    // camera model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+
+    // capture time, converted to ISO 8601 when possible
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_time_original: Option<String>,
+
+    // 1 = normal, 3 = 180°, 6 = 90° CW, 8 = 90° CCW (others are mirrored)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orientation: Option<u16>,
+
+    // software used to produce or edit the image
+    #[serde(skip_serializing_if = "Option::is_none")]
+    software: Option<String>,
+
+    // decimal degrees, negative for South
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gps_latitude: Option<f64>,
+
+    // decimal degrees, negative for West
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gps_longitude: Option<f64>,
+}
+
+impl JPEG {
+    // walk through JPEG segments to find the EXIF APP1 segment and decode it
+    pub fn exif(bytes: &[u8]) -> Option<serde_json::Value> {
+        let tiff = exif_segment(bytes)?;
+        let meta = ExifMeta::from_tiff(tiff)?;
+        serde_json::to_value(meta).ok()
+    }
+}
+
+// return the TIFF data embedded in the APP1 segment if any
+fn exif_segment(bytes: &[u8]) -> Option<&[u8]> {
+    // skip SOI
+    let mut offset = 2usize;
+
+    loop {
+        if *bytes.get(offset)? != 0xFF {
+            return None;
+        }
+
+        // markers can be padded with any number of 0xFF
+        let mut marker = *bytes.get(offset + 1)?;
+        while marker == 0xFF {
+            offset += 1;
+            marker = *bytes.get(offset + 1)?;
+        }
+        offset += 2;
+
+        match marker {
+            // no EXIF before image data
+            SOS | EOI => return None,
+
+            // standalone markers: no length
+            0x01 | 0xD0..=0xD7 => continue,
+
+            _ => {
+                // length includes the 2 length bytes
+                let length = Endianness::BigEndian.u16(bytes, offset)? as usize;
+                if length < 2 {
+                    return None;
+                }
+                let payload = bytes.get(offset + 2..offset + length)?;
+
+                if marker == APP1 && payload.starts_with(b"Exif\x00\x00") {
+                    return Some(&payload[6..]);
+                }
+
+                offset += length;
+            }
+        }
+    }
+}
+
+// a single IFD entry
+struct IfdEntry {
+    tag: u16,
+    r#type: u16,
+    count: u32,
+
+    // offset of the 4-byte value/offset field within the TIFF data
+    field: usize,
+}
+
+// TIFF structure found in EXIF data: endianness is only known when reading the header
+struct Tiff<'a> {
+    data: &'a [u8],
+    endianness: Endianness,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let endianness = match data.get(0..2)? {
+            b"II" => Endianness::LittleEndian,
+            b"MM" => Endianness::BigEndian,
+            _ => return None,
+        };
+
+        // magic number
+        if endianness.u16(data, 2)? != 42 {
+            return None;
+        }
+
+        Some(Self { data, endianness })
+    }
+
+    // offset of the first IFD
+    fn ifd0(&self) -> Option<usize> {
+        self.endianness.u32(self.data, 4).map(|x| x as usize)
+    }
+
+    // read all entries of the IFD at offset
+    fn ifd(&self, offset: usize) -> Option<Vec<IfdEntry>> {
+        let count = self.endianness.u16(self.data, offset)? as usize;
+
+        // don't trust count blindly
+        if offset + 2 + count * 12 > self.data.len() {
+            return None;
+        }
+
+        let entries = (0..count)
+            .filter_map(|i| {
+                let pos = offset + 2 + i * 12;
+                Some(IfdEntry {
+                    tag: self.endianness.u16(self.data, pos)?,
+                    r#type: self.endianness.u16(self.data, pos + 2)?,
+                    count: self.endianness.u32(self.data, pos + 4)?,
+                    field: pos + 8,
+                })
+            })
+            .collect();
+
+        Some(entries)
+    }
+
+    // values are stored inline if they fit into 4 bytes, otherwise the field is an offset
+    fn value(&self, entry: &IfdEntry, size: usize) -> Option<&'a [u8]> {
+        let len = size.checked_mul(entry.count as usize)?;
+        let start = if len <= 4 {
+            entry.field
+        } else {
+            self.endianness.u32(self.data, entry.field)? as usize
+        };
+        self.data.get(start..start.checked_add(len)?)
+    }
+
+    fn ascii(&self, entry: &IfdEntry) -> Option<String> {
+        if entry.r#type != TYPE_ASCII {
+            return None;
+        }
+        let raw = self.value(entry, 1)?;
+        let s = String::from_utf8_lossy(raw)
+            .trim_end_matches('\0')
+            .trim()
+            .to_string();
+        (!s.is_empty()).then_some(s)
+    }
+
+    fn integer(&self, entry: &IfdEntry) -> Option<u32> {
+        match entry.r#type {
+            TYPE_SHORT => self.endianness.u16(self.data, entry.field).map(u32::from),
+            TYPE_LONG => self.endianness.u32(self.data, entry.field),
+            _ => None,
+        }
+    }
+
+    fn rationals(&self, entry: &IfdEntry) -> Option<Vec<f64>> {
+        if entry.r#type != TYPE_RATIONAL {
+            return None;
+        }
+        let raw = self.value(entry, 8)?;
+        raw.chunks_exact(8)
+            .map(|c| {
+                let num = self.endianness.u32(c, 0)?;
+                let den = self.endianness.u32(c, 4)?;
+                (den != 0).then(|| num as f64 / den as f64)
+            })
+            .collect()
+    }
+
+    // GPS coordinates are stored as degrees, minutes, seconds with a N/S or E/W reference
+    fn coordinate(&self, gps: &[IfdEntry], value_tag: u16, ref_tag: u16) -> Option<f64> {
+        let dms = self.rationals(gps.iter().find(|e| e.tag == value_tag)?)?;
+        let [deg, min, sec] = dms.as_slice() else {
+            return None;
+        };
+        let decimal = deg + min / 60.0 + sec / 3600.0;
+
+        match gps
+            .iter()
+            .find(|e| e.tag == ref_tag)
+            .and_then(|e| self.ascii(e))
+            .as_deref()
+        {
+            Some("S") | Some("W") => Some(-decimal),
+            _ => Some(decimal),
+        }
+    }
+}
+
+impl ExifMeta {
+    // decode IFD0 and the EXIF and GPS sub-IFDs
+    fn from_tiff(data: &[u8]) -> Option<Self> {
+        let tiff = Tiff::new(data)?;
+        let ifd0 = tiff.ifd(tiff.ifd0()?)?;
+
+        let mut meta = ExifMeta::default();
+        let mut exif_ifd = None;
+        let mut gps_ifd = None;
+
+        for entry in &ifd0 {
+            match entry.tag {
+                TAG_MAKE => meta.make = tiff.ascii(entry),
+                TAG_MODEL => meta.model = tiff.ascii(entry),
+                TAG_SOFTWARE => meta.software = tiff.ascii(entry),
+                TAG_ORIENTATION => meta.orientation = tiff.integer(entry).map(|x| x as u16),
+                TAG_EXIF_IFD => exif_ifd = tiff.integer(entry),
+                TAG_GPS_IFD => gps_ifd = tiff.integer(entry),
+                _ => (),
+            }
+        }
+
+        // capture time is in the EXIF sub-IFD
+        if let Some(entries) = exif_ifd.and_then(|offset| tiff.ifd(offset as usize)) {
+            meta.date_time_original = entries
+                .iter()
+                .find(|e| e.tag == TAG_DATETIME_ORIGINAL)
+                .and_then(|e| tiff.ascii(e))
+                .map(|dt| iso8601(&dt));
+        }
+
+        if let Some(entries) = gps_ifd.and_then(|offset| tiff.ifd(offset as usize)) {
+            meta.gps_latitude = tiff.coordinate(&entries, TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF);
+            meta.gps_longitude =
+                tiff.coordinate(&entries, TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF);
+        }
+
+        Some(meta)
+    }
+}
+
+// EXIF dates are "YYYY:MM:DD HH:MM:SS": convert to ISO 8601 to ease SQL queries
+// or keep as is if not conforming
+fn iso8601(dt: &str) -> String {
+    match NaiveDateTime::parse_from_str(dt, "%Y:%m:%d %H:%M:%S") {
+        Ok(ndt) => ndt.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        Err(_) => dt.to_string(),
+    }
+}
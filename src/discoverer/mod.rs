@@ -1,18 +1,19 @@
-use std::{io::Read, sync::LazyLock};
+use std::{sync::LazyLock, time::SystemTime};
 
 use bincode::{
-    config::{BigEndian, Config, Configuration, Fixint, LittleEndian},
+    config::{BigEndian, Configuration, Fixint, LittleEndian},
     Decode,
 };
-use hex_literal::hex;
-use serde::{Deserialize, Serialize};
-use serde_json::{Result, Value};
+use serde::Serialize;
 
 // all modules corresponding to file types here
 // #tag
 pub mod bmp;
+pub mod elf;
 pub mod gif;
 pub mod ico;
+pub mod jpeg;
+pub mod pe;
 pub mod png;
 pub mod regf;
 pub mod sqlite3;
@@ -34,21 +35,68 @@ pub static LITTLEENDIAN_CONFIG: LazyLock<Configuration<LittleEndian, Fixint>> =
             .with_fixed_int_encoding()
     });
 
+// a member extracted in memory from an archive, to be analyzed as a virtual artefact
+pub struct ArchiveMember {
+    // path inside the archive
+    pub name: String,
+
+    // directory entry, no data
+    pub is_dir: bool,
+
+    // last modification time if stored in the archive
+    pub modified: Option<SystemTime>,
+
+    // uncompressed data
+    pub data: Vec<u8>,
+}
+
 pub struct FileSignature {
     header: &'static [u8],
     footer: Option<&'static [u8]>,
     mime: &'static str,
+
+    // extensions usually found for this type, "" meaning no extension
+    extensions: &'static [&'static str],
     endianness: Endianness,
 }
 
 // to get some metadat, we have to know whether integers are stored
 // using big or little endian. E.g: PNG uses big endian
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum Endianness {
     BigEndian,
     LittleEndian,
 }
 
+// some formats (TIFF, ELF, ...) only know their endianness at runtime, so bincode
+// static configs can't be used. These read integers at a given offset, and return None
+// if the slice is too short
+impl Endianness {
+    fn u16(&self, bytes: &[u8], offset: usize) -> Option<u16> {
+        let buf: [u8; 2] = bytes.get(offset..offset.checked_add(2)?)?.try_into().ok()?;
+        match self {
+            Self::BigEndian => Some(u16::from_be_bytes(buf)),
+            Self::LittleEndian => Some(u16::from_le_bytes(buf)),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8], offset: usize) -> Option<u32> {
+        let buf: [u8; 4] = bytes.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+        match self {
+            Self::BigEndian => Some(u32::from_be_bytes(buf)),
+            Self::LittleEndian => Some(u32::from_le_bytes(buf)),
+        }
+    }
+
+    fn u64(&self, bytes: &[u8], offset: usize) -> Option<u64> {
+        let buf: [u8; 8] = bytes.get(offset..offset.checked_add(8)?)?.try_into().ok()?;
+        match self {
+            Self::BigEndian => Some(u64::from_be_bytes(buf)),
+            Self::LittleEndian => Some(u64::from_le_bytes(buf)),
+        }
+    }
+}
+
 // a trait for tyring to discover file types using magic numbers
 pub trait Discoverer<'a> {
     const FILE_SIGNATURE: FileSignature;
@@ -68,6 +116,27 @@ pub trait Discoverer<'a> {
     }
 }
 
+// extensions expected for a discovered mime type
+pub fn extensions(mime: &str) -> Option<&'static [&'static str]> {
+    [
+        bmp::BMP::FILE_SIGNATURE,
+        elf::ELF::FILE_SIGNATURE,
+        gif::GIF87a::FILE_SIGNATURE,
+        gif::GIF89a::FILE_SIGNATURE,
+        ico::ICO::FILE_SIGNATURE,
+        jpeg::JPEG::FILE_SIGNATURE,
+        pe::PE::FILE_SIGNATURE,
+        png::PNG::FILE_SIGNATURE,
+        regf::REGF::FILE_SIGNATURE,
+        sqlite3::SQLITE3::FILE_SIGNATURE,
+        wav::WAV::FILE_SIGNATURE,
+        zip::ZIP::FILE_SIGNATURE,
+    ]
+    .into_iter()
+    .find(|fs| fs.mime == mime)
+    .map(|fs| fs.extensions)
+}
+
 // a macro for defining impl Discoverer
 #[macro_export]
 macro_rules! impl_discoverer {
@@ -75,7 +144,7 @@ macro_rules! impl_discoverer {
     // Sign = tuple containing signatures
     ($Struct:ident, $Sign:ident) => {
         pub struct $Struct;
-        impl<'a> crate::discoverer::Discoverer<'a> for $Struct {
+        impl<'a> $crate::discoverer::Discoverer<'a> for $Struct {
             const FILE_SIGNATURE: FileSignature = $Sign;
 
             fn mime(bytes: &'a [u8]) -> Option<&'static str> {
@@ -109,8 +178,13 @@ macro_rules! impl_discoverer {
 
 #[cfg(test)]
 mod tests {
+    use crate::discoverer::elf::ELF;
+    use crate::discoverer::jpeg::JPEG;
+    use crate::discoverer::pe::PE;
     use crate::discoverer::png::PNG;
     use crate::discoverer::sqlite3::SQLITE3;
+    use crate::discoverer::zip::ZIP;
+    use crate::memory::MappedFile;
 
     use super::*;
 
@@ -135,4 +209,216 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn jpeg() -> anyhow::Result<()> {
+        let buffer = std::fs::read("tests/test.jpg")?;
+        assert_eq!(JPEG::mime(&buffer), Some("jpeg"));
+
+        // test.jpg is JFIF only
+        assert!(JPEG::exif(&buffer).is_none());
+
+        let buffer = std::fs::read("tests/test.png")?;
+        assert!(JPEG::mime(&buffer).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn exif() {
+        // big endian IFD entry
+        fn entry(tag: u16, r#type: u16, count: u32, value: [u8; 4]) -> Vec<u8> {
+            [
+                &tag.to_be_bytes()[..],
+                &r#type.to_be_bytes(),
+                &count.to_be_bytes(),
+                &value,
+            ]
+            .concat()
+        }
+        fn rational(dms: [u32; 3]) -> Vec<u8> {
+            dms.iter()
+                .flat_map(|x| [x.to_be_bytes(), 1u32.to_be_bytes()].concat())
+                .collect()
+        }
+
+        let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+
+        // IFD0 at 8: Make, EXIF IFD, GPS IFD
+        tiff.extend(3u16.to_be_bytes());
+        tiff.extend(entry(0x010F, 2, 6, 50u32.to_be_bytes()));
+        tiff.extend(entry(0x8769, 4, 1, 56u32.to_be_bytes()));
+        tiff.extend(entry(0x8825, 4, 1, 94u32.to_be_bytes()));
+        tiff.extend(0u32.to_be_bytes());
+        tiff.extend(b"Canon\x00");
+
+        // EXIF IFD at 56
+        tiff.extend(1u16.to_be_bytes());
+        tiff.extend(entry(0x9003, 2, 20, 74u32.to_be_bytes()));
+        tiff.extend(0u32.to_be_bytes());
+        tiff.extend(b"2021:06:15 10:20:30\x00");
+
+        // GPS IFD at 94
+        tiff.extend(4u16.to_be_bytes());
+        tiff.extend(entry(1, 2, 2, *b"N\x00\x00\x00"));
+        tiff.extend(entry(2, 5, 3, 148u32.to_be_bytes()));
+        tiff.extend(entry(3, 2, 2, *b"W\x00\x00\x00"));
+        tiff.extend(entry(4, 5, 3, 172u32.to_be_bytes()));
+        tiff.extend(0u32.to_be_bytes());
+        tiff.extend(rational([48, 51, 30]));
+        tiff.extend(rational([2, 17, 42]));
+
+        // SOI + APP1 + EOI
+        let mut jpeg = b"\xFF\xD8\xFF\xE1".to_vec();
+        jpeg.extend((tiff.len() as u16 + 8).to_be_bytes());
+        jpeg.extend(b"Exif\x00\x00");
+        jpeg.extend(tiff);
+        jpeg.extend(b"\xFF\xD9");
+
+        let meta = JPEG::exif(&jpeg).unwrap();
+        assert_eq!(meta["make"], "Canon");
+        assert_eq!(meta["date_time_original"], "2021-06-15T10:20:30");
+        assert_eq!(meta["gps_latitude"].as_f64().unwrap(), 48.858333333333334);
+        assert_eq!(meta["gps_longitude"].as_f64().unwrap(), -2.295);
+        assert!(meta.get("model").is_none());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn elf() -> anyhow::Result<()> {
+        // the test binary itself is an ELF file
+        let buffer = std::fs::read(std::env::current_exe()?)?;
+        assert_eq!(ELF::mime(&buffer), Some("elf"));
+
+        let meta = ELF::elf(&buffer).unwrap();
+        assert!(meta["type"] == "dyn" || meta["type"] == "exec");
+        assert_eq!(meta["class"], 8 * std::mem::size_of::<usize>());
+        assert!(meta["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|s| s["name"] == ".text"));
+
+        let buffer = std::fs::read("tests/test.png")?;
+        assert!(ELF::mime(&buffer).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn pe() -> anyhow::Result<()> {
+        // minimal PE32+ importing KERNEL32!ExitProcess
+        let mut pe = vec![0u8; 0x400];
+        pe[0..2].copy_from_slice(b"MZ");
+        pe[0x3C] = 0x40;
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+
+        // COFF header: AMD64, 1 section, SizeOfOptionalHeader = 240, EXECUTABLE_IMAGE
+        pe[0x44..0x46].copy_from_slice(&0x8664u16.to_le_bytes());
+        pe[0x46] = 1;
+        pe[0x48..0x4C].copy_from_slice(&1623752430u32.to_le_bytes());
+        pe[0x54] = 240;
+        pe[0x56] = 0x22;
+
+        // optional header: PE32+, console, 16 data directories, import directory
+        pe[0x58..0x5A].copy_from_slice(&0x20Bu16.to_le_bytes());
+        pe[0x58 + 68] = 3;
+        pe[0x58 + 108] = 16;
+        pe[0x58 + 120..0x58 + 124].copy_from_slice(&0x1000u32.to_le_bytes());
+        pe[0x58 + 124] = 40;
+
+        // section table: .idata VA 0x1000 mapped at 0x200
+        let sh = 0x58 + 240;
+        pe[sh..sh + 6].copy_from_slice(b".idata");
+        for (i, v) in [0x200u32, 0x1000, 0x200, 0x200].iter().enumerate() {
+            pe[sh + 8 + i * 4..sh + 12 + i * 4].copy_from_slice(&v.to_le_bytes());
+        }
+
+        // import descriptor, thunks, DLL name and hint/name
+        pe[0x200..0x204].copy_from_slice(&0x1028u32.to_le_bytes());
+        pe[0x20C..0x210].copy_from_slice(&0x1050u32.to_le_bytes());
+        pe[0x210..0x214].copy_from_slice(&0x1028u32.to_le_bytes());
+        pe[0x228..0x22C].copy_from_slice(&0x1060u32.to_le_bytes());
+        pe[0x250..0x25C].copy_from_slice(b"KERNEL32.dll");
+        pe[0x262..0x26D].copy_from_slice(b"ExitProcess");
+
+        assert_eq!(PE::mime(&pe), Some("pe"));
+        let meta = PE::pe(&pe).unwrap();
+        assert_eq!(meta["machine"], "AMD64");
+        assert_eq!(meta["subsystem"], "windows_cui");
+        assert_eq!(meta["compile_time"], "2021-06-15T10:20:30");
+        assert_eq!(meta["sections"][0]["name"], ".idata");
+        assert_eq!(meta["imports"][0]["dll"], "KERNEL32.dll");
+        assert_eq!(meta["imports"][0]["functions"][0], "ExitProcess");
+        assert_eq!(meta["imphash"], "f9ade0aa18f660a34a4fa23392e21838");
+        assert_eq!(meta["signed"], false);
+        assert_eq!(MappedFile::from(pe.clone()).discover().0, Some("pe"));
+
+        // 4000 descriptors sharing 4096 ordinal thunks at 0x14000, named A.dll
+        let mut many = pe.clone();
+        many.resize(0x1D000, 0);
+        let size = (0x1D000u32 - 0x200).to_le_bytes();
+        many[sh + 8..sh + 12].copy_from_slice(&size);
+        many[sh + 16..sh + 20].copy_from_slice(&size);
+        for i in 0..4000 {
+            let d = 0x200 + i * 20;
+            many[d..d + 4].copy_from_slice(&(0x14000u32 + 0xE00).to_le_bytes());
+            many[d + 12..d + 16].copy_from_slice(&(0x1C100u32 + 0xE00).to_le_bytes());
+        }
+        for t in many[0x14000..0x1C000].chunks_exact_mut(8) {
+            t.copy_from_slice(&(1u64 << 63 | 1).to_le_bytes());
+        }
+        many[0x1C100..0x1C105].copy_from_slice(b"A.dll");
+        let meta = PE::pe(&many).unwrap();
+        let imports = meta["imports"].as_array().unwrap();
+        let functions: usize = imports
+            .iter()
+            .map(|i| i["functions"].as_array().unwrap().len())
+            .sum();
+        assert_eq!(imports[0]["functions"][0], "ord1");
+        assert_eq!(imports.len() + functions, 4096);
+
+        // DOS stub only: not reported as PE
+        pe[0x40] = 0;
+        assert!(PE::pe(&pe).is_none());
+        assert_eq!(MappedFile::from(pe).discover(), (None, None));
+        let text = MappedFile::from(b"MZ is a text file".to_vec());
+        assert!(text.discover().0.is_none());
+
+        let buffer = std::fs::read("tests/test.png")?;
+        assert!(PE::mime(&buffer).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn zip_members() -> anyhow::Result<()> {
+        use std::io::Write;
+
+        // build a zip with a directory and 2 files
+        let mut writer = ::zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = ::zip::write::SimpleFileOptions::default();
+        writer.add_directory("inner/", options)?;
+        writer.start_file("inner/test.png", options)?;
+        writer.write_all(&std::fs::read("tests/test.png")?)?;
+        writer.start_file("readme.txt", options)?;
+        writer.write_all(&[b'A'; 1000])?;
+        let buffer = writer.finish()?.into_inner();
+
+        let mut budget = 10_000;
+        let members = ZIP::members(&buffer, &mut budget)?;
+        assert_eq!(members.len(), 3);
+        assert!(members[0].is_dir);
+        assert_eq!(members[1].name, "inner/test.png");
+        assert_eq!(PNG::mime(&members[1].data), Some("png"));
+        assert_eq!(budget, 10_000 - 363 - 1000);
+
+        // extraction stops when budget is exhausted
+        let mut budget = 500;
+        let members = ZIP::members(&buffer, &mut budget)?;
+        assert_eq!(members.len(), 2);
+        assert_eq!(budget, 0);
+
+        Ok(())
+    }
 }
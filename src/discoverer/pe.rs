@@ -0,0 +1,421 @@
+use chrono::DateTime;
+use md5::{Digest, Md5};
+use serde::Serialize;
+
+use crate::{
+    discoverer::{Endianness, FileSignature},
+    impl_discoverer,
+};
+
+//-------------------------------------------------------------------------------------------
+// PE
+//-------------------------------------------------------------------------------------------
+const SIGNATURE: FileSignature = FileSignature {
+    header: b"MZ",
+    footer: None,
+    mime: "pe",
+    extensions: &[
+        "exe", "dll", "sys", "scr", "cpl", "ocx", "drv", "efi", "mui", "com", "winmd",
+    ],
+    endianness: Endianness::LittleEndian,
+};
+
+impl_discoverer!(PE, SIGNATURE);
+
+// optional header magic
+const PE32: u16 = 0x10B;
+const PE32_PLUS: u16 = 0x20B;
+
+// data directories indexes
+const DIRECTORY_EXPORT: usize = 0;
+const DIRECTORY_IMPORT: usize = 1;
+const DIRECTORY_SECURITY: usize = 4;
+
+// don't loop forever on corrupted files. Imported DLLs and functions share the
+// same budget, as descriptors could all point to the same thunks
+const MAX_IMPORTS: usize = 4096;
+const MAX_EXPORTS: usize = 65536;
+
+// COFF characteristics flags
+const CHARACTERISTICS: [(u16, &str); 7] = [
+    (0x0001, "relocs_stripped"),
+    (0x0002, "executable_image"),
+    (0x0020, "large_address_aware"),
+    (0x0100, "32bit_machine"),
+    (0x0200, "debug_stripped"),
+    (0x1000, "system"),
+    (0x2000, "dll"),
+];
+
+// for some types, we'll not call the FileSignature trait's metadata()
+// but a custom one, as Rust doesn't yet support trait's method specialization
+#[derive(Debug, Default, Serialize)]
+pub struct PeMeta {
+    // target architecture
+    machine: String,
+
+    // "PE32" or "PE32+"
+    format: &'static str,
+
+    // link time, as found in the COFF header (could be forged)
+    compile_time: Option<String>,
+
+    // windows_gui, windows_cui, native, ...
+    subsystem: String,
+
+    // raw COFF characteristics
+    characteristics: u16,
+
+    // decoded characteristics
+    flags: Vec<&'static str>,
+
+    // list of sections
+    sections: Vec<PeSection>,
+
+    // imported functions per DLL
+    imports: Vec<PeImport>,
+
+    // exported function names
+    exports: Vec<String>,
+
+    // imphash as computed by pefile (without ordinal resolution)
+    imphash: Option<String>,
+
+    // an Authenticode certificate table is present
+    signed: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct PeSection {
+    name: String,
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_size: u32,
+    characteristics: u32,
+    entropy: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct PeImport {
+    dll: String,
+    functions: Vec<String>,
+}
+
+// section header
+struct SectionHeader {
+    virtual_size: u32,
+    virtual_address: u32,
+    raw_size: u32,
+    raw_offset: u32,
+}
+
+// PE file once DOS and NT headers are validated
+struct Pe<'a> {
+    data: &'a [u8],
+    is64: bool,
+    sections: Vec<SectionHeader>,
+
+    // offset of the data directories
+    directories: usize,
+    nb_directories: usize,
+}
+
+impl PE {
+    // follow e_lfanew and decode the NT headers, sections, imports and exports
+    pub fn pe(bytes: &[u8]) -> Option<serde_json::Value> {
+        let meta = PeMeta::try_from(bytes).ok()?;
+        serde_json::to_value(meta).ok()
+    }
+}
+
+// PE is always little endian
+fn u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Endianness::LittleEndian.u16(bytes, offset)
+}
+
+fn u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Endianness::LittleEndian.u32(bytes, offset)
+}
+
+fn u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Endianness::LittleEndian.u64(bytes, offset)
+}
+
+impl<'a> Pe<'a> {
+    // convert a relative virtual address to a file offset using sections
+    fn offset(&self, rva: u32) -> Option<usize> {
+        self.sections
+            .iter()
+            .find(|s| {
+                let size = s.virtual_size.max(s.raw_size);
+                rva >= s.virtual_address && rva - s.virtual_address < size
+            })
+            .map(|s| (rva - s.virtual_address) as usize + s.raw_offset as usize)
+    }
+
+    // data directory (RVA, size)
+    fn directory(&self, index: usize) -> Option<(u32, u32)> {
+        if index >= self.nb_directories {
+            return None;
+        }
+        let pos = self.directories + index * 8;
+        let rva = u32(self.data, pos)?;
+        let size = u32(self.data, pos + 4)?;
+        (rva != 0 && size != 0).then_some((rva, size))
+    }
+
+    // NUL terminated string at a given RVA
+    fn string(&self, rva: u32) -> Option<String> {
+        let s = self.data.get(self.offset(rva)?..)?;
+        let end = s.iter().take(512).position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&s[..end]).into_owned())
+    }
+
+    fn imports(&self) -> Vec<PeImport> {
+        let mut imports = Vec::new();
+        let mut budget = MAX_IMPORTS;
+        let Some(mut pos) = self
+            .directory(DIRECTORY_IMPORT)
+            .and_then(|(rva, _)| self.offset(rva))
+        else {
+            return imports;
+        };
+
+        // array of IMAGE_IMPORT_DESCRIPTOR ended by a null one
+        while budget > 0 {
+            let (Some(oft), Some(name), Some(ft)) = (
+                u32(self.data, pos),
+                u32(self.data, pos + 12),
+                u32(self.data, pos + 16),
+            ) else {
+                break;
+            };
+            if name == 0 {
+                break;
+            }
+
+            // original first thunk could be zeroed by some linkers
+            let thunks = if oft != 0 { oft } else { ft };
+            budget -= 1;
+            imports.push(PeImport {
+                dll: self.string(name).unwrap_or_default(),
+                functions: self.functions(thunks, &mut budget),
+            });
+
+            pos += 20;
+        }
+
+        imports
+    }
+
+    // walk the thunk array: either ordinals or RVAs to IMAGE_IMPORT_BY_NAME
+    fn functions(&self, rva: u32, budget: &mut usize) -> Vec<String> {
+        let mut functions = Vec::new();
+        let Some(mut pos) = self.offset(rva) else {
+            return functions;
+        };
+
+        let (size, ordinal_flag) = if self.is64 {
+            (8, 1u64 << 63)
+        } else {
+            (4, 1u64 << 31)
+        };
+
+        while *budget > 0 {
+            let thunk = if self.is64 {
+                u64(self.data, pos)
+            } else {
+                u32(self.data, pos).map(u64::from)
+            };
+            match thunk {
+                None | Some(0) => break,
+                Some(t) if t & ordinal_flag != 0 => functions.push(format!("ord{}", t & 0xFFFF)),
+                Some(t) => functions.push(
+                    (t as u32)
+                        .checked_add(2)
+                        .and_then(|rva| self.string(rva))
+                        .unwrap_or_default(),
+                ),
+            }
+            *budget -= 1;
+            pos += size;
+        }
+
+        functions
+    }
+
+    fn exports(&self) -> Vec<String> {
+        let Some(pos) = self
+            .directory(DIRECTORY_EXPORT)
+            .and_then(|(rva, _)| self.offset(rva))
+        else {
+            return Vec::new();
+        };
+
+        let (Some(nb_names), Some(names)) = (u32(self.data, pos + 24), u32(self.data, pos + 32))
+        else {
+            return Vec::new();
+        };
+        let Some(names) = self.offset(names) else {
+            return Vec::new();
+        };
+
+        (0..(nb_names as usize).min(MAX_EXPORTS))
+            .map_while(|i| u32(self.data, names + i * 4))
+            .filter_map(|rva| self.string(rva))
+            .collect()
+    }
+}
+
+impl TryFrom<&[u8]> for PeMeta {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let invalid = || anyhow::anyhow!("invalid PE header");
+
+        // DOS header gives the offset of the NT headers
+        let e_lfanew = u32(bytes, 0x3C).ok_or_else(invalid)? as usize;
+        if bytes.get(e_lfanew..e_lfanew + 4) != Some(b"PE\0\0") {
+            return Err(invalid());
+        }
+
+        // COFF header
+        let coff = e_lfanew + 4;
+        let machine = u16(bytes, coff).ok_or_else(invalid)?;
+        let nb_sections = u16(bytes, coff + 2).ok_or_else(invalid)?;
+        let timestamp = u32(bytes, coff + 4).ok_or_else(invalid)?;
+        let optional_size = u16(bytes, coff + 16).ok_or_else(invalid)? as usize;
+        let characteristics = u16(bytes, coff + 18).ok_or_else(invalid)?;
+
+        // optional header
+        let optional = coff + 20;
+        let (format, is64, directories, nb_directories) =
+            match u16(bytes, optional).ok_or_else(invalid)? {
+                PE32 => ("PE32", false, optional + 96, u32(bytes, optional + 92)),
+                PE32_PLUS => ("PE32+", true, optional + 112, u32(bytes, optional + 108)),
+                _ => return Err(invalid()),
+            };
+        let subsystem = u16(bytes, optional + 68).ok_or_else(invalid)?;
+
+        // section table follows the optional header
+        let table = optional + optional_size;
+        let mut pe = Pe {
+            data: bytes,
+            is64,
+            sections: Vec::new(),
+            directories,
+            nb_directories: nb_directories.unwrap_or_default().min(16) as usize,
+        };
+
+        let mut meta = PeMeta {
+            machine: machine_name(machine),
+            format,
+            compile_time: DateTime::from_timestamp(timestamp as i64, 0)
+                .map(|dt| dt.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            subsystem: subsystem_name(subsystem),
+            characteristics,
+            flags: CHARACTERISTICS
+                .iter()
+                .filter(|(flag, _)| characteristics & flag != 0)
+                .map(|(_, name)| *name)
+                .collect(),
+            ..Default::default()
+        };
+
+        for i in 0..nb_sections as usize {
+            let pos = table + i * 40;
+            let Some(raw_name) = bytes.get(pos..pos + 8) else {
+                break;
+            };
+            let sh = SectionHeader {
+                virtual_size: u32(bytes, pos + 8).ok_or_else(invalid)?,
+                virtual_address: u32(bytes, pos + 12).ok_or_else(invalid)?,
+                raw_size: u32(bytes, pos + 16).ok_or_else(invalid)?,
+                raw_offset: u32(bytes, pos + 20).ok_or_else(invalid)?,
+            };
+
+            // entropy only on what is really in the file
+            let start = sh.raw_offset as usize;
+            let raw = bytes
+                .get(start..start.saturating_add(sh.raw_size as usize).min(bytes.len()))
+                .unwrap_or_default();
+
+            meta.sections.push(PeSection {
+                name: String::from_utf8_lossy(raw_name)
+                    .trim_end_matches('\0')
+                    .to_string(),
+                virtual_address: sh.virtual_address,
+                virtual_size: sh.virtual_size,
+                raw_size: sh.raw_size,
+                characteristics: u32(bytes, pos + 36).ok_or_else(invalid)?,
+                entropy: entropy::shannon_entropy(raw),
+            });
+            pe.sections.push(sh);
+        }
+
+        meta.imports = pe.imports();
+        meta.exports = pe.exports();
+        meta.imphash = imphash(&meta.imports);
+
+        // security directory holds a file offset to WIN_CERTIFICATE structures
+        meta.signed = pe.directory(DIRECTORY_SECURITY).is_some();
+
+        Ok(meta)
+    }
+}
+
+// MD5 of the comma separated list of lowercase "dll.function", dll without extension
+fn imphash(imports: &[PeImport]) -> Option<String> {
+    let list: Vec<_> = imports
+        .iter()
+        .flat_map(|imp| {
+            let dll = imp.dll.to_lowercase();
+            let dll = match dll.rsplit_once('.') {
+                Some((stem, "dll" | "ocx" | "sys")) => stem.to_string(),
+                _ => dll,
+            };
+            imp.functions
+                .iter()
+                .map(move |f| format!("{dll}.{}", f.to_lowercase()))
+        })
+        .collect();
+
+    if list.is_empty() {
+        return None;
+    }
+
+    let digest = Md5::digest(list.join(",").as_bytes());
+    Some(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+// most common machine values
+fn machine_name(value: u16) -> String {
+    match value {
+        0x014C => "i386",
+        0x0200 => "IA64",
+        0x01C0 => "ARM",
+        0x01C4 => "ARMNT",
+        0x8664 => "AMD64",
+        0xAA64 => "ARM64",
+        _ => return format!("unknown({value:#x})"),
+    }
+    .to_string()
+}
+
+fn subsystem_name(value: u16) -> String {
+    match value {
+        1 => "native",
+        2 => "windows_gui",
+        3 => "windows_cui",
+        7 => "posix_cui",
+        9 => "windows_ce_gui",
+        10 => "efi_application",
+        11 => "efi_boot_service_driver",
+        12 => "efi_runtime_driver",
+        13 => "efi_rom",
+        14 => "xbox",
+        16 => "windows_boot_application",
+        _ => return format!("unknown({value})"),
+    }
+    .to_string()
+}
@@ -13,6 +13,7 @@ const SIGNATURE: FileSignature = FileSignature {
     header: b"\x89\x50\x4E\x47\x0D\x0A\x1A\x0A",
     footer: Some(b"\xae\x42\x60\x82"),
     mime: "png",
+    extensions: &["png"],
     endianness: Endianness::BigEndian,
     //metafunc: Some(|x| &x[16..29]),
 };
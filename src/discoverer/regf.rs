@@ -14,11 +14,13 @@ const SIGNATURE: FileSignature = FileSignature {
     header: b"regf",
     footer: None,
     mime: "regf",
+    extensions: &["", "dat", "hve", "hiv", "sav"],
     endianness: Endianness::LittleEndian,
 };
 
 impl_discoverer!(REGF, SIGNATURE);
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Decode)]
 pub struct RegistryBaseBlock {
     // ASCII string
@@ -12,6 +12,7 @@ const SIGNATURE: FileSignature = FileSignature {
     header: b"\x53\x51\x4C\x69\x74\x65\x20\x66\x6F\x72\x6D\x61\x74\x20\x33\x00",
     footer: None,
     mime: "sqlite3",
+    extensions: &["", "db", "sqlite", "sqlite3", "db3", "sqlitedb"],
     endianness: Endianness::BigEndian,
 };
 
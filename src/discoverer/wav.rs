@@ -2,7 +2,7 @@ use bincode::Decode;
 use serde::Serialize;
 
 use crate::{
-    discoverer::{Endianness, FileSignature, LITTLEENDIAN_CONFIG},
+    discoverer::{Endianness, FileSignature},
     impl_discoverer,
 };
 
@@ -13,6 +13,7 @@ const SIGNATURE: FileSignature = FileSignature {
     header: b"RIFF",
     footer: None,
     mime: "wav",
+    extensions: &["wav", "wave"],
     endianness: Endianness::LittleEndian,
     // metafunc: Some(struct_decoder),
 };
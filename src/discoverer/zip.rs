@@ -1,11 +1,15 @@
-use std::io::Cursor;
+use std::{
+    io::{Cursor, Read},
+    time::SystemTime,
+};
 
-use bincode::Decode;
+use chrono::NaiveDate;
+use log::warn;
 use serde::Serialize;
 use zip::ZipArchive;
 
 use crate::{
-    discoverer::{Endianness, FileSignature},
+    discoverer::{ArchiveMember, Endianness, FileSignature},
     impl_discoverer,
 };
 
@@ -16,17 +20,15 @@ const SIGNATURE: FileSignature = FileSignature {
     header: b"PK\x03\x04",
     footer: None,
     mime: "zip",
+    extensions: &[
+        "zip", "jar", "war", "ear", "apk", "aar", "ipa", "docx", "xlsx", "pptx", "odt", "ods",
+        "odp", "epub", "xpi", "whl", "nupkg", "kmz", "vsix", "appx", "msix", "xps", "3mf",
+    ],
     endianness: Endianness::LittleEndian,
 };
 
 impl_discoverer!(ZIP, SIGNATURE);
 
-#[repr(C)]
-#[derive(Debug, Default, Serialize, Decode)]
-pub struct ZipFile {
-    pub files: Vec<String>,
-}
-
 // for some types, we'll not call the FileSignature trait's metadata()
 // but a custom one, as Rust doesn't yet support trait's method specialization
 #[derive(Debug, Serialize)]
@@ -58,4 +60,57 @@ impl ZIP {
 
         serde_json::to_value(v).ok()
     }
+
+    // extract all members in memory. budget is the number of bytes which can still be
+    // extracted: it's decreased for each member and extraction stops when exhausted
+    // to defend against zip bombs
+    pub fn members(bytes: &[u8], budget: &mut u64) -> anyhow::Result<Vec<ArchiveMember>> {
+        let mut members = Vec::new();
+
+        let c = Cursor::new(bytes);
+        let mut archive = ZipArchive::new(c)?;
+
+        for i in 0..archive.len() {
+            let entry = match archive.by_index(i) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("unable to read zip entry #{i}: {e}");
+                    continue;
+                }
+            };
+
+            // declared size can't be trusted, so read at most budget + 1 bytes
+            let name = entry.name().to_string();
+            let is_dir = entry.is_dir();
+            let modified = entry.last_modified().and_then(|dt| {
+                let ndt =
+                    NaiveDate::from_ymd_opt(dt.year().into(), dt.month().into(), dt.day().into())?
+                        .and_hms_opt(dt.hour().into(), dt.minute().into(), dt.second().into())?;
+                Some(SystemTime::from(ndt.and_utc()))
+            });
+
+            let mut data = Vec::new();
+            if let Err(e) = entry.take(budget.saturating_add(1)).read_to_end(&mut data) {
+                warn!("unable to extract zip entry '{name}': {e}");
+                continue;
+            }
+            if data.len() as u64 > *budget {
+                warn!(
+                    "extraction budget exhausted at zip entry '{name}', skipping remaining entries"
+                );
+                *budget = 0;
+                break;
+            }
+            *budget -= data.len() as u64;
+
+            members.push(ArchiveMember {
+                name,
+                is_dir,
+                modified,
+                data,
+            });
+        }
+
+        Ok(members)
+    }
 }
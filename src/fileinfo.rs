@@ -9,14 +9,33 @@ use diesel::pg::Pg;
 use diesel::serialize;
 use diesel::serialize::{Output, ToSql};
 use diesel::sql_types::Text;
-use diesel::Insertable;
+use diesel::{AsChangeset, Insertable, Queryable, Selectable};
 
-use crate::schema::{artefact, run_history};
+use crate::schema::{artefact, hash_set, partition, run_history};
 
-const FT_FILE: &'static str = "F";
-const FT_DIRECTORY: &'static str = "D";
-const FT_SYMLINK: &'static str = "S";
-const FT_UNKNOWN: &'static str = "U";
+const FT_FILE: &str = "F";
+const FT_DIRECTORY: &str = "D";
+const FT_SYMLINK: &str = "S";
+const FT_UNKNOWN: &str = "U";
+
+// run status
+pub const RUN_RUNNING: &str = "running";
+pub const RUN_COMPLETED: &str = "completed";
+pub const RUN_INTERRUPTED: &str = "interrupted";
+
+// hash set matching
+pub const KNOWN_GOOD: &str = "good";
+pub const KNOWN_BAD: &str = "bad";
+pub const KNOWN_UNKNOWN: &str = "unknown";
+
+// classification of the content from its entropy
+pub const ENTROPY_PLAIN: &str = "plain";
+pub const ENTROPY_COMPRESSED: &str = "compressed";
+pub const ENTROPY_ENCRYPTED: &str = "encrypted";
+
+// allocation status of files read from a disk image
+pub const ALLOCATED: &str = "allocated";
+pub const DELETED: &str = "deleted";
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, AsExpression)]
 #[diesel(sql_type = Text)]
@@ -42,20 +61,29 @@ impl From<&FileType> for ForensicsFileType {
     }
 }
 
+impl ForensicsFileType {
+    // value stored in the type column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::File => FT_FILE,
+            Self::Directory => FT_DIRECTORY,
+            Self::Symlink => FT_SYMLINK,
+            Self::Unknown => FT_UNKNOWN,
+        }
+    }
+}
+
 // Implement ToSql for ForensicsFileType which is a custom type
 impl ToSql<Text, Pg> for ForensicsFileType {
     fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
-        match self {
-            Self::File => ToSql::<Text, Pg>::to_sql(FT_FILE, out),
-            Self::Directory => ToSql::<Text, Pg>::to_sql(FT_DIRECTORY, out),
-            Self::Symlink => ToSql::<Text, Pg>::to_sql(FT_SYMLINK, out),
-            Self::Unknown => ToSql::<Text, Pg>::to_sql(FT_UNKNOWN, out),
-        }
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
     }
 }
 
+// None values are inserted as NULL rather than DEFAULT, so that all rows of a batch
+// have the same columns (also mandatory for COPY)
 #[derive(Debug, Insertable)]
-#[diesel(table_name = artefact)]
+#[diesel(table_name = artefact, treat_none_as_default_value = false)]
 pub struct FileInfo {
     // full path: on UNIX platforms, could be represented as a UTF-8 string
     pub path: String,
@@ -96,13 +124,93 @@ pub struct FileInfo {
     pub blake3: String,
 
     // Shannon entropy
-    pub entropy: Option<f32>,
+    pub entropy: Option<f64>,
 
     // try to have kind of mime type from magic numbers
     pub mime: Option<&'static str>,
 
     // optional metadata for the file
     pub metadata: Option<serde_json::Value>,
+
+    // for archive members, path of the containing archive
+    pub parent: Option<String>,
+
+    // run which produced this artefact
+    pub run_id: String,
+
+    // fuzzy hashes
+    pub ssdeep: Option<String>,
+    pub tlsh: Option<String>,
+
+    // md5 and sha1 hashes, for matching against external hash sets
+    pub md5: String,
+    pub sha1: String,
+
+    // good, bad or unknown when matched against hash sets, with the hash set label
+    pub known: Option<String>,
+    pub known_label: Option<String>,
+
+    // entropy of each block, chi-square of the byte distribution and the derived
+    // plain, compressed or encrypted classification
+    pub entropy_min: Option<f64>,
+    pub entropy_max: Option<f64>,
+    pub entropy_mean: Option<f64>,
+    pub entropy_stddev: Option<f64>,
+    pub chi_square: Option<f64>,
+    pub entropy_class: Option<String>,
+
+    // whether the extension disagrees with the discovered type, and why
+    pub ext_mismatch: Option<bool>,
+    pub mismatch_reason: Option<String>,
+
+    // unix metadata: inode, device, owner with names when they can be resolved,
+    // mode including setuid/setgid/sticky bits, hard links and inode change time
+    pub inode: Option<i64>,
+    pub dev: Option<i64>,
+    pub uid: Option<i64>,
+    pub gid: Option<i64>,
+    pub user_name: Option<String>,
+    pub group_name: Option<String>,
+    pub mode: Option<i32>,
+    pub permissions: Option<String>,
+    pub nlink: Option<i64>,
+    pub changed: Option<SystemTime>,
+
+    // extended attributes, POSIX ACLs and file capabilities
+    pub xattrs: Option<serde_json::Value>,
+
+    // for symbolic links, the target as stored and resolved, and whether it exists
+    pub link_target: Option<String>,
+    pub link_resolved: Option<String>,
+    pub link_dangling: Option<bool>,
+
+    // for hard links, path of the first occurrence which was hashed instead of this one
+    pub hardlink_of: Option<String>,
+
+    // timestamps as nanoseconds since the epoch, as timestamp columns only keep microseconds
+    pub created_ns: Option<i64>,
+    pub accessed_ns: Option<i64>,
+    pub modified_ns: Option<i64>,
+    pub changed_ns: Option<i64>,
+
+    // for files read from a disk image: allocated or deleted
+    pub allocation: Option<String>,
+
+    // for files read from a disk image, data location in the image
+    pub extents: Option<serde_json::Value>,
+
+    // deletion time, e.g. ext dtime
+    pub deleted: Option<SystemTime>,
+
+    // NTFS MFT record sequence number, the record number being kept as inode
+    pub sequence: Option<i32>,
+
+    // NTFS $FILE_NAME timestamps, the $STANDARD_INFORMATION ones being created, modified,
+    // accessed and changed
+    pub fn_created: Option<SystemTime>,
+    pub fn_modified: Option<SystemTime>,
+    pub fn_accessed: Option<SystemTime>,
+    pub fn_changed: Option<SystemTime>,
 }
 
 // has to implement default manually cause SystemTime has no default
@@ -124,30 +232,137 @@ impl Default for FileInfo {
             entropy: None,
             mime: None,
             metadata: None,
+            parent: None,
+            run_id: String::new(),
+            ssdeep: None,
+            tlsh: None,
+            md5: String::new(),
+            sha1: String::new(),
+            known: None,
+            known_label: None,
+            entropy_min: None,
+            entropy_max: None,
+            entropy_mean: None,
+            entropy_stddev: None,
+            chi_square: None,
+            entropy_class: None,
+            ext_mismatch: None,
+            mismatch_reason: None,
+            inode: None,
+            dev: None,
+            uid: None,
+            gid: None,
+            user_name: None,
+            group_name: None,
+            mode: None,
+            permissions: None,
+            nlink: None,
+            changed: None,
+            xattrs: None,
+            link_target: None,
+            link_resolved: None,
+            link_dangling: None,
+            hardlink_of: None,
+            created_ns: None,
+            accessed_ns: None,
+            modified_ns: None,
+            changed_ns: None,
+            allocation: None,
+            extents: None,
+            deleted: None,
+            sequence: None,
+            fn_created: None,
+            fn_modified: None,
+            fn_accessed: None,
+            fn_changed: None,
         }
     }
 }
 
-#[derive(Debug, Insertable)]
-#[diesel(table_name = run_history)]
+// saved at start and updated at the end, so that interrupted runs can be resumed
+#[derive(Debug, Insertable, Queryable, Selectable, AsChangeset)]
+#[diesel(table_name = run_history, primary_key(run_id), treat_none_as_null = true)]
 pub struct RunHistory {
+    pub run_id: String,
+    pub case_name: Option<String>,
     pub start_time: SystemTime,
     pub end_time: SystemTime,
     pub elapsed: String,
     pub nb_files: i64,
     pub args: String,
     pub tags: String,
+    pub status: String,
+    pub source: String,
+    pub base_run: Option<String>,
+
+    // case metadata and stored hashes of EWF images, verified only if asked
+    pub examiner: Option<String>,
+    pub case_number: Option<String>,
+    pub acquired: Option<SystemTime>,
+    pub image_md5: Option<String>,
+    pub image_sha1: Option<String>,
+    pub verified: Option<bool>,
 }
 
 impl Default for RunHistory {
     fn default() -> Self {
         Self {
+            run_id: String::new(),
+            case_name: None,
             start_time: SystemTime::now(),
             end_time: SystemTime::now(),
             elapsed: String::new(),
             nb_files: 0,
             args: String::new(),
             tags: String::new(),
+            status: RUN_RUNNING.to_string(),
+            source: String::new(),
+            base_run: None,
+            examiner: None,
+            case_number: None,
+            acquired: None,
+            image_md5: None,
+            image_sha1: None,
+            verified: None,
         }
     }
 }
+
+// a partition found in a disk image
+#[derive(Debug, Default, Insertable)]
+#[diesel(table_name = partition, treat_none_as_default_value = false)]
+pub struct Partition {
+    pub run_id: String,
+    pub image: String,
+
+    // as numbered by Linux: logical MBR partitions start at 5, 0 for an image without partition table
+    pub number: i32,
+
+    // mbr, gpt or none
+    pub scheme: String,
+
+    // in bytes
+    pub start: i64,
+    pub len: i64,
+
+    // MBR type (e.g. 0x83) or GPT type GUID, with its name if known
+    pub type_id: String,
+    pub type_name: Option<String>,
+
+    // GPT partition name and unique GUID
+    pub label: Option<String>,
+    pub guid: Option<String>,
+
+    // detected from the boot sector or superblock
+    pub filesystem: Option<String>,
+}
+
+// a hash loaded from a known-good or known-bad hash set
+#[derive(Debug, Insertable)]
+#[diesel(table_name = hash_set, treat_none_as_default_value = false)]
+pub struct KnownHash {
+    pub hash: String,
+    pub kind: &'static str,
+    pub label: Option<String>,
+    pub source: String,
+}
@@ -0,0 +1,157 @@
+// filters applied while walking the source directory, so that unwanted entries
+// are never sent to the workers
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::{args::Args, fileinfo::ForensicsFileType, image::ImageFile};
+
+#[derive(Debug)]
+pub struct WalkFilter {
+    // when not empty, only files matching one of these are kept
+    include: GlobSet,
+
+    // files or directories matching one of these are skipped, directories not being descended
+    exclude: GlobSet,
+
+    // file size boundaries
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl WalkFilter {
+    pub fn new(args: &Args) -> anyhow::Result<Self> {
+        Ok(Self {
+            include: glob_set(&args.include)?,
+            exclude: glob_set(&args.exclude)?,
+            min_size: args.min_size,
+            max_size: args.max_size,
+        })
+    }
+
+    // configure the walker according to depth, filesystem and symlinks options
+    pub fn walker(args: &Args) -> WalkDir {
+        let mut walker = WalkDir::new(args.dir.as_deref().unwrap())
+            .same_file_system(args.one_file_system)
+            .follow_links(args.follow_symlinks);
+
+        if let Some(depth) = args.max_depth {
+            walker = walker.max_depth(depth);
+        }
+
+        walker
+    }
+
+    // called before descending into a directory: the starting one is always kept
+    pub fn is_excluded(&self, entry: &DirEntry) -> bool {
+        entry.depth() != 0 && is_match(&self.exclude, entry.path(), entry.file_name())
+    }
+
+    // include and size filters only apply to files, directories being kept to be walked
+    pub fn is_selected(&self, entry: &DirEntry) -> bool {
+        if !entry.file_type().is_file() {
+            return true;
+        }
+
+        if !self.include.is_empty() && !is_match(&self.include, entry.path(), entry.file_name()) {
+            return false;
+        }
+
+        // only stat the file if needed
+        if self.min_size.is_none() && self.max_size.is_none() {
+            return true;
+        }
+        let Ok(meta) = entry.metadata() else {
+            // let the worker report the error
+            return true;
+        };
+
+        self.is_size_selected(meta.len())
+    }
+
+    // same filters for entries found in disk images, paths being like disk.raw!/p1/etc/passwd
+    pub fn is_image_excluded(&self, file: &ImageFile) -> bool {
+        file.depth != 0 && is_match(&self.exclude, &file.fi.path, &file.fi.name)
+    }
+
+    pub fn is_image_selected(&self, file: &ImageFile) -> bool {
+        if file.fi.r#type != ForensicsFileType::File {
+            return true;
+        }
+
+        (self.include.is_empty() || is_match(&self.include, &file.fi.path, &file.fi.name))
+            && self.is_size_selected(file.fi.len as u64)
+    }
+
+    fn is_size_selected(&self, len: u64) -> bool {
+        self.min_size.is_none_or(|min| len >= min) && self.max_size.is_none_or(|max| len <= max)
+    }
+}
+
+// globs are matched against the whole path and the file name, so that
+// e.g. "node_modules" or "*.vmdk" work without a leading "**/"
+fn is_match(set: &GlobSet, path: impl AsRef<Path>, name: impl AsRef<Path>) -> bool {
+    set.is_match(path) || set.is_match(name)
+}
+
+fn glob_set(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn walk_filter() -> anyhow::Result<()> {
+        let test = crate::testing::TestDb::new()?;
+        let dir = test.files();
+        for (path, len) in [
+            ("small.txt", 1),
+            ("big.txt", 100),
+            ("disk.vmdk", 10),
+            ("node_modules/lib.js", 10),
+            ("a/b/deep.txt", 10),
+        ] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, vec![0; len])?;
+        }
+
+        let walk = |options: &[&str]| -> anyhow::Result<Vec<String>> {
+            let dir = dir.to_string_lossy();
+            let args = Args::try_parse_from(["iaa", "-d", &dir].iter().chain(options))?;
+            let filter = WalkFilter::new(&args)?;
+            let mut files: Vec<String> = WalkFilter::walker(&args)
+                .into_iter()
+                .filter_entry(|e| !filter.is_excluded(e))
+                .flatten()
+                .filter(|e| e.file_type().is_file() && filter.is_selected(e))
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect();
+            files.sort();
+            Ok(files)
+        };
+
+        assert_eq!(walk(&[])?.len(), 5);
+        assert_eq!(
+            walk(&["--exclude", "node_modules", "--exclude", "*.vmdk"])?,
+            ["big.txt", "deep.txt", "small.txt"]
+        );
+        assert_eq!(walk(&["--include", "*.js"])?, ["lib.js"]);
+        assert_eq!(walk(&["--max-depth", "2"])?.len(), 4);
+        assert_eq!(
+            walk(&["--min-size", "5", "--max-size", "50", "--include", "*.txt"])?,
+            ["deep.txt"]
+        );
+        assert!(walk(&["--include", "a{"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+// fuzzy hashes, used to find near-duplicate files
+use std::path::Path;
+
+use crate::{args::Args, memory::MappedFile, storage::Storage};
+
+pub mod ssdeep;
+pub mod tlsh;
+
+// print all artefacts similar to a file: ssdeep score at least ssdeep_score
+// or TLSH distance at most tlsh_distance
+pub fn search(path: &Path, storage: &mut dyn Storage, args: &Args) -> anyhow::Result<()> {
+    let mapped = MappedFile::try_from(path)?;
+    let ssdeep = mapped.ssdeep();
+    let tlsh = mapped.tlsh();
+
+    let mut hashes = storage.fuzzy_hashes()?;
+
+    // the same file could have been indexed by several runs
+    hashes.sort_unstable();
+    hashes.dedup();
+
+    let mut found: Vec<_> = hashes
+        .iter()
+        .filter_map(|(other, other_ssdeep, other_tlsh)| {
+            let score = other_ssdeep
+                .as_deref()
+                .and_then(|h| ssdeep::compare(&ssdeep, h))
+                .filter(|s| *s >= args.ssdeep_score);
+            let distance = tlsh
+                .as_deref()
+                .zip(other_tlsh.as_deref())
+                .and_then(|(h1, h2)| tlsh::distance(h1, h2))
+                .filter(|d| *d <= args.tlsh_distance);
+
+            (score.is_some() || distance.is_some()).then_some((score, distance, other))
+        })
+        .collect();
+
+    // most similar first
+    found.sort_by_key(|(score, distance, _)| (std::cmp::Reverse(*score), *distance));
+
+    println!("ssdeep\ttlsh\tpath");
+    for (score, distance, other) in found {
+        println!(
+            "{}\t{}\t{other}",
+            score.map_or("-".to_string(), |s| s.to_string()),
+            distance.map_or("-".to_string(), |d| d.to_string()),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // pseudo random but reproducible text
+    fn sample(seed: u32, len: usize) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x = x.wrapping_mul(1103515245).wrapping_add(12345);
+                b"abcdefghijklmnopqrstuvwxyz .,\n"[(x >> 16) as usize % 30]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ssdeep() {
+        assert_eq!(ssdeep::hash(b""), "3::");
+        assert_eq!(ssdeep::hash(b"a"), "3:E:E");
+
+        let data = sample(1, 20_000);
+        let mut patched = data.clone();
+        patched[10_000..10_100].copy_from_slice(&sample(2, 100));
+
+        let h1 = ssdeep::hash(&data);
+        let h2 = ssdeep::hash(&patched);
+        let h3 = ssdeep::hash(&sample(3, 20_000));
+
+        // chunked input gives the same hash
+        let mut chunked = ssdeep::Ssdeep::default();
+        data.chunks(1000).for_each(|c| chunked.update(c));
+        assert_eq!(chunked.finalize(), h1);
+
+        assert_eq!(ssdeep::compare(&h1, &h1), Some(100));
+        assert!(ssdeep::compare(&h1, &h2).unwrap() > 50);
+        assert_eq!(ssdeep::compare(&h1, &h3), Some(0));
+        assert_eq!(ssdeep::compare(&h1, "foo"), None);
+    }
+
+    // examples of the python-ssdeep documentation, as computed by libfuzzy
+    #[test]
+    fn ssdeep_reference() {
+        let h1 =
+            ssdeep::hash(b"Also called fuzzy hashes, Ctph can match inputs that have homologies.");
+        let h2 =
+            ssdeep::hash(b"Also called fuzzy hashes, CTPH can match inputs that have homologies.");
+        assert_eq!(h1, "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C");
+        assert_eq!(h2, "3:AXGBicFlIHBGcL6wCrFQEv:AXGH6xLsr2C");
+        assert_eq!(ssdeep::compare(&h1, &h2), Some(22));
+
+        let mut streamed = ssdeep::Ssdeep::default();
+        streamed.update(b"Also called fuzzy hashes, ");
+        streamed.update(b"Ctph can match inputs that have homologies.");
+        assert_eq!(streamed.finalize(), h1);
+    }
+
+    #[test]
+    fn tlsh() {
+        assert_eq!(tlsh::hash(b"too short"), None);
+        assert_eq!(tlsh::hash(&[0; 1000]), None);
+
+        let data = sample(1, 20_000);
+        let mut patched = data.clone();
+        patched[10_000..10_100].copy_from_slice(&sample(2, 100));
+
+        let h1 = tlsh::hash(&data).unwrap();
+        let h2 = tlsh::hash(&patched).unwrap();
+        let h3 = tlsh::hash(&sample(3, 5_000)).unwrap();
+        assert_eq!(h1.len(), 72);
+
+        assert_eq!(tlsh::distance(&h1, &h1), Some(0));
+        assert!(tlsh::distance(&h1, &h2).unwrap() < tlsh::distance(&h1, &h3).unwrap());
+        assert_eq!(tlsh::distance(&h1, "T1"), None);
+    }
+}
@@ -0,0 +1,315 @@
+// ssdeep context triggered piecewise hashing (CTPH), compatible with ssdeep 2.x:
+// all block sizes are computed in a single pass, the right one being chosen at the end
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCKSIZE: u64 = 3;
+const SPAMSUM_LENGTH: usize = 64;
+const NUM_BLOCKHASHES: usize = 31;
+const HASH_PRIME: u32 = 0x01000193;
+const HASH_INIT: u32 = 0x28021967;
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn block_size(index: usize) -> u64 {
+    MIN_BLOCKSIZE << index
+}
+
+// FNV-like hash of the current piece
+fn sum_hash(c: u8, h: u32) -> u32 {
+    h.wrapping_mul(HASH_PRIME) ^ c as u32
+}
+
+// rolling hash on the last ROLLING_WINDOW bytes, used to trigger piece boundaries
+#[derive(Default)]
+struct Roll {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl Roll {
+    fn update(&mut self, c: u8) {
+        let slot = self.n % ROLLING_WINDOW;
+
+        self.h2 = self.h2.wrapping_sub(self.h1);
+        self.h2 = self.h2.wrapping_add(ROLLING_WINDOW as u32 * c as u32);
+        self.h1 = self.h1.wrapping_add(c as u32);
+        self.h1 = self.h1.wrapping_sub(self.window[slot] as u32);
+        self.window[slot] = c;
+        self.n += 1;
+        self.h3 = (self.h3 << 5) ^ c as u32;
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+// digest for a single block size, and its truncated half for twice the block size
+#[derive(Clone, Copy)]
+struct BlockHash {
+    h: u32,
+    halfh: u32,
+    digest: [u8; SPAMSUM_LENGTH],
+    halfdigest: u8,
+    dindex: usize,
+}
+
+impl BlockHash {
+    fn new(h: u32, halfh: u32) -> Self {
+        Self {
+            h,
+            halfh,
+            digest: [0; SPAMSUM_LENGTH],
+            halfdigest: 0,
+            dindex: 0,
+        }
+    }
+}
+
+pub struct Ssdeep {
+    bh: [BlockHash; NUM_BLOCKHASHES],
+
+    // block hashes still alive
+    start: usize,
+    end: usize,
+
+    roll: Roll,
+    total: u64,
+
+    // hash of the last piece for the largest block size
+    lasth: Option<u32>,
+}
+
+impl Default for Ssdeep {
+    fn default() -> Self {
+        Self {
+            bh: [BlockHash::new(HASH_INIT, HASH_INIT); NUM_BLOCKHASHES],
+            start: 0,
+            end: 1,
+            roll: Roll::default(),
+            total: 0,
+            lasth: None,
+        }
+    }
+}
+
+impl Ssdeep {
+    pub fn update(&mut self, data: &[u8]) {
+        self.total += data.len() as u64;
+        for c in data {
+            self.step(*c);
+        }
+    }
+
+    fn step(&mut self, c: u8) {
+        self.roll.update(c);
+        let h = self.roll.sum() as u64;
+
+        for bh in &mut self.bh[self.start..self.end] {
+            bh.h = sum_hash(c, bh.h);
+            bh.halfh = sum_hash(c, bh.halfh);
+        }
+        if let Some(lasth) = &mut self.lasth {
+            *lasth = sum_hash(c, *lasth);
+        }
+
+        // block sizes are multiple of each others: stop at the first one not triggered
+        let mut i = self.start;
+        while i < self.end {
+            let bs = block_size(i);
+            if h % bs != bs - 1 {
+                break;
+            }
+
+            // first piece for this block size: start the next one
+            if self.bh[i].dindex == 0 {
+                self.fork();
+            }
+
+            let bh = &mut self.bh[i];
+            bh.digest[bh.dindex] = B64[bh.h as usize % 64];
+            bh.halfdigest = B64[bh.halfh as usize % 64];
+
+            if bh.dindex < SPAMSUM_LENGTH - 1 {
+                bh.dindex += 1;
+                bh.h = HASH_INIT;
+                if bh.dindex < SPAMSUM_LENGTH / 2 {
+                    bh.halfh = HASH_INIT;
+                    bh.halfdigest = 0;
+                }
+            } else {
+                self.reduce();
+            }
+
+            i += 1;
+        }
+    }
+
+    // the next block size starts with the current hash of the last one
+    fn fork(&mut self) {
+        if self.end < NUM_BLOCKHASHES {
+            let last = self.bh[self.end - 1];
+            self.bh[self.end] = BlockHash::new(last.h, last.halfh);
+            self.end += 1;
+        } else if self.lasth.is_none() {
+            self.lasth = Some(self.bh[self.end - 1].h);
+        }
+    }
+
+    // the smallest block size is full and won't be selected anymore
+    fn reduce(&mut self) {
+        if self.end - self.start < 2
+            || block_size(self.start) * SPAMSUM_LENGTH as u64 >= self.total
+            || self.bh[self.start + 1].dindex < SPAMSUM_LENGTH / 2
+        {
+            return;
+        }
+        self.start += 1;
+    }
+
+    // blocksize:digest:digest for twice the blocksize
+    pub fn finalize(&self) -> String {
+        let h = self.roll.sum();
+
+        // smallest block size giving at most SPAMSUM_LENGTH pieces, but with enough of them
+        let mut bi = self.start;
+        while block_size(bi) * (SPAMSUM_LENGTH as u64) < self.total && bi < NUM_BLOCKHASHES - 1 {
+            bi += 1;
+        }
+        if bi >= self.end {
+            bi = self.end - 1;
+        }
+        while bi > self.start && self.bh[bi].dindex < SPAMSUM_LENGTH / 2 {
+            bi -= 1;
+        }
+
+        let bh = &self.bh[bi];
+        let mut digest = bh.digest[..bh.dindex].to_vec();
+        if h != 0 {
+            digest.push(B64[bh.h as usize % 64]);
+        } else if bh.digest[bh.dindex] != 0 {
+            digest.push(bh.digest[bh.dindex]);
+        }
+        digest.push(b':');
+
+        if bi < self.end - 1 {
+            let bh = &self.bh[bi + 1];
+            let len = bh.dindex.min(SPAMSUM_LENGTH / 2 - 1);
+            digest.extend_from_slice(&bh.digest[..len]);
+            if h != 0 {
+                digest.push(B64[bh.halfh as usize % 64]);
+            } else if bh.halfdigest != 0 {
+                digest.push(bh.halfdigest);
+            }
+        } else if h != 0 {
+            let h = match self.lasth {
+                Some(lasth) if bi != 0 => lasth,
+                _ => bh.h,
+            };
+            digest.push(B64[h as usize % 64]);
+        }
+
+        format!("{}:{}", block_size(bi), String::from_utf8_lossy(&digest))
+    }
+}
+
+// hash a whole buffer
+pub fn hash(data: &[u8]) -> String {
+    let mut ssdeep = Ssdeep::default();
+    ssdeep.update(data);
+    ssdeep.finalize()
+}
+
+// similarity score between 0 (no match) and 100, None if one of the hashes is invalid
+pub fn compare(hash1: &str, hash2: &str) -> Option<u32> {
+    let (bs1, s1b1, s1b2) = parse(hash1)?;
+    let (bs2, s2b1, s2b2) = parse(hash2)?;
+
+    // only block sizes equal or twice the other one can be compared
+    if bs1 != bs2 && bs1.checked_mul(2) != Some(bs2) && bs2.checked_mul(2) != Some(bs1) {
+        return Some(0);
+    }
+
+    if bs1 == bs2 && s1b1 == s2b1 {
+        return Some(100);
+    }
+
+    let score = if bs1 == bs2 {
+        score_strings(&s1b1, &s2b1, bs1).max(score_strings(&s1b2, &s2b2, bs1 * 2))
+    } else if bs1 == bs2 * 2 {
+        score_strings(&s1b1, &s2b2, bs1)
+    } else {
+        score_strings(&s1b2, &s2b1, bs2)
+    };
+
+    Some(score)
+}
+
+// split blocksize:digest1:digest2, removing runs of more than 3 identical characters
+fn parse(hash: &str) -> Option<(u64, Vec<u8>, Vec<u8>)> {
+    let mut parts = hash.splitn(3, ':');
+    let bs = parts.next()?.parse().ok()?;
+    let d1 = parts.next()?;
+
+    // a file name could be added after a comma
+    let d2 = parts.next()?.split(',').next()?;
+
+    Some((bs, eliminate_sequences(d1), eliminate_sequences(d2)))
+}
+
+fn eliminate_sequences(digest: &str) -> Vec<u8> {
+    let mut result: Vec<u8> = Vec::with_capacity(digest.len());
+    for c in digest.bytes() {
+        if result.len() < 3 || result[result.len() - 3..].iter().any(|x| *x != c) {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn score_strings(s1: &[u8], s2: &[u8], block_size: u64) -> u32 {
+    if s1.len() > SPAMSUM_LENGTH || s2.len() > SPAMSUM_LENGTH || !has_common_substring(s1, s2) {
+        return 0;
+    }
+
+    let mut score = edit_distance(s1, s2) as u64;
+    score = score * SPAMSUM_LENGTH as u64 / (s1.len() + s2.len()) as u64;
+    score = 100 * score / SPAMSUM_LENGTH as u64;
+    if score >= 100 {
+        return 0;
+    }
+    score = 100 - score;
+
+    // small block sizes could give too high scores for short digests
+    let threshold = (99 + ROLLING_WINDOW as u64) / ROLLING_WINDOW as u64 * MIN_BLOCKSIZE;
+    if block_size < threshold {
+        let cap = block_size / MIN_BLOCKSIZE * s1.len().min(s2.len()) as u64;
+        score = score.min(cap);
+    }
+
+    score as u32
+}
+
+// digests are only compared if they share at least ROLLING_WINDOW consecutive characters
+fn has_common_substring(s1: &[u8], s2: &[u8]) -> bool {
+    s1.windows(ROLLING_WINDOW)
+        .any(|w1| s2.windows(ROLLING_WINDOW).any(|w2| w1 == w2))
+}
+
+// Levenshtein distance where a substitution costs 2
+fn edit_distance(s1: &[u8], s2: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=s2.len()).collect();
+    let mut current = vec![0; s2.len() + 1];
+
+    for (i, c1) in s1.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, c2) in s2.iter().enumerate() {
+            let replace = previous[j] + if c1 == c2 { 0 } else { 2 };
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[s2.len()]
+}
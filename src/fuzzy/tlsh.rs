@@ -0,0 +1,263 @@
+// TLSH locality sensitive hash (128 buckets, 1 byte checksum), as the T1 hashes
+// of the reference implementation
+const WINDOW: usize = 5;
+const BUCKETS: usize = 128;
+const CODE_SIZE: usize = BUCKETS / 4;
+
+// shorter data doesn't give a meaningful hash
+const MIN_DATA_LENGTH: u64 = 50;
+
+// Pearson hashing permutation
+const V_TABLE: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163, 14, 197, 213, 181, 161,
+    85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200, 110, 177, 104, 103, 141, 253, 255, 50, 77,
+    101, 81, 18, 45, 96, 31, 222, 25, 107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227,
+    149, 235, 97, 234, 57, 22, 60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248, 174, 169,
+    211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243, 132, 56, 148, 75, 128, 133,
+    158, 100, 130, 126, 91, 13, 153, 246, 216, 219, 119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92,
+    32, 136, 114, 52, 10, 138, 30, 48, 183, 156, 35, 61, 26, 143, 74, 251, 94, 129, 162, 63, 152,
+    170, 7, 115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131, 125, 173, 15, 238, 79,
+    95, 89, 16, 105, 137, 225, 224, 217, 160, 37, 123, 118, 73, 2, 157, 46, 116, 9, 145, 134, 228,
+    207, 212, 202, 215, 69, 229, 27, 188, 67, 124, 168, 252, 42, 4, 29, 108, 21, 247, 19, 205, 39,
+    203, 233, 40, 186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76, 140, 36, 210,
+    172, 41, 54, 159, 8, 185, 232, 113, 196, 231, 47, 146, 120, 51, 65, 28, 144, 254, 221, 93, 189,
+    194, 139, 112, 43, 71, 109, 184, 209,
+];
+
+fn pearson(salt: u8, i: u8, j: u8, k: u8) -> u8 {
+    let h = V_TABLE[salt as usize];
+    let h = V_TABLE[(h ^ i) as usize];
+    let h = V_TABLE[(h ^ j) as usize];
+    V_TABLE[(h ^ k) as usize]
+}
+
+pub struct Tlsh {
+    window: [u8; WINDOW],
+    buckets: [u32; 256],
+    checksum: u8,
+    len: u64,
+}
+
+impl Default for Tlsh {
+    fn default() -> Self {
+        Self {
+            window: [0; WINDOW],
+            buckets: [0; 256],
+            checksum: 0,
+            len: 0,
+        }
+    }
+}
+
+impl Tlsh {
+    pub fn update(&mut self, data: &[u8]) {
+        for c in data {
+            let j = (self.len % WINDOW as u64) as usize;
+            self.window[j] = *c;
+
+            // triplets of the 5 bytes sliding window are counted into buckets
+            if self.len >= WINDOW as u64 - 1 {
+                let a = *c;
+                let b = self.window[(j + 4) % WINDOW];
+                let c = self.window[(j + 3) % WINDOW];
+                let d = self.window[(j + 2) % WINDOW];
+                let e = self.window[(j + 1) % WINDOW];
+
+                self.checksum = pearson(0, a, b, self.checksum);
+                for (salt, x, y) in [
+                    (2, b, c),
+                    (3, b, d),
+                    (5, c, d),
+                    (7, c, e),
+                    (11, b, e),
+                    (13, d, e),
+                ] {
+                    self.buckets[pearson(salt, a, x, y) as usize] += 1;
+                }
+            }
+
+            self.len += 1;
+        }
+    }
+
+    // None when data is too short or not diverse enough
+    pub fn finalize(&self) -> Option<String> {
+        if self.len < MIN_DATA_LENGTH {
+            return None;
+        }
+
+        let buckets = &self.buckets[..BUCKETS];
+        let mut sorted = buckets.to_vec();
+        sorted.sort_unstable();
+        let (q1, q2, q3) = (
+            sorted[BUCKETS / 4 - 1],
+            sorted[BUCKETS / 2 - 1],
+            sorted[BUCKETS - BUCKETS / 4 - 1],
+        );
+
+        let nonzero = buckets.iter().filter(|b| **b > 0).count();
+        if q3 == 0 || nonzero <= CODE_SIZE * 2 {
+            return None;
+        }
+
+        // each bucket is coded on 2 bits according to the quartile it belongs to
+        let mut code = [0u8; CODE_SIZE];
+        for (i, chunk) in buckets.chunks_exact(4).enumerate() {
+            for (j, count) in chunk.iter().enumerate() {
+                let value = if *count > q3 {
+                    3
+                } else if *count > q2 {
+                    2
+                } else if *count > q1 {
+                    1
+                } else {
+                    0
+                };
+                code[i] |= value << (j * 2);
+            }
+        }
+
+        // same as the reference: integer product, float division
+        let q1ratio = ((q1.wrapping_mul(100) as f32) / q3 as f32) as u32 % 16;
+        let q2ratio = ((q2.wrapping_mul(100) as f32) / q3 as f32) as u32 % 16;
+
+        let mut hash = format!(
+            "T1{:02X}{:02X}{:02X}",
+            swap(self.checksum),
+            swap(length_code(self.len)),
+            (q1ratio << 4) | q2ratio
+        );
+        for b in code.iter().rev() {
+            hash.push_str(&format!("{b:02X}"));
+        }
+
+        Some(hash)
+    }
+}
+
+// log scale of the data length
+fn length_code(len: u64) -> u8 {
+    let len = len as f32;
+    let i = if len <= 656.0 {
+        (len.ln() / 0.4054651).floor()
+    } else if len <= 3199.0 {
+        (len.ln() / 0.26236426 - 8.72777).floor()
+    } else {
+        (len.ln() / 0.09531018 - 62.5472).floor()
+    };
+    (i as i64 & 0xFF) as u8
+}
+
+fn swap(b: u8) -> u8 {
+    b.rotate_left(4)
+}
+
+// hash a whole buffer
+pub fn hash(data: &[u8]) -> Option<String> {
+    let mut tlsh = Tlsh::default();
+    tlsh.update(data);
+    tlsh.finalize()
+}
+
+// distance between two hashes, 0 meaning identical. None if one of the hashes is invalid
+pub fn distance(hash1: &str, hash2: &str) -> Option<u32> {
+    let h1 = parse(hash1)?;
+    let h2 = parse(hash2)?;
+
+    let mut diff = 0;
+
+    // length
+    diff += match mod_diff(swap(h1[1]) as u32, swap(h2[1]) as u32, 256) {
+        0 => 0,
+        1 => 1,
+        d => d * 12,
+    };
+
+    // quartile ratios
+    for shift in [4, 0] {
+        let q = mod_diff(
+            (h1[2] >> shift) as u32 & 0xF,
+            (h2[2] >> shift) as u32 & 0xF,
+            16,
+        );
+        diff += if q <= 1 { q } else { (q - 1) * 12 };
+    }
+
+    // checksum
+    if h1[0] != h2[0] {
+        diff += 1;
+    }
+
+    // body: 2-bit codes, the farthest ones being penalized
+    for (b1, b2) in h1[3..].iter().zip(&h2[3..]) {
+        for shift in (0..8).step_by(2) {
+            diff += match ((b1 >> shift) & 3).abs_diff((b2 >> shift) & 3) {
+                3 => 6,
+                d => d as u32,
+            };
+        }
+    }
+
+    Some(diff)
+}
+
+// checksum, length, quartiles and body
+fn parse(hash: &str) -> Option<Vec<u8>> {
+    let hex = hash.strip_prefix("T1").unwrap_or(hash);
+    if hex.len() != (3 + CODE_SIZE) * 2 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn mod_diff(x: u32, y: u32, range: u32) -> u32 {
+    let d = x.abs_diff(y);
+    d.min(range - d)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // digests of the test fixtures, checked against a port of the reference tlsh_impl.cpp
+    const DIGESTS: [(&str, &str); 4] = [
+        (
+            "tests/ctph.txt",
+            "T1A2A022A3CC0FB00C8C0222228B82082A8E02E0F2C28002A8CC0CAC0E022023E00C30F0",
+        ),
+        (
+            "tests/test.png",
+            "T1F8E0C0AB7193CD3F950C7F7E23044E44FAD31BE7D1061254479096622F36B04009B64D",
+        ),
+        (
+            "tests/test.jpg",
+            "T14D526C9F1523989FF52A66F50FA02A4AA4151F04E17203E0B47F2DFF567C1062ECAE1D",
+        ),
+        (
+            "tests/test.db",
+            "T1A7F12B82C33820A0D0100AB198822F8C0E34D10D41230F933C740020CC00D8D9CF1B8C",
+        ),
+    ];
+
+    #[test]
+    fn known_answers() -> anyhow::Result<()> {
+        for (path, digest) in DIGESTS {
+            let data = std::fs::read(path)?;
+            assert_eq!(hash(&data).as_deref(), Some(digest), "{path}");
+
+            // same digest when fed by chunks
+            let mut tlsh = Tlsh::default();
+            data.chunks(7).for_each(|c| tlsh.update(c));
+            assert_eq!(tlsh.finalize().as_deref(), Some(digest), "{path}");
+            assert_eq!(distance(digest, digest), Some(0));
+        }
+
+        // too short or not enough variation
+        assert_eq!(hash(&[0; 49]), None);
+        assert_eq!(hash(&[0; 4096]), None);
+        Ok(())
+    }
+}
@@ -1,13 +1,197 @@
 // calculate all kinds of hashes
 
-use std::path::Path;
+use std::io::{self, ErrorKind, Read};
 
-pub struct Hashes;
+use md5::{Digest, Md5};
+use sha1::Sha1;
+use sha2::Sha256;
 
-impl Hashes {
-    pub fn sha256<P: AsRef<Path> + Sync>(path: &P) -> anyhow::Result<String> {
-        // load data from file
-        let data = sha256::try_digest(path)?;
-        Ok(sha256::digest(data))
+use crate::{
+    args::Args,
+    fileinfo::FileInfo,
+    fuzzy::{ssdeep::Ssdeep, tlsh::Tlsh},
+    randomness::Entropy,
+};
+
+// data is fed to all hashers chunk by chunk, so that it's read only once
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+// digests asked on the command line, computed in a single pass over the data
+#[derive(Default)]
+pub struct Digests {
+    md5: Option<Md5>,
+    sha1: Option<Sha1>,
+    sha256: Option<Sha256>,
+    blake3: Option<blake3::Hasher>,
+    ssdeep: Option<Box<Ssdeep>>,
+    tlsh: Option<Box<Tlsh>>,
+    entropy: Option<Box<Entropy>>,
+}
+
+impl From<&Args> for Digests {
+    fn from(args: &Args) -> Self {
+        Self {
+            md5: args.md5.then(Md5::new),
+            sha1: args.sha1.then(Sha1::new),
+            sha256: args.sha256.then(Sha256::new),
+            blake3: args.blake3.then(blake3::Hasher::new),
+            ssdeep: args.ssdeep.then(Box::default),
+            tlsh: args.tlsh.then(Box::default),
+            entropy: None,
+        }
+    }
+}
+
+impl Digests {
+    // also calculate entropy while reading data, by blocks of block_size bytes
+    pub fn with_entropy(mut self, block_size: Option<u64>) -> Self {
+        self.entropy = block_size.map(|size| Box::new(Entropy::new(size)));
+        self
+    }
+
+    // feed all hashers with a chunk of data
+    pub fn update(&mut self, data: &[u8]) {
+        if let Some(h) = &mut self.md5 {
+            h.update(data);
+        }
+        if let Some(h) = &mut self.sha1 {
+            h.update(data);
+        }
+        if let Some(h) = &mut self.sha256 {
+            h.update(data);
+        }
+        if let Some(h) = &mut self.blake3 {
+            h.update(data);
+        }
+        if let Some(h) = &mut self.ssdeep {
+            h.update(data);
+        }
+        if let Some(h) = &mut self.tlsh {
+            h.update(data);
+        }
+        if let Some(e) = &mut self.entropy {
+            e.update(data);
+        }
+    }
+
+    // hash a whole buffer
+    pub fn digest(mut self, data: &[u8], fi: &mut FileInfo) {
+        for chunk in data.chunks(CHUNK_SIZE) {
+            self.update(chunk);
+        }
+        self.finalize(fi);
+    }
+
+    // hash data read by chunks, for files which can't be mapped into memory.
+    // Returns the entropy calculated in the same pass, if asked
+    pub fn stream(
+        mut self,
+        mut reader: impl Read,
+        fi: &mut FileInfo,
+    ) -> io::Result<Option<Box<Entropy>>> {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => self.update(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self.finalize(fi))
+    }
+
+    // set hashes as lowercase hex strings, entropy being left to the caller
+    pub fn finalize(self, fi: &mut FileInfo) -> Option<Box<Entropy>> {
+        if let Some(h) = self.md5 {
+            fi.md5 = hex(&h.finalize());
+        }
+        if let Some(h) = self.sha1 {
+            fi.sha1 = hex(&h.finalize());
+        }
+        if let Some(h) = self.sha256 {
+            fi.sha256 = hex(&h.finalize());
+        }
+        if let Some(h) = self.blake3 {
+            fi.blake3 = h.finalize().to_string();
+        }
+        if let Some(h) = self.ssdeep {
+            fi.ssdeep = Some(h.finalize());
+        }
+        if let Some(h) = self.tlsh {
+            fi.tlsh = h.finalize();
+        }
+        self.entropy
+    }
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn stream() {
+        let data: Vec<u8> = (0..3 * CHUNK_SIZE + 100)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect();
+        let args = Args::parse_from(["iaa", "-d", ".", "--md5", "--sha256", "--ssdeep"]);
+
+        let mut mapped = FileInfo::default();
+        Digests::from(&args).digest(&data, &mut mapped);
+
+        let mut streamed = FileInfo::default();
+        Digests::from(&args)
+            .with_entropy(Some(4096))
+            .stream(data.as_slice(), &mut streamed)
+            .unwrap()
+            .unwrap()
+            .finalize(&mut streamed);
+
+        assert_eq!(mapped.md5, streamed.md5);
+        assert_eq!(mapped.sha256, streamed.sha256);
+        assert_eq!(mapped.ssdeep, streamed.ssdeep);
+
+        let expected = entropy::shannon_entropy(&data) as f64;
+        assert!((streamed.entropy.unwrap() - expected).abs() < 1e-4);
+    }
+
+    // RFC 1321 and FIPS 180 examples
+    #[test]
+    fn known_answers() {
+        let args = Args::parse_from(["iaa", "-d", ".", "--md5", "--sha1", "--sha256"]);
+
+        for (data, md5, sha1, sha256) in [
+            (
+                &b""[..],
+                "d41d8cd98f00b204e9800998ecf8427e",
+                "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                b"abc",
+                "900150983cd24fb0d6963f7d28e17f72",
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+        ] {
+            let mut fi = FileInfo::default();
+            Digests::from(&args).digest(data, &mut fi);
+            assert_eq!((fi.md5.as_str(), fi.sha1.as_str()), (md5, sha1));
+            assert_eq!(fi.sha256, sha256);
+        }
+
+        // a million 'a', read by chunks
+        let mut fi = FileInfo::default();
+        Digests::from(&args)
+            .stream(&[b'a'; 1_000_000][..], &mut fi)
+            .unwrap();
+        assert_eq!(fi.md5, "7707d6ae4e027c70eea2a935c2296f21");
+        assert_eq!(fi.sha1, "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
     }
 }
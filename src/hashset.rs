@@ -0,0 +1,248 @@
+// known-good and known-bad hash sets: NSRL RDS SQLite databases, CSV files with labels
+// or plain text files with one hash per line are loaded into the hash_set table
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use sqlite::State;
+
+use crate::{fileinfo::KnownHash, storage::Storage};
+
+// number of hashes inserted at once
+const CHUNK_SIZE: usize = 10_000;
+
+// NSRL RDS are SQLite databases
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+// CSV header columns used as labels, lowercase
+const LABEL_COLUMNS: &[&str] = &["filename", "file_name", "name", "label"];
+
+// load a hash set file, replacing hashes previously loaded from the same file.
+// Returns the number of hashes loaded
+pub fn import(path: &Path, kind: &'static str, storage: &mut dyn Storage) -> anyhow::Result<usize> {
+    let source = std::fs::canonicalize(path)?.to_string_lossy().into_owned();
+    storage.clear_hash_set(&source)?;
+
+    let mut loader = Loader {
+        storage,
+        kind,
+        source,
+        rows: Vec::with_capacity(CHUNK_SIZE),
+        count: 0,
+        label_column: None,
+    };
+
+    let mut header = [0u8; 16];
+    let is_sqlite = File::open(path)?.read_exact(&mut header).is_ok() && header == SQLITE_HEADER;
+
+    if is_sqlite {
+        loader.nsrl(path)?;
+    } else {
+        // plain text files have no label: use the file name
+        let default_label = path.file_name().map(|n| n.to_string_lossy().into_owned());
+        for line in BufReader::new(File::open(path)?).lines() {
+            loader.csv_line(&line?, default_label.as_deref())?;
+        }
+    }
+
+    loader.flush()?;
+    Ok(loader.count)
+}
+
+// buffer hashes and insert them by chunks
+struct Loader<'a> {
+    storage: &'a mut dyn Storage,
+    kind: &'static str,
+    source: String,
+    rows: Vec<KnownHash>,
+    count: usize,
+
+    // column of the file name, found in a CSV header like the NSRLFile.txt one
+    label_column: Option<usize>,
+}
+
+impl Loader<'_> {
+    fn push(&mut self, hash: &str, label: Option<&str>) -> anyhow::Result<()> {
+        self.rows.push(KnownHash {
+            hash: hash.to_lowercase(),
+            kind: self.kind,
+            label: label.map(String::from),
+            source: self.source.clone(),
+        });
+
+        if self.rows.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        self.storage.insert_hash_set(&self.rows)?;
+        self.count += self.rows.len();
+        self.rows.clear();
+        Ok(())
+    }
+
+    // NSRL RDS v3: all hashes of the FILE table, labelled with the file name
+    fn nsrl(&mut self, path: &Path) -> anyhow::Result<()> {
+        let conn = sqlite::open(path)?;
+        let mut stmt = conn.prepare("SELECT md5, sha1, sha256, file_name FROM FILE")?;
+
+        while let State::Row = stmt.next()? {
+            let label: Option<String> = stmt.read(3)?;
+            for i in 0..3 {
+                let hash: Option<String> = stmt.read(i)?;
+                if let Some(hash) = hash.filter(|h| is_hash(h)) {
+                    self.push(&hash, label.as_deref())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // all fields looking like MD5, SHA1 or SHA256 hashes are loaded, the label being the
+    // file name column of the header if any, or the first other non empty field which
+    // isn't a CRC32. This also handles legacy NSRLFile.txt files ("SHA-1","MD5","CRC32",
+    // "FileName",...) and md5sum-like outputs
+    fn csv_line(&mut self, line: &str, default_label: Option<&str>) -> anyhow::Result<()> {
+        let fields = if line.contains([',', ';', '\t']) {
+            split_csv(line)
+        } else {
+            line.trim()
+                .splitn(2, char::is_whitespace)
+                .map(|f| f.trim().to_string())
+                .collect()
+        };
+
+        // headers have no hash
+        if !fields.iter().any(|f| is_hash(f)) {
+            if let Some(i) = fields
+                .iter()
+                .position(|f| LABEL_COLUMNS.contains(&f.to_lowercase().as_str()))
+            {
+                self.label_column = Some(i);
+            }
+            return Ok(());
+        }
+
+        let label = match self.label_column {
+            Some(i) => fields.get(i).filter(|f| !f.is_empty()),
+            None => fields
+                .iter()
+                .find(|f| !f.is_empty() && !is_hash(f) && !is_crc32(f)),
+        }
+        .map(String::as_str)
+        .or(default_label);
+
+        for field in fields.iter().filter(|f| is_hash(f)) {
+            self.push(field, label)?;
+        }
+        Ok(())
+    }
+}
+
+// MD5, SHA1 or SHA256 as hex strings
+fn is_hash(s: &str) -> bool {
+    matches!(s.len(), 32 | 40 | 64) && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// NSRLFile.txt has a CRC32 column between the hashes and the file name
+fn is_crc32(s: &str) -> bool {
+    s.len() == 8 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// split a CSV line, taking care of double-quoted fields
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' | ';' | '\t' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields.iter().map(|f| f.trim().to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            split_csv(r#""d41d8cd98f00b204e9800998ecf8427e","a, ""b""";x"#),
+            vec!["d41d8cd98f00b204e9800998ecf8427e", r#"a, "b""#, "x"]
+        );
+        assert!(is_hash("D41D8CD98F00B204E9800998ECF8427E"));
+        assert!(!is_hash("d41d8cd98f00b204e9800998ecf8427"));
+        assert!(!is_hash("SHA-1"));
+    }
+
+    #[test]
+    fn nsrl_file() -> anyhow::Result<()> {
+        let test = crate::testing::TestDb::new()?;
+        let mut storage = test.storage()?;
+
+        let load = |storage: &mut dyn Storage, name: &str, content: &str| {
+            let path = test.files().join(name);
+            std::fs::write(&path, content)?;
+            import(&path, "good", storage)
+        };
+        let label = |storage: &mut dyn Storage, hash: &str| {
+            storage
+                .known_hash(&[hash])
+                .map(|k| k.and_then(|(_, label)| label))
+        };
+
+        // legacy RDS 2.x NSRLFile.txt
+        let nsrl = r#""SHA-1","MD5","CRC32","FileName","FileSize","ProductCode","OpSystemCode","SpecialCode"
+"0000002D9D62AEBE1E0E9DB6C4C4C7C16A163D2C","1D6EBB5A789ABD108FF578263E1F40F3","FFFFFFFF","_sfx_0024._p",4109,21000,"358",""
+"#;
+        assert_eq!(load(storage.as_mut(), "NSRLFile.txt", nsrl)?, 2);
+        assert_eq!(
+            label(storage.as_mut(), "1d6ebb5a789abd108ff578263e1f40f3")?.as_deref(),
+            Some("_sfx_0024._p")
+        );
+
+        // no header: CRC32 is skipped too
+        let nsrl = nsrl
+            .lines()
+            .nth(1)
+            .unwrap()
+            .replace("_sfx_0024._p", "other.exe")
+            .replace("0000002D", "1111112D");
+        load(storage.as_mut(), "noheader.txt", &nsrl)?;
+        assert_eq!(
+            label(storage.as_mut(), "1111112d9d62aebe1e0e9db6c4c4c7c16a163d2c")?.as_deref(),
+            Some("other.exe")
+        );
+
+        // md5sum output
+        load(
+            storage.as_mut(),
+            "md5sum.txt",
+            "d41d8cd98f00b204e9800998ecf8427e  empty.txt\n",
+        )?;
+        assert_eq!(
+            label(storage.as_mut(), "d41d8cd98f00b204e9800998ecf8427e")?.as_deref(),
+            Some("empty.txt")
+        );
+        Ok(())
+    }
+}
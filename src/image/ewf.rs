@@ -0,0 +1,557 @@
+// EnCase (EWF-E01) images: the media is split into chunks, zlib compressed or not, stored
+// in sectors sections of one or several segment files (.E01, .E02, ...) and located by
+// their table sections
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::bail;
+use chrono::NaiveDateTime;
+use flate2::read::ZlibDecoder;
+use log::warn;
+use md5::{Digest, Md5};
+use sha1::Sha1;
+
+use crate::image::{le_u16, le_u32, le_u64, RawImage, Source};
+
+pub const EWF_SIGNATURE: &[u8] = b"EVF\x09\x0D\x0A\xFF\x00";
+
+const FILE_HEADER_LEN: u64 = 13;
+const SECTION_DESCRIPTOR_LEN: u64 = 76;
+const TABLE_HEADER_LEN: usize = 24;
+
+// table entries: 31-bit offset, the high bit being set for compressed chunks
+const COMPRESSED: u32 = 0x8000_0000;
+
+// uncompressed chunks are followed by their Adler-32 checksum
+const CHECKSUM_LEN: u64 = 4;
+
+// zlib header, checksum and block headers: more than an incompressible chunk needs
+const ZLIB_OVERHEAD: u64 = 1024;
+
+// largest chunk accepted from the volume section, real ones being 32 KiB by default
+const MAX_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
+// header sections only hold a few case fields
+const MAX_HEADER_LEN: u64 = 1024 * 1024;
+
+// decompressed chunks kept for the workers reading the same parts of the media
+const CACHED_CHUNKS: usize = 64;
+
+// hashes are verified by reading this much at once
+const VERIFY_BUF_LEN: usize = 1024 * 1024;
+
+struct Chunk {
+    segment: usize,
+    offset: u64,
+    len: u64,
+    compressed: bool,
+}
+
+// case metadata of the header section
+#[derive(Debug, Default)]
+pub struct Header {
+    pub case_number: Option<String>,
+    pub examiner: Option<String>,
+    pub acquired: Option<SystemTime>,
+}
+
+pub struct Ewf {
+    segments: Vec<Box<dyn Source>>,
+    chunks: Vec<Chunk>,
+    chunk_size: u64,
+    len: u64,
+    cache: Mutex<VecDeque<(usize, Arc<Vec<u8>>)>>,
+
+    pub header: Header,
+    pub md5: Option<[u8; 16]>,
+    pub sha1: Option<[u8; 20]>,
+}
+
+impl Ewf {
+    // path of the first segment, the next ones being found from its extension
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut ewf = Self::new();
+        loop {
+            let path = segment_path(path, ewf.segments.len() as u16 + 1)?;
+            if !path.exists() {
+                warn!("EWF segment '{}' is missing", path.display());
+                break;
+            }
+            if ewf.add_segment(Box::new(RawImage::open(&path)?))? {
+                break;
+            }
+        }
+        ewf.finish()
+    }
+
+    fn new() -> Self {
+        Self {
+            segments: Vec::new(),
+            chunks: Vec::new(),
+            chunk_size: 0,
+            len: 0,
+            cache: Mutex::new(VecDeque::new()),
+            header: Header::default(),
+            md5: None,
+            sha1: None,
+        }
+    }
+
+    fn finish(mut self) -> anyhow::Result<Self> {
+        if self.chunk_size == 0 {
+            bail!("no volume section in EWF image");
+        }
+        let count = self.len.div_ceil(self.chunk_size) as usize;
+        if self.chunks.len() < count {
+            bail!(
+                "EWF image has {} chunks instead of {count}: missing segments?",
+                self.chunks.len()
+            );
+        }
+        self.chunks.truncate(count);
+        Ok(self)
+    }
+
+    // returns true if this is the last segment, ending with a done section
+    fn add_segment(&mut self, segment: Box<dyn Source>) -> anyhow::Result<bool> {
+        let number = self.segments.len() + 1;
+        let mut header = [0; FILE_HEADER_LEN as usize];
+        read_exact_at(segment.as_ref(), &mut header, 0)?;
+        if &header[..8] != EWF_SIGNATURE || le_u16(&header, 9) as usize != number {
+            bail!("invalid EWF segment {number}");
+        }
+
+        let mut pos = FILE_HEADER_LEN;
+        let mut sectors_end = 0;
+        let mut header2 = false;
+        let last = loop {
+            let mut descriptor = [0; SECTION_DESCRIPTOR_LEN as usize];
+            read_exact_at(segment.as_ref(), &mut descriptor, pos)?;
+            let r#type = descriptor[..16]
+                .split(|&b| b == 0)
+                .next()
+                .unwrap_or_default();
+            let next = le_u64(&descriptor, 16);
+            let size = le_u64(&descriptor, 24);
+
+            let start = pos + SECTION_DESCRIPTOR_LEN;
+            let data = || -> io::Result<Vec<u8>> {
+                let len = size
+                    .saturating_sub(SECTION_DESCRIPTOR_LEN)
+                    .min(segment.len());
+                let mut data = vec![0; len as usize];
+                read_exact_at(segment.as_ref(), &mut data, start)?;
+                Ok(data)
+            };
+
+            match r#type {
+                // both point to themselves
+                b"done" => break true,
+                b"next" => break false,
+
+                // header2 is the same as header, in UTF-16
+                b"header2" => {
+                    self.header = parse_header(&decompress(&data()?)?);
+                    header2 = true;
+                }
+                b"header" if !header2 => self.header = parse_header(&decompress(&data()?)?),
+                b"volume" | b"disk" => {
+                    let data = data()?;
+                    if data.len() < 24 {
+                        bail!("invalid EWF volume section");
+                    }
+                    let bytes_per_sector = le_u32(&data, 12) as u64;
+                    let chunk_size = le_u32(&data, 8) as u64 * bytes_per_sector;
+                    if !matches!(bytes_per_sector, 512 | 4096) || chunk_size > MAX_CHUNK_SIZE {
+                        bail!("invalid EWF volume section");
+                    }
+                    self.chunk_size = chunk_size;
+                    self.len = le_u64(&data, 16).saturating_mul(bytes_per_sector);
+                }
+                b"sectors" => {
+                    let Some(end) = pos.checked_add(size) else {
+                        bail!("invalid EWF sectors section");
+                    };
+                    sectors_end = end;
+                }
+
+                // table2 is a copy of table
+                b"table" => {
+                    let data = data()?;
+                    if data.len() < TABLE_HEADER_LEN {
+                        bail!("invalid EWF table section");
+                    }
+                    let count = le_u32(&data, 0) as usize;
+                    let base = le_u64(&data, 8);
+                    let chunk_offset = |entry: u32| base.checked_add((entry & !COMPRESSED) as u64);
+                    let entries: Vec<u32> = data[TABLE_HEADER_LEN..]
+                        .chunks_exact(4)
+                        .take(count)
+                        .map(|e| le_u32(e, 0))
+                        .collect();
+
+                    // the last chunk ends with the sectors section, or with the table
+                    // section itself for old images having no sectors section
+                    for (i, &entry) in entries.iter().enumerate() {
+                        let Some(offset) = chunk_offset(entry) else {
+                            bail!("invalid EWF table section");
+                        };
+                        let end = match entries.get(i + 1) {
+                            Some(&next) => chunk_offset(next),
+                            None if sectors_end > offset => Some(sectors_end),
+                            None => pos.checked_add(size),
+                        };
+                        let Some(end) = end else {
+                            bail!("invalid EWF table section");
+                        };
+                        self.chunks.push(Chunk {
+                            segment: self.segments.len(),
+                            offset,
+                            len: end.saturating_sub(offset),
+                            compressed: entry & COMPRESSED != 0,
+                        });
+                    }
+                }
+                b"hash" => self.md5 = stored_hash(&data()?, 0),
+                b"digest" => {
+                    let data = data()?;
+                    self.md5 = stored_hash(&data, 0);
+                    self.sha1 = stored_hash(&data, 16);
+                }
+                _ => (),
+            }
+
+            if next <= pos {
+                bail!("invalid EWF section chain in segment {number}");
+            }
+            pos = next;
+        };
+
+        self.segments.push(segment);
+        Ok(last)
+    }
+
+    fn chunk(&self, index: usize) -> io::Result<Arc<Vec<u8>>> {
+        if let Some((_, data)) = self.cache.lock().unwrap().iter().find(|(i, _)| *i == index) {
+            return Ok(data.clone());
+        }
+
+        // offsets and lengths come from the table: they are checked before allocating
+        let chunk = &self.chunks[index];
+        let segment = self.segments[chunk.segment].as_ref();
+        if chunk.len > self.chunk_size + ZLIB_OVERHEAD
+            || chunk
+                .offset
+                .checked_add(chunk.len)
+                .is_none_or(|end| end > segment.len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("EWF chunk {index} is invalid"),
+            ));
+        }
+
+        let expected = self
+            .chunk_size
+            .min(self.len - index as u64 * self.chunk_size);
+        let data = if chunk.compressed {
+            let mut raw = vec![0; chunk.len as usize];
+            read_exact_at(segment, &mut raw, chunk.offset)?;
+            let mut data = Vec::new();
+            ZlibDecoder::new(&raw[..])
+                .take(expected)
+                .read_to_end(&mut data)?;
+            data
+        } else {
+            let mut data = vec![0; chunk.len.saturating_sub(CHECKSUM_LEN).min(expected) as usize];
+            read_exact_at(segment, &mut data, chunk.offset)?;
+            data
+        };
+        if (data.len() as u64) < expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("EWF chunk {index} is truncated"),
+            ));
+        }
+
+        let data = Arc::new(data);
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() == CACHED_CHUNKS {
+            cache.pop_front();
+        }
+        cache.push_back((index, data.clone()));
+        Ok(data)
+    }
+
+    // compares the stored hashes to the ones of the media, None if none is stored
+    pub fn verify(&self) -> anyhow::Result<Option<bool>> {
+        if self.md5.is_none() && self.sha1.is_none() {
+            return Ok(None);
+        }
+
+        let mut md5 = Md5::new();
+        let mut sha1 = Sha1::new();
+        let mut buf = vec![0; VERIFY_BUF_LEN];
+        let mut offset = 0;
+        while offset < self.len {
+            let n = self.read_at(&mut buf, offset)?;
+            md5.update(&buf[..n]);
+            sha1.update(&buf[..n]);
+            offset += n as u64;
+        }
+
+        let md5_ok = self.md5.is_none_or(|h| h == md5.finalize()[..]);
+        let sha1_ok = self.sha1.is_none_or(|h| h == sha1.finalize()[..]);
+        Ok(Some(md5_ok && sha1_ok))
+    }
+}
+
+impl Source for Ewf {
+    // reads up to the end of a chunk
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        if offset >= self.len {
+            return Ok(0);
+        }
+        let index = (offset / self.chunk_size) as usize;
+        let chunk = self.chunk(index)?;
+
+        let start = (offset - index as u64 * self.chunk_size) as usize;
+        let n = buf.len().min(chunk.len() - start);
+        buf[..n].copy_from_slice(&chunk[start..start + n]);
+        Ok(n)
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+}
+
+// .E01 to .E99, then .EAA to .EZZ, .FAA and so on, keeping the case of the first one
+fn segment_path(first: &Path, number: u16) -> anyhow::Result<PathBuf> {
+    let ext = first
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let Some(letter) = ext.chars().next().filter(|_| ext.len() == 3) else {
+        bail!("EWF images are expected to have an .E01 extension");
+    };
+
+    let ext = if number <= 99 {
+        format!("{letter}{number:02}")
+    } else {
+        let n = (number - 100) as u32;
+        let base = if letter.is_ascii_lowercase() {
+            b'a'
+        } else {
+            b'A'
+        };
+        let first = char::from_u32(letter as u32 + n / (26 * 26));
+        let Some(first) = first.filter(char::is_ascii_alphabetic) else {
+            bail!("too many EWF segments");
+        };
+        format!(
+            "{first}{}{}",
+            (base + (n / 26 % 26) as u8) as char,
+            (base + (n % 26) as u8) as char
+        )
+    };
+    Ok(first.with_extension(ext))
+}
+
+// all zeros when not computed by the acquisition tool
+fn stored_hash<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    let hash: [u8; N] = data.get(offset..offset + N)?.try_into().ok()?;
+    hash.iter().any(|&b| b != 0).then_some(hash)
+}
+
+// truncated to MAX_HEADER_LEN, against decompression bombs
+fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut text = Vec::new();
+    ZlibDecoder::new(data)
+        .take(MAX_HEADER_LEN)
+        .read_to_end(&mut text)?;
+    Ok(text)
+}
+
+// tab separated identifiers then values, following the "main" line. The acquisition date
+// is a timestamp in header2, and a local date like "2024 3 5 14 20 31" in header, read as UTC
+fn parse_header(raw: &[u8]) -> Header {
+    let text = match raw {
+        [0xFF, 0xFE, utf16 @ ..] => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(raw).into_owned(),
+    };
+
+    let mut lines = text.lines().skip_while(|l| l.trim() != "main").skip(1);
+    let (Some(keys), Some(values)) = (lines.next(), lines.next()) else {
+        return Header::default();
+    };
+    let fields: HashMap<&str, &str> = keys.split('\t').zip(values.split('\t')).collect();
+    let field = |key| {
+        fields
+            .get(key)
+            .map(|v: &&str| v.trim())
+            .filter(|v| !v.is_empty())
+    };
+
+    let acquired = field("m").and_then(|m| match m.parse::<u64>() {
+        Ok(secs) => UNIX_EPOCH.checked_add(Duration::from_secs(secs)),
+        Err(_) => NaiveDateTime::parse_from_str(m, "%Y %m %d %H %M %S")
+            .ok()
+            .map(|dt| dt.and_utc().into()),
+    });
+
+    Header {
+        case_number: field("c").map(String::from),
+        examiner: field("e").map(String::from),
+        acquired,
+    }
+}
+
+fn read_exact_at(source: &dyn Source, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match source.read_at(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unzip(path: &str) -> Vec<Vec<u8>> {
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        (0..zip.len())
+            .map(|i| {
+                let mut data = Vec::new();
+                zip.by_index(i).unwrap().read_to_end(&mut data).unwrap();
+                data
+            })
+            .collect()
+    }
+
+    // the ext4 test image in 2 segments of 32 KiB chunks, 2 of them uncompressed
+    #[test]
+    fn ewf() {
+        let mut ewf = Ewf::new();
+        let mut segments = unzip("tests/test.E01.zip").into_iter();
+        assert!(!ewf.add_segment(Box::new(segments.next().unwrap())).unwrap());
+        assert!(ewf.add_segment(Box::new(segments.next().unwrap())).unwrap());
+        let ewf = ewf.finish().unwrap();
+
+        assert_eq!(ewf.header.case_number.as_deref(), Some("CASE-42"));
+        assert_eq!(ewf.header.examiner.as_deref(), Some("Jane Doe"));
+        assert_eq!(
+            ewf.header.acquired,
+            Some(UNIX_EPOCH + Duration::from_secs(1709648431))
+        );
+        assert_eq!(ewf.verify().unwrap(), Some(true));
+
+        // across compressed and uncompressed chunks
+        let media = unzip("tests/test.ext4.zip").remove(0);
+        let volume = crate::image::Volume::from(Arc::new(ewf) as Arc<dyn Source>);
+        assert_eq!(volume.len(), media.len() as u64);
+        let data = volume.read_vec(30000, 40000).unwrap();
+        assert_eq!(data, media[30000..70000]);
+    }
+
+    #[test]
+    fn invalid_chunks() {
+        let mut ewf = Ewf::new();
+        for segment in unzip("tests/test.E01.zip") {
+            ewf.add_segment(Box::new(segment)).unwrap();
+        }
+        let mut ewf = ewf.finish().unwrap();
+        assert!(ewf.chunk(0).is_ok());
+
+        // larger than a chunk, beyond the segment
+        ewf.chunks[1].len = 1 << 40;
+        ewf.chunks[2].offset = u64::MAX - 10;
+        assert!(ewf.chunk(1).is_err());
+        assert!(ewf.chunk(2).is_err());
+    }
+
+    // offset of the data of the first section of this type
+    fn section(segment: &[u8], r#type: &[u8]) -> usize {
+        let mut pos = FILE_HEADER_LEN as usize;
+        while !segment[pos..].starts_with(r#type) {
+            pos = le_u64(segment, pos + 16) as usize;
+        }
+        pos + SECTION_DESCRIPTOR_LEN as usize
+    }
+
+    #[test]
+    fn invalid_sections() {
+        let segment = unzip("tests/test.E01.zip").remove(0);
+        let add = |segment: Vec<u8>| Ewf::new().add_segment(Box::new(segment));
+        assert!(add(segment.clone()).is_ok());
+
+        // 1000 bytes sectors, 8 GiB chunks
+        let volume = section(&segment, b"volume");
+        let mut sectors = segment.clone();
+        sectors[volume + 12..volume + 16].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(add(sectors).is_err());
+        let mut chunks = segment.clone();
+        chunks[volume + 8..volume + 12].copy_from_slice(&(1u32 << 24).to_le_bytes());
+        assert!(add(chunks).is_err());
+
+        // chunk offsets overflowing
+        let table = section(&segment, b"table");
+        let mut base = segment.clone();
+        base[table + 8..table + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(add(base).is_err());
+    }
+
+    #[test]
+    fn header_bomb() {
+        use std::io::Write;
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        zlib.write_all(&vec![b'a'; 2 * MAX_HEADER_LEN as usize])
+            .unwrap();
+        let data = decompress(&zlib.finish().unwrap()).unwrap();
+        assert_eq!(data.len() as u64, MAX_HEADER_LEN);
+    }
+
+    #[test]
+    fn header() {
+        let text = b"1\r\nmain\r\nc\tn\te\tm\r\n42\t1\tJohn\t2024 3 5 14 20 31\r\n\r\n";
+        let header = parse_header(text);
+        assert_eq!(header.case_number.as_deref(), Some("42"));
+        assert_eq!(header.examiner.as_deref(), Some("John"));
+        assert_eq!(
+            header.acquired,
+            Some(UNIX_EPOCH + Duration::from_secs(1709648431))
+        );
+    }
+
+    #[test]
+    fn segments() {
+        let path = Path::new("case/disk.E01");
+        assert_eq!(segment_path(path, 2).unwrap(), Path::new("case/disk.E02"));
+        assert_eq!(segment_path(path, 100).unwrap(), Path::new("case/disk.EAA"));
+        assert_eq!(segment_path(path, 127).unwrap(), Path::new("case/disk.EBB"));
+        assert_eq!(
+            segment_path(Path::new("disk.e01"), 776).unwrap(),
+            Path::new("disk.faa")
+        );
+    }
+}
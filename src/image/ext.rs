@@ -0,0 +1,945 @@
+// ext2/3/4, read-only. Unallocated inodes still having timestamps are reported as deleted
+// files, named from the directory entries left in directory slack when possible
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    io,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::bail;
+use log::warn;
+
+use crate::{
+    fileinfo::{ForensicsFileType, ALLOCATED, DELETED},
+    image::{
+        le_u16, le_u32, Content, FileSystem, ImageFile, Run, Runs, Source, Volume, Walk,
+        MAX_WALK_DEPTH,
+    },
+};
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const EXT_MAGIC: u16 = 0xEF53;
+const ROOT_INODE: u32 = 2;
+
+// revision 0 has fixed inode size and first inode
+const GOOD_OLD_INODE_SIZE: u64 = 128;
+const GOOD_OLD_FIRST_INODE: u32 = 11;
+
+// features
+const INCOMPAT_META_BG: u32 = 0x10;
+const INCOMPAT_64BIT: u32 = 0x80;
+const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+
+// block group flags
+const BG_INODE_UNINIT: u16 = 0x1;
+const BG_BLOCK_UNINIT: u16 = 0x2;
+
+// inode flags
+const EXTENTS_FL: u32 = 0x80000;
+const INLINE_DATA_FL: u32 = 0x1000_0000;
+
+// i_mode file types
+const S_IFMT: u16 = 0xF000;
+const S_IFREG: u16 = 0x8000;
+const S_IFDIR: u16 = 0x4000;
+const S_IFLNK: u16 = 0xA000;
+
+// i_block holds 15 block numbers: 12 direct, then simple, double and triple indirect ones
+const I_BLOCK_LEN: usize = 60;
+const DIRECT_BLOCKS: usize = 12;
+
+// extent tree
+const EXTENT_MAGIC: u16 = 0xF30A;
+const EXTENT_MAX_DEPTH: u16 = 5;
+const EXTENT_INIT_MAX_LEN: u16 = 32768;
+
+// directory entries
+const DIR_ENTRY_HEADER: usize = 8;
+
+// where deleted inodes whose name wasn't found are reported, as done by The Sleuth Kit
+const ORPHAN_FILES: &str = "$OrphanFiles";
+
+pub struct Ext {
+    volume: Volume,
+    root: String,
+}
+
+impl Ext {
+    pub fn new(volume: Volume, root: &str) -> Self {
+        Self {
+            volume,
+            root: root.to_string(),
+        }
+    }
+}
+
+impl FileSystem for Ext {
+    fn walk(&mut self, visit: &mut dyn FnMut(ImageFile) -> Walk) -> anyhow::Result<bool> {
+        let mut reader = Reader::new(&self.volume)?;
+
+        reader.walked.insert(ROOT_INODE);
+        if reader.walk_dir(ROOT_INODE, &self.root, 1, visit)? {
+            return Ok(true);
+        }
+        reader.walk_deleted(&self.root, visit)
+    }
+}
+
+struct Group {
+    block_bitmap: u64,
+    inode_bitmap: u64,
+    inode_table: u64,
+    flags: u16,
+
+    // inodes at the end of the table which were never used
+    unused_inodes: u32,
+}
+
+struct Inode {
+    mode: u16,
+    uid: u32,
+    gid: u32,
+    size: u64,
+    links: u16,
+    flags: u32,
+    block: [u8; I_BLOCK_LEN],
+
+    accessed: Option<SystemTime>,
+    changed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+    deleted: Option<SystemTime>,
+    created: Option<SystemTime>,
+}
+
+impl Inode {
+    fn file_type(&self) -> ForensicsFileType {
+        match self.mode & S_IFMT {
+            S_IFREG => ForensicsFileType::File,
+            S_IFDIR => ForensicsFileType::Directory,
+            S_IFLNK => ForensicsFileType::Symlink,
+            _ => ForensicsFileType::Unknown,
+        }
+    }
+}
+
+struct Reader {
+    volume: Volume,
+    block_size: u64,
+    blocks: u64,
+    first_data_block: u64,
+    blocks_per_group: u64,
+    inodes: u32,
+    inodes_per_group: u32,
+    inode_size: u64,
+    first_inode: u32,
+    groups: Vec<Group>,
+
+    // bits of all groups, uninitialized ones being all zeros
+    inode_bitmap: Vec<u8>,
+    block_bitmap: Option<Vec<u8>>,
+
+    // loop protection
+    walked: HashSet<u32>,
+
+    // first path seen for inodes having several links
+    hard_links: HashMap<u32, String>,
+
+    // names of unallocated inodes found in directory slack, with their depth
+    deleted_names: HashMap<u32, (String, usize)>,
+}
+
+impl Reader {
+    fn new(volume: &Volume) -> anyhow::Result<Self> {
+        let sb = volume.read_vec(SUPERBLOCK_OFFSET, 1024)?;
+        if le_u16(&sb, 56) != EXT_MAGIC {
+            bail!("invalid ext superblock");
+        }
+
+        let log_block_size = le_u32(&sb, 24);
+        if log_block_size > 6 {
+            bail!("invalid ext block size");
+        }
+        let block_size = 1024u64 << log_block_size;
+        let incompat = le_u32(&sb, 96);
+        let ro_compat = le_u32(&sb, 100);
+        if incompat & INCOMPAT_META_BG != 0 {
+            bail!("ext meta_bg feature is not supported");
+        }
+
+        let (inode_size, first_inode) = if le_u32(&sb, 76) == 0 {
+            (GOOD_OLD_INODE_SIZE, GOOD_OLD_FIRST_INODE)
+        } else {
+            (le_u16(&sb, 88) as u64, le_u32(&sb, 84))
+        };
+        let is_64bit = incompat & INCOMPAT_64BIT != 0;
+        let blocks = le_u32(&sb, 4) as u64
+            | if is_64bit {
+                (le_u32(&sb, 0x150) as u64) << 32
+            } else {
+                0
+            };
+        let first_data_block = le_u32(&sb, 20) as u64;
+        let blocks_per_group = le_u32(&sb, 32) as u64;
+        let inodes_per_group = le_u32(&sb, 40);
+        if blocks_per_group == 0 || inodes_per_group == 0 || inode_size < GOOD_OLD_INODE_SIZE {
+            bail!("invalid ext superblock");
+        }
+
+        // group descriptors follow the superblock
+        let desc_size = if is_64bit {
+            le_u16(&sb, 254).max(32) as usize
+        } else {
+            32
+        };
+        let count = blocks
+            .saturating_sub(first_data_block)
+            .div_ceil(blocks_per_group) as usize;
+        let Some(len) = count.checked_mul(desc_size) else {
+            bail!("invalid ext block count {blocks}");
+        };
+        let table = volume.read_vec((first_data_block + 1) * block_size, len)?;
+
+        // unused inodes are only tracked with group descriptor checksums
+        let csum = ro_compat & (RO_COMPAT_GDT_CSUM | RO_COMPAT_METADATA_CSUM) != 0;
+        let groups: Vec<Group> = table
+            .chunks_exact(desc_size)
+            .map(|d| {
+                let hi = |offset| {
+                    if desc_size >= 64 {
+                        (le_u32(d, offset) as u64) << 32
+                    } else {
+                        0
+                    }
+                };
+                let unused_hi = if desc_size >= 64 { le_u16(d, 0x32) } else { 0 };
+                Group {
+                    block_bitmap: le_u32(d, 0) as u64 | hi(0x20),
+                    inode_bitmap: le_u32(d, 4) as u64 | hi(0x24),
+                    inode_table: le_u32(d, 8) as u64 | hi(0x28),
+                    flags: le_u16(d, 0x12),
+                    unused_inodes: if csum {
+                        (unused_hi as u32) << 16 | le_u16(d, 0x1C) as u32
+                    } else {
+                        0
+                    },
+                }
+            })
+            .collect();
+
+        let mut reader = Self {
+            volume: volume.clone(),
+            block_size,
+            blocks,
+            first_data_block,
+            blocks_per_group,
+            inodes: le_u32(&sb, 0),
+            inodes_per_group,
+            inode_size,
+            first_inode,
+            groups,
+            inode_bitmap: Vec::new(),
+            block_bitmap: None,
+            walked: HashSet::new(),
+            hard_links: HashMap::new(),
+            deleted_names: HashMap::new(),
+        };
+        reader.inode_bitmap =
+            reader.bitmap(|g| g.inode_bitmap, BG_INODE_UNINIT, inodes_per_group as u64)?;
+        Ok(reader)
+    }
+
+    // bitmaps of all groups concatenated
+    fn bitmap(
+        &self,
+        block: impl Fn(&Group) -> u64,
+        uninit: u16,
+        bits_per_group: u64,
+    ) -> io::Result<Vec<u8>> {
+        let len = bits_per_group.div_ceil(8) as usize;
+        let mut bitmap = Vec::with_capacity(len * self.groups.len());
+
+        for group in &self.groups {
+            if group.flags & uninit != 0 {
+                bitmap.resize(bitmap.len() + len, 0);
+            } else {
+                let mut bits = self.volume.read_vec(block(group) * self.block_size, len)?;
+                bitmap.append(&mut bits);
+            }
+        }
+        Ok(bitmap)
+    }
+
+    fn is_allocated(&self, ino: u32) -> bool {
+        let n = (ino - 1) as usize;
+        self.inode_bitmap
+            .get(n / 8)
+            .is_some_and(|b| b & (1 << (n % 8)) != 0)
+    }
+
+    fn is_block_free(&self, block: u64) -> bool {
+        let Some(bitmap) = &self.block_bitmap else {
+            return false;
+        };
+        let Some(n) = block.checked_sub(self.first_data_block) else {
+            return false;
+        };
+
+        // each group bitmap is padded to a whole byte
+        let per_group = self.blocks_per_group.div_ceil(8) * 8;
+        let n = (n / self.blocks_per_group * per_group + n % self.blocks_per_group) as usize;
+        bitmap.get(n / 8).is_some_and(|b| b & (1 << (n % 8)) == 0)
+    }
+
+    fn inode(&self, ino: u32) -> anyhow::Result<Inode> {
+        if ino == 0 || ino > self.inodes {
+            bail!("invalid inode {ino}");
+        }
+        let Some(group) = self
+            .groups
+            .get(((ino - 1) / self.inodes_per_group) as usize)
+        else {
+            bail!("no group for inode {ino}");
+        };
+        let index = ((ino - 1) % self.inodes_per_group) as u64;
+
+        let raw = self.volume.read_vec(
+            group
+                .inode_table
+                .saturating_mul(self.block_size)
+                .saturating_add(index * self.inode_size),
+            self.inode_size as usize,
+        )?;
+        Ok(parse_inode(&raw))
+    }
+
+    // returns true when the walk has to stop
+    fn walk_dir(
+        &mut self,
+        ino: u32,
+        path: &str,
+        depth: usize,
+        visit: &mut dyn FnMut(ImageFile) -> Walk,
+    ) -> anyhow::Result<bool> {
+        let dir = self.inode(ino)?;
+        let data = self.mapped_data(&dir)?;
+
+        // inline directories start with the parent inode
+        let entries = if dir.flags & INLINE_DATA_FL != 0 {
+            data.get(4..).unwrap_or_default()
+        } else {
+            &data
+        };
+        let (entries, slack) = dir_entries(entries);
+
+        for (child, name) in slack {
+            if child <= self.inodes && !self.is_allocated(child) {
+                self.deleted_names
+                    .entry(child)
+                    .or_insert_with(|| (format!("{path}/{name}"), depth));
+            }
+        }
+
+        for (child, name) in entries {
+            let path = format!("{path}/{name}");
+            let inode = match self.inode(child) {
+                Ok(inode) => inode,
+                Err(e) => {
+                    warn!("unable to read inode of '{path}': {e}");
+                    continue;
+                }
+            };
+
+            let file = self.image_file(path.clone(), child, &inode, depth, false);
+            match visit(file) {
+                Walk::Stop => return Ok(true),
+                Walk::Skip => continue,
+                Walk::Continue => (),
+            }
+
+            if inode.file_type() != ForensicsFileType::Directory || !self.walked.insert(child) {
+                continue;
+            }
+            if depth >= MAX_WALK_DEPTH {
+                warn!("'{path}' is nested too deeply, not walked");
+                continue;
+            }
+            match self.walk_dir(child, &path, depth + 1, visit) {
+                Ok(true) => return Ok(true),
+                Ok(false) => (),
+                Err(e) => warn!("unable to read directory '{path}': {e}"),
+            }
+        }
+
+        Ok(false)
+    }
+
+    // unallocated inodes which were used once, i.e. having timestamps. Returns true
+    // when the walk has to stop
+    fn walk_deleted(
+        &mut self,
+        root: &str,
+        visit: &mut dyn FnMut(ImageFile) -> Walk,
+    ) -> anyhow::Result<bool> {
+        self.block_bitmap =
+            Some(self.bitmap(|g| g.block_bitmap, BG_BLOCK_UNINIT, self.blocks_per_group)?);
+
+        for g in 0..self.groups.len() {
+            let group = &self.groups[g];
+            if group.flags & BG_INODE_UNINIT != 0 {
+                continue;
+            }
+            let used = self.inodes_per_group.saturating_sub(group.unused_inodes);
+            let table = self.volume.read_vec(
+                group.inode_table.saturating_mul(self.block_size),
+                (used as u64 * self.inode_size) as usize,
+            )?;
+
+            for (i, raw) in table.chunks_exact(self.inode_size as usize).enumerate() {
+                // more groups than needed for the inode count
+                let ino = g as u64 * self.inodes_per_group as u64 + i as u64 + 1;
+                if ino > self.inodes as u64 {
+                    break;
+                }
+                let ino = ino as u32;
+                if ino < self.first_inode || self.is_allocated(ino) {
+                    continue;
+                }
+                let inode = parse_inode(raw);
+                if inode.deleted.is_none() && inode.changed.is_none() {
+                    continue;
+                }
+
+                let (path, depth) = self
+                    .deleted_names
+                    .get(&ino)
+                    .cloned()
+                    .unwrap_or_else(|| (format!("{root}/{ORPHAN_FILES}/OrphanFile-{ino}"), 2));
+                let file = self.image_file(path, ino, &inode, depth, true);
+                if visit(file) == Walk::Stop {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn image_file(
+        &mut self,
+        path: String,
+        ino: u32,
+        inode: &Inode,
+        depth: usize,
+        deleted: bool,
+    ) -> ImageFile {
+        let r#type = inode.file_type();
+        let mut file = ImageFile::new(path, r#type, inode.size);
+        file.depth = depth;
+
+        let fi = &mut file.fi;
+        fi.inode = Some(ino as i64);
+        fi.uid = Some(inode.uid as i64);
+        fi.gid = Some(inode.gid as i64);
+        fi.mode = Some(inode.mode as i32);
+        #[cfg(unix)]
+        {
+            fi.permissions = Some(crate::unix::permissions(inode.mode as u32));
+        }
+        fi.nlink = Some(inode.links as i64);
+        fi.created = inode.created;
+        fi.accessed = inode.accessed.unwrap_or(UNIX_EPOCH);
+        fi.modified = inode.modified.unwrap_or(UNIX_EPOCH);
+        fi.changed = inode.changed;
+        fi.deleted = inode.deleted;
+        fi.allocation = Some(if deleted { DELETED } else { ALLOCATED }.to_string());
+
+        match r#type {
+            ForensicsFileType::File if !deleted && inode.links > 1 => {
+                match self.hard_links.entry(ino) {
+                    Entry::Occupied(first) => fi.hardlink_of = Some(first.get().clone()),
+                    Entry::Vacant(e) => _ = e.insert(fi.path.clone()),
+                }
+            }
+            ForensicsFileType::Symlink => fi.link_target = self.link_target(inode),
+            _ => (),
+        }
+
+        if r#type == ForensicsFileType::File && fi.hardlink_of.is_none() {
+            // the entry is still saved, like NTFS files whose data can't be read
+            let content = self.content(inode).unwrap_or_else(|e| {
+                warn!("unable to read the content of '{}': {e}", fi.path);
+                None
+            });
+
+            // blocks of deleted files could have been reused since, and their size be
+            // anything: it can't be larger than the volume
+            file.content = content.filter(|c| {
+                !deleted || (inode.size <= self.volume.len() && self.is_recoverable(c))
+            });
+            if inode.flags & INLINE_DATA_FL == 0 {
+                file.fi.extents = file.content.as_ref().map(Content::extents);
+            }
+        }
+
+        file
+    }
+
+    // fast symbolic links are stored in i_block
+    fn link_target(&self, inode: &Inode) -> Option<String> {
+        let target = if inode.size < I_BLOCK_LEN as u64
+            && inode.flags & (EXTENTS_FL | INLINE_DATA_FL) == 0
+        {
+            inode.block[..inode.size as usize].to_vec()
+        } else {
+            self.mapped_data(inode).ok()?
+        };
+        Some(String::from_utf8_lossy(&target).into_owned())
+    }
+
+    // data of directories and symbolic links, only read from their mapped blocks as the
+    // size of a corrupted inode could be anything
+    fn mapped_data(&self, inode: &Inode) -> anyhow::Result<Vec<u8>> {
+        if inode.size > self.volume.len() {
+            bail!("invalid inode size {}", inode.size);
+        }
+
+        let mut data = Vec::new();
+        if let Some(content) = self.content(inode)? {
+            for run in &content.runs {
+                if let Some(offset) = run.offset {
+                    data.append(&mut content.volume.read_vec(offset, run.len as usize)?);
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    fn is_recoverable(&self, content: &Content) -> bool {
+        let mut data = content.runs.iter().filter_map(|r| Some((r.offset?, r.len)));
+        let mut has_data = false;
+
+        let all_free = data.all(|(offset, len)| {
+            has_data = true;
+            let first = offset / self.block_size;
+            (first..(offset + len).div_ceil(self.block_size)).all(|b| self.is_block_free(b))
+        });
+        has_data && all_free
+    }
+
+    // file data from extents, block map or inline data
+    fn content(&self, inode: &Inode) -> anyhow::Result<Option<Content>> {
+        if inode.size == 0 {
+            return Ok(None);
+        }
+
+        if inode.flags & INLINE_DATA_FL != 0 {
+            // data beyond i_block, in the system.data attribute, is ignored
+            let len = inode.size.min(I_BLOCK_LEN as u64);
+            let data = inode.block[..len as usize].to_vec();
+            return Ok(Some(Content {
+                volume: Volume::from(Arc::new(data) as Arc<dyn Source>),
+                runs: vec![Run {
+                    offset: Some(0),
+                    len,
+                }],
+            }));
+        }
+
+        let mut runs = Runs::new(inode.size);
+        if inode.flags & EXTENTS_FL != 0 {
+            // each extent holds at least a block
+            let max = inode.size.div_ceil(self.block_size) as usize;
+            let mut extents = Vec::new();
+            self.extents(&inode.block, EXTENT_MAX_DEPTH, &mut extents, max)?;
+            extents.sort_by_key(|e| e.0);
+
+            let mut next = 0;
+            for (logical, len, physical) in extents {
+                if logical < next {
+                    continue;
+                }
+                runs.push(None, (logical - next) * self.block_size);
+                runs.push(physical.map(|p| p * self.block_size), len * self.block_size);
+                next = logical + len;
+            }
+        } else {
+            // sizes of deleted inodes could be anything
+            let count = inode.size.div_ceil(self.block_size).min(self.blocks);
+            let mut blocks = 0;
+            for (i, ptr) in inode.block.chunks_exact(4).enumerate() {
+                let level = i.saturating_sub(DIRECT_BLOCKS - 1) as u32;
+                self.block_map(le_u32(ptr, 0), level, &mut runs, &mut blocks, count)?;
+            }
+        }
+
+        Ok(Some(Content {
+            volume: self.volume.clone(),
+            runs: runs.finish(),
+        }))
+    }
+
+    // leaves of an extent tree node, as (logical block, length, physical block), up to max
+    fn extents(
+        &self,
+        node: &[u8],
+        depth: u16,
+        extents: &mut Vec<(u64, u64, Option<u64>)>,
+        max: usize,
+    ) -> anyhow::Result<()> {
+        if le_u16(node, 0) != EXTENT_MAGIC {
+            bail!("invalid extent header");
+        }
+        let entries = le_u16(node, 2) as usize;
+        let node_depth = le_u16(node, 6);
+        if node_depth >= depth {
+            bail!("extent tree too deep");
+        }
+
+        for e in node[12..].chunks_exact(12).take(entries) {
+            if extents.len() >= max {
+                break;
+            }
+            if node_depth == 0 {
+                let len = le_u16(e, 4);
+                let physical = (le_u16(e, 6) as u64) << 32 | le_u32(e, 8) as u64;
+
+                // uninitialized extents are read as zeros
+                let (len, physical) = if len > EXTENT_INIT_MAX_LEN {
+                    (len - EXTENT_INIT_MAX_LEN, None)
+                } else {
+                    (len, Some(physical))
+                };
+                let physical = physical.filter(|p| p + len as u64 <= self.blocks);
+                extents.push((le_u32(e, 0) as u64, len as u64, physical));
+            } else {
+                let leaf = (le_u16(e, 8) as u64) << 32 | le_u32(e, 4) as u64;
+                if leaf >= self.blocks {
+                    bail!("invalid extent index");
+                }
+                let child = self
+                    .volume
+                    .read_vec(leaf * self.block_size, self.block_size as usize)?;
+                self.extents(&child, node_depth, extents, max)?;
+            }
+        }
+        Ok(())
+    }
+
+    // runs of direct or indirect blocks, up to count blocks. Missing blocks and tables
+    // are holes, pushed as a single run
+    fn block_map(
+        &self,
+        ptr: u32,
+        level: u32,
+        runs: &mut Runs,
+        blocks: &mut u64,
+        count: u64,
+    ) -> io::Result<()> {
+        if *blocks >= count {
+            return Ok(());
+        }
+        let ptr = ptr as u64;
+        if ptr == 0 || ptr >= self.blocks {
+            let holes = (self.block_size / 4)
+                .saturating_pow(level)
+                .min(count - *blocks);
+            runs.push(None, holes.saturating_mul(self.block_size));
+            *blocks += holes;
+            return Ok(());
+        }
+        if level == 0 {
+            runs.push(Some(ptr * self.block_size), self.block_size);
+            *blocks += 1;
+            return Ok(());
+        }
+
+        let table = self
+            .volume
+            .read_vec(ptr * self.block_size, self.block_size as usize)?;
+        for p in table.chunks_exact(4) {
+            self.block_map(le_u32(p, 0), level - 1, runs, blocks, count)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_inode(raw: &[u8]) -> Inode {
+    let extra = if raw.len() > 128 {
+        (le_u16(raw, 128) as usize).min(raw.len() - 128)
+    } else {
+        0
+    };
+
+    // nanoseconds and epoch bits in the extra fields, if large enough
+    let time = |offset: usize, extra_offset: usize| {
+        let extra = (extra >= extra_offset - 128 + 4).then(|| le_u32(raw, extra_offset));
+        ext_time(le_u32(raw, offset), extra)
+    };
+
+    Inode {
+        mode: le_u16(raw, 0),
+        uid: (le_u16(raw, 120) as u32) << 16 | le_u16(raw, 2) as u32,
+        gid: (le_u16(raw, 122) as u32) << 16 | le_u16(raw, 24) as u32,
+        size: (le_u32(raw, 108) as u64) << 32 | le_u32(raw, 4) as u64,
+        links: le_u16(raw, 26),
+        flags: le_u32(raw, 32),
+        block: raw[40..40 + I_BLOCK_LEN].try_into().unwrap(),
+        accessed: time(8, 140),
+        changed: time(12, 132),
+        modified: time(16, 136),
+        deleted: ext_time(le_u32(raw, 20), None),
+        created: (extra >= 24).then(|| time(144, 148)).flatten(),
+    }
+}
+
+// seconds are signed 32 bits, extended by 2 epoch bits next to the nanoseconds
+fn ext_time(secs: u32, extra: Option<u32>) -> Option<SystemTime> {
+    let extra = extra.unwrap_or_default();
+    if secs == 0 && extra == 0 {
+        return None;
+    }
+
+    let secs = secs as i32 as i64 + (((extra & 0x3) as i64) << 32);
+    let nanos = Duration::from_nanos((extra >> 2) as u64);
+    let since = Duration::from_secs(secs.unsigned_abs());
+    let time = if secs >= 0 {
+        UNIX_EPOCH.checked_add(since)?
+    } else {
+        UNIX_EPOCH.checked_sub(since)?
+    };
+    time.checked_add(nanos)
+}
+
+// (inode, name) of the live entries, and of the deleted ones found in the space left
+// by each entry
+#[allow(clippy::type_complexity)]
+fn dir_entries(data: &[u8]) -> (Vec<(u32, String)>, Vec<(u32, String)>) {
+    let mut entries = Vec::new();
+    let mut slack = Vec::new();
+    let mut pos = 0;
+
+    while pos + DIR_ENTRY_HEADER <= data.len() {
+        let ino = le_u32(data, pos);
+        let rec_len = le_u16(data, pos + 4) as usize;
+        let name_len = data[pos + 6] as usize;
+        if rec_len < DIR_ENTRY_HEADER || pos + rec_len > data.len() {
+            break;
+        }
+
+        let mut used = 0;
+        if ino != 0 && DIR_ENTRY_HEADER + name_len <= rec_len {
+            let name = &data[pos + DIR_ENTRY_HEADER..pos + DIR_ENTRY_HEADER + name_len];
+            if name != b"." && name != b".." {
+                entries.push((ino, String::from_utf8_lossy(name).into_owned()));
+            }
+            used = (DIR_ENTRY_HEADER + name_len).next_multiple_of(4);
+        }
+
+        // removing an entry extends the previous one, leaving it in place
+        let mut deleted = pos + used;
+        while deleted + DIR_ENTRY_HEADER < pos + rec_len {
+            let ino = le_u32(data, deleted);
+            let name_len = data[deleted + 6] as usize;
+            let end = deleted + DIR_ENTRY_HEADER + name_len;
+            if ino == 0 || name_len == 0 || end > pos + rec_len {
+                break;
+            }
+            let name = &data[deleted + DIR_ENTRY_HEADER..end];
+            if name.contains(&0) || name.contains(&b'/') {
+                break;
+            }
+            slack.push((ino, String::from_utf8_lossy(name).into_owned()));
+            deleted = end.next_multiple_of(4);
+        }
+
+        pos += rec_len;
+    }
+
+    (entries, slack)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    // 256 KiB ext4 without journal: etc/hosts (setuid, hard linked as hosts.hard),
+    // docs/big.txt, docs/hosts.lnk -> ../etc/hosts and docs/secret.txt removed with debugfs
+    fn image() -> Vec<u8> {
+        let file = std::fs::File::open("tests/test.ext4.zip").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        let mut data = Vec::new();
+        zip.by_index(0).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn ext4() {
+        let volume = Volume::from(Arc::new(image()) as Arc<dyn Source>);
+        let mut files = Vec::new();
+        Ext::new(volume, "img!/p0")
+            .walk(&mut |file| {
+                let mut data = Vec::new();
+                if let Some(content) = &file.content {
+                    content.reader().read_to_end(&mut data).unwrap();
+                }
+                files.push((file.fi, data));
+                Walk::Continue
+            })
+            .unwrap();
+
+        let find = |path: &str| files.iter().find(|(fi, _)| fi.path == path).unwrap();
+
+        let (hosts, data) = find("img!/p0/etc/hosts");
+        assert_eq!(data, b"127.0.0.1 localhost\n");
+        assert_eq!(hosts.mode, Some(0o104755));
+        assert_eq!(hosts.nlink, Some(2));
+        assert!(hosts.created.is_some());
+
+        let (hard, _) = find("img!/p0/hosts.hard");
+        assert_eq!(hard.hardlink_of.as_deref(), Some("img!/p0/etc/hosts"));
+
+        let (big, data) = find("img!/p0/docs/big.txt");
+        assert_eq!(data.len(), 5290);
+        assert!(data.starts_with(b"line 0\nline 1\n"));
+        assert_eq!(big.allocation.as_deref(), Some(ALLOCATED));
+
+        let (link, _) = find("img!/p0/docs/hosts.lnk");
+        assert_eq!(link.link_target.as_deref(), Some("../etc/hosts"));
+
+        // deleted inode, named from the directory slack
+        let (secret, data) = find("img!/p0/docs/secret.txt");
+        assert_eq!(secret.allocation.as_deref(), Some(DELETED));
+        assert!(secret.deleted.is_some());
+        assert_eq!(data, b"secret stuff to delete\n");
+    }
+
+    // inode number of a path, and the offset of that inode in the image
+    fn inode_at(data: &[u8], path: &str) -> (u32, usize) {
+        let volume = Volume::from(Arc::new(data.to_vec()) as Arc<dyn Source>);
+        let mut ino = 0;
+        Ext::new(volume.clone(), "img!/p0")
+            .walk(&mut |file| {
+                if file.fi.path == path {
+                    ino = file.fi.inode.unwrap() as u32;
+                }
+                Walk::Continue
+            })
+            .unwrap();
+
+        let reader = Reader::new(&volume).unwrap();
+        let group = &reader.groups[((ino - 1) / reader.inodes_per_group) as usize];
+        let index = ((ino - 1) % reader.inodes_per_group) as u64;
+        let offset = group.inode_table * reader.block_size + index * reader.inode_size;
+        (ino, offset as usize)
+    }
+
+    #[test]
+    fn unreadable_content() {
+        let mut data = image();
+
+        // extent header of big.txt
+        let (ino, offset) = inode_at(&data, "img!/p0/docs/big.txt");
+        data[offset + 40..offset + 42].fill(0);
+
+        let mut files = Vec::new();
+        Ext::new(Volume::from(Arc::new(data) as Arc<dyn Source>), "img!/p0")
+            .walk(&mut |file| {
+                files.push(file);
+                Walk::Continue
+            })
+            .unwrap();
+
+        let big = files
+            .iter()
+            .find(|f| f.fi.inode == Some(ino as i64))
+            .unwrap();
+        assert!(big.content.is_none());
+        assert!(files.iter().any(|f| f.fi.path == "img!/p0/etc/hosts"));
+    }
+
+    // sizes of corrupted inodes could be anything
+    #[test]
+    fn huge_sizes() {
+        let mut data = image();
+        let (_, docs) = inode_at(&data, "img!/p0/docs");
+        let (secret, deleted) = inode_at(&data, "img!/p0/docs/secret.txt");
+        let (_, link) = inode_at(&data, "img!/p0/docs/hosts.lnk");
+        for offset in [docs, deleted, link] {
+            data[offset + 108..offset + 112].fill(0xFF);
+        }
+
+        let mut files = Vec::new();
+        Ext::new(Volume::from(Arc::new(data) as Arc<dyn Source>), "img!/p0")
+            .walk(&mut |file| {
+                files.push(file);
+                Walk::Continue
+            })
+            .unwrap();
+
+        // docs isn't walked, its deleted file is then an orphan without content
+        let paths: Vec<_> = files.iter().map(|f| f.fi.path.as_str()).collect();
+        assert!(paths.contains(&"img!/p0/docs"));
+        assert!(!paths.contains(&"img!/p0/docs/big.txt"));
+        let orphan = format!("img!/p0/{ORPHAN_FILES}/OrphanFile-{secret}");
+        let secret = files.iter().find(|f| f.fi.path == orphan).unwrap();
+        assert!(secret.content.is_none());
+    }
+
+    // holes of indirect blocks aren't mapped block by block
+    #[test]
+    fn block_map_holes() {
+        let volume = Volume::from(Arc::new(image()) as Arc<dyn Source>);
+        let mut reader = Reader::new(&volume).unwrap();
+        reader.blocks = u32::MAX as u64;
+
+        let mut inode = parse_inode(&[0; 256]);
+        inode.size = 1 << 40;
+        inode.block[..4].copy_from_slice(&1u32.to_le_bytes());
+        let content = reader.content(&inode).unwrap().unwrap();
+        let runs: Vec<_> = content.runs.iter().map(|r| (r.offset, r.len)).collect();
+        assert_eq!(
+            runs,
+            [
+                (Some(reader.block_size), reader.block_size),
+                (None, (1 << 40) - reader.block_size)
+            ]
+        );
+    }
+
+    // group descriptors of a huge block count
+    #[test]
+    fn block_count_overflow() {
+        let mut data = image();
+        let sb = SUPERBLOCK_OFFSET as usize;
+        data[sb + 32..sb + 36].copy_from_slice(&1u32.to_le_bytes());
+        data[sb + 96..sb + 100].copy_from_slice(&INCOMPAT_64BIT.to_le_bytes());
+        data[sb + 254..sb + 256].copy_from_slice(&64u16.to_le_bytes());
+        data[sb + 4..sb + 8].fill(0xFF);
+        data[sb + 0x150..sb + 0x154].fill(0xFF);
+
+        let volume = Volume::from(Arc::new(data) as Arc<dyn Source>);
+        assert!(Reader::new(&volume).is_err());
+    }
+
+    #[test]
+    fn slack() {
+        // "a" whose record was extended over the removed "bb"
+        let mut block = vec![0u8; 64];
+        block[0..4].copy_from_slice(&12u32.to_le_bytes());
+        block[4..6].copy_from_slice(&64u16.to_le_bytes());
+        block[6] = 1;
+        block[8] = b'a';
+        block[12..16].copy_from_slice(&13u32.to_le_bytes());
+        block[16..18].copy_from_slice(&52u16.to_le_bytes());
+        block[18] = 2;
+        block[20..22].copy_from_slice(b"bb");
+
+        let (entries, slack) = dir_entries(&block);
+        assert_eq!(entries, [(12, "a".to_string())]);
+        assert_eq!(slack, [(13, "bb".to_string())]);
+    }
+}
@@ -0,0 +1,969 @@
+// FAT12/16/32 and exFAT, read-only. Deleted directory entries are reported too, their
+// content being recovered when their clusters are still free
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::bail;
+use chrono::{NaiveDate, NaiveDateTime};
+use log::warn;
+
+use crate::{
+    fileinfo::{ForensicsFileType, ALLOCATED, DELETED},
+    image::{
+        le_u16, le_u32, le_u64, Content, FileSystem, ImageFile, Run, Runs, Volume, Walk,
+        MAX_WALK_DEPTH,
+    },
+};
+
+const DIR_ENTRY_SIZE: usize = 32;
+const FIRST_CLUSTER: u32 = 2;
+
+// FAT directory entries
+const FAT_DELETED: u8 = 0xE5;
+const FAT_KANJI_E5: u8 = 0x05;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const LFN_LAST: u8 = 0x40;
+const LOWERCASE_BASE: u8 = 0x08;
+const LOWERCASE_EXT: u8 = 0x10;
+
+// exFAT directory entry types, the in use bit being cleared for deleted entries
+const EXFAT_IN_USE: u8 = 0x80;
+const EXFAT_BITMAP: u8 = 0x81;
+const EXFAT_FILE: u8 = 0x05;
+const EXFAT_STREAM: u8 = 0x40;
+const EXFAT_NAME: u8 = 0x41;
+const EXFAT_NO_FAT_CHAIN: u8 = 0x02;
+const EXFAT_NAME_CHARS: usize = 15;
+
+// below these cluster counts, the FAT is FAT12 or FAT16
+const MAX_FAT12_CLUSTERS: u32 = 4085;
+const MAX_FAT16_CLUSTERS: u32 = 65525;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Fat12,
+    Fat16,
+    Fat32,
+    ExFat,
+}
+
+pub struct Fat {
+    volume: Volume,
+    root: String,
+}
+
+impl Fat {
+    pub fn new(volume: Volume, root: &str) -> Self {
+        Self {
+            volume,
+            root: root.to_string(),
+        }
+    }
+}
+
+impl FileSystem for Fat {
+    fn walk(&mut self, visit: &mut dyn FnMut(ImageFile) -> Walk) -> anyhow::Result<bool> {
+        let boot = self.volume.read_vec(0, 512)?;
+        let mut reader = if &boot[3..11] == b"EXFAT   " {
+            Reader::exfat(&self.volume, &boot)?
+        } else {
+            Reader::fat(&self.volume, &boot)?
+        };
+
+        let root = reader.read_root()?;
+        reader.walk_dir(&root, &self.root, 1, false, visit)
+    }
+}
+
+// a file or directory found in a directory
+#[derive(Debug)]
+struct DirEntry {
+    name: String,
+    is_dir: bool,
+    deleted: bool,
+    len: u64,
+    first_cluster: u32,
+
+    // exFAT files could be stored without FAT chain
+    contiguous: bool,
+
+    created: Option<SystemTime>,
+    accessed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+}
+
+struct Reader {
+    volume: Volume,
+    kind: Kind,
+    cluster_size: u64,
+
+    // offset of the first cluster (numbered 2)
+    data_offset: u64,
+    clusters: u32,
+
+    // FAT entries, indexed by cluster number
+    fat: Vec<u32>,
+
+    // FAT12/16 root directory is outside of the data area
+    root_dir: Option<(u64, u64)>,
+    root_cluster: u32,
+
+    // exFAT clusters in use, FAT entries being kept when files are deleted
+    bitmap: Option<Vec<u8>>,
+
+    // first cluster of the directories already walked, as a loop protection
+    walked: HashSet<u32>,
+}
+
+impl Reader {
+    fn fat(volume: &Volume, boot: &[u8]) -> anyhow::Result<Self> {
+        let bytes_per_sector = le_u16(boot, 11) as u64;
+        let sectors_per_cluster = boot[13] as u64;
+        let reserved = le_u16(boot, 14) as u64;
+        let fats = boot[16] as u64;
+        let root_entries = le_u16(boot, 17) as u64;
+        let fat_sectors = match le_u16(boot, 22) {
+            0 => le_u32(boot, 36) as u64,
+            n => n as u64,
+        };
+        let total_sectors = match le_u16(boot, 19) {
+            0 => le_u32(boot, 32) as u64,
+            n => n as u64,
+        };
+
+        if !matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096)
+            || !sectors_per_cluster.is_power_of_two()
+            || fats == 0
+            || fat_sectors == 0
+        {
+            bail!("invalid FAT boot sector");
+        }
+
+        let root_sectors = (root_entries * DIR_ENTRY_SIZE as u64).div_ceil(bytes_per_sector);
+        let data_sector = reserved + fats * fat_sectors + root_sectors;
+        let clusters = (total_sectors.saturating_sub(data_sector) / sectors_per_cluster) as u32;
+
+        // the FAT type only depends on the number of clusters
+        let kind = if clusters < MAX_FAT12_CLUSTERS {
+            Kind::Fat12
+        } else if clusters < MAX_FAT16_CLUSTERS {
+            Kind::Fat16
+        } else {
+            Kind::Fat32
+        };
+
+        let table = volume.read_vec(
+            reserved * bytes_per_sector,
+            (fat_sectors * bytes_per_sector) as usize,
+        )?;
+        // a corrupted boot sector could claim more clusters than the FAT holds
+        let bits_per_entry = match kind {
+            Kind::Fat12 => 12,
+            Kind::Fat16 => 16,
+            _ => 32,
+        };
+        let entries =
+            (clusters as usize + FIRST_CLUSTER as usize).min(table.len() * 8 / bits_per_entry);
+        let fat = (0..entries)
+            .map(|n| match kind {
+                Kind::Fat12 => {
+                    let v = u16_at(&table, n + n / 2) as u32;
+                    if n % 2 == 0 {
+                        v & 0xFFF
+                    } else {
+                        v >> 4
+                    }
+                }
+                Kind::Fat16 => u16_at(&table, n * 2) as u32,
+                _ => u32_at(&table, n * 4) & 0x0FFF_FFFF,
+            })
+            .collect();
+
+        let root_dir = (kind != Kind::Fat32).then_some((
+            (reserved + fats * fat_sectors) * bytes_per_sector,
+            root_sectors * bytes_per_sector,
+        ));
+
+        Ok(Self {
+            volume: volume.clone(),
+            kind,
+            cluster_size: sectors_per_cluster * bytes_per_sector,
+            data_offset: data_sector * bytes_per_sector,
+            clusters,
+            fat,
+            root_dir,
+            root_cluster: le_u32(boot, 44),
+            bitmap: None,
+            walked: HashSet::new(),
+        })
+    }
+
+    fn exfat(volume: &Volume, boot: &[u8]) -> anyhow::Result<Self> {
+        let bytes_per_sector_shift = boot[108];
+        let sectors_per_cluster_shift = boot[109];
+        if !(9..=12).contains(&bytes_per_sector_shift)
+            || bytes_per_sector_shift + sectors_per_cluster_shift > 25
+        {
+            bail!("invalid exFAT boot sector");
+        }
+
+        let bytes_per_sector = 1u64 << bytes_per_sector_shift;
+        let fat_offset = le_u32(boot, 80) as u64 * bytes_per_sector;
+        let fat_length = le_u32(boot, 84) as u64 * bytes_per_sector;
+        let clusters = le_u32(boot, 92);
+
+        let entries = (clusters as u64 + FIRST_CLUSTER as u64).min(fat_length / 4);
+        let table = volume.read_vec(fat_offset, entries as usize * 4)?;
+
+        let mut reader = Self {
+            volume: volume.clone(),
+            kind: Kind::ExFat,
+            cluster_size: bytes_per_sector << sectors_per_cluster_shift,
+            data_offset: le_u32(boot, 88) as u64 * bytes_per_sector,
+            clusters,
+            fat: table.chunks_exact(4).map(|e| le_u32(e, 0)).collect(),
+            root_dir: None,
+            root_cluster: le_u32(boot, 96),
+            bitmap: None,
+            walked: HashSet::new(),
+        };
+
+        // the allocation bitmap is found in the root directory
+        let root = reader.read_root()?;
+        if let Some(entry) = root
+            .chunks_exact(DIR_ENTRY_SIZE)
+            .find(|e| e[0] == EXFAT_BITMAP)
+        {
+            let clusters = reader.chain(le_u32(entry, 20));
+            let mut bitmap = reader.read_clusters(&clusters)?;
+            bitmap.truncate(le_u64(entry, 24) as usize);
+            reader.bitmap = Some(bitmap);
+        }
+
+        Ok(reader)
+    }
+
+    fn read_root(&mut self) -> anyhow::Result<Vec<u8>> {
+        if let Some((offset, len)) = self.root_dir {
+            return Ok(self.volume.read_vec(offset, len as usize)?);
+        }
+
+        self.walked.insert(self.root_cluster);
+        let clusters = self.chain(self.root_cluster);
+        Ok(self.read_clusters(&clusters)?)
+    }
+
+    // returns true when the walk has to stop
+    fn walk_dir(
+        &mut self,
+        data: &[u8],
+        path: &str,
+        depth: usize,
+        in_deleted: bool,
+        visit: &mut dyn FnMut(ImageFile) -> Walk,
+    ) -> anyhow::Result<bool> {
+        let entries = if self.kind == Kind::ExFat {
+            exfat_entries(data)
+        } else {
+            fat_entries(data)
+        };
+
+        for entry in entries {
+            let path = format!("{path}/{}", entry.name);
+            let deleted = in_deleted || entry.deleted;
+            let clusters = self.clusters(&entry, deleted);
+
+            let r#type = if entry.is_dir {
+                ForensicsFileType::Directory
+            } else {
+                ForensicsFileType::File
+            };
+            let mut file = ImageFile::new(path.clone(), r#type, entry.len);
+            file.depth = depth;
+            file.fi.created = entry.created;
+            file.fi.accessed = entry.accessed.unwrap_or(UNIX_EPOCH);
+            file.fi.modified = entry.modified.unwrap_or(UNIX_EPOCH);
+            file.fi.allocation = Some(if deleted { DELETED } else { ALLOCATED }.to_string());
+
+            if !entry.is_dir {
+                file.content = clusters.as_ref().map(|clusters| Content {
+                    volume: self.volume.clone(),
+                    runs: self.runs(clusters, entry.len),
+                });
+                file.fi.extents = file.content.as_ref().map(Content::extents);
+            }
+
+            match visit(file) {
+                Walk::Stop => return Ok(true),
+                Walk::Skip => continue,
+                Walk::Continue => (),
+            }
+
+            let Some(clusters) = clusters.filter(|c| entry.is_dir && !c.is_empty()) else {
+                continue;
+            };
+            if !self.walked.insert(clusters[0]) {
+                continue;
+            }
+            if depth >= MAX_WALK_DEPTH {
+                warn!("'{path}' is nested too deeply, not walked");
+                continue;
+            }
+
+            // a single unreadable directory shouldn't stop the walk
+            let data = match self.read_clusters(&clusters) {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("unable to read directory '{path}': {e}");
+                    continue;
+                }
+            };
+            if self.walk_dir(&data, &path, depth + 1, deleted, visit)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    // clusters of an entry, None if its content can't be recovered. Entries of a deleted
+    // directory are deleted too, even if their own entry is intact
+    fn clusters(&self, entry: &DirEntry, deleted: bool) -> Option<Vec<u32>> {
+        if !self.is_valid(entry.first_cluster) {
+            return None;
+        }
+
+        // FAT directories have no size
+        let count = if entry.is_dir && self.kind != Kind::ExFat {
+            if deleted {
+                1
+            } else {
+                self.clusters as u64
+            }
+        } else {
+            entry.len.div_ceil(self.cluster_size)
+        };
+
+        let clusters: Vec<u32> = if entry.contiguous || (deleted && self.kind != Kind::ExFat) {
+            // deleted FAT entries have their chain cleared: clusters are assumed to be
+            // contiguous, as most often
+            let last = entry.first_cluster as u64 + count;
+            if last > self.clusters as u64 + FIRST_CLUSTER as u64 {
+                return None;
+            }
+            (entry.first_cluster..last as u32).collect()
+        } else {
+            let mut chain = self.chain(entry.first_cluster);
+            chain.truncate(count as usize);
+            chain
+        };
+
+        // clusters of deleted entries could have been reused since
+        if deleted && !clusters.iter().all(|c| self.is_free(*c)) {
+            return None;
+        }
+        Some(clusters)
+    }
+
+    // follow the FAT from a cluster, stopping at the first cluster seen twice. A corrupted
+    // boot sector could claim more clusters than the volume holds
+    fn chain(&self, first: u32) -> Vec<u32> {
+        let max = (self.volume.len().saturating_sub(self.data_offset) / self.cluster_size)
+            .min(self.clusters as u64) as usize;
+        let mut chain = Vec::new();
+        let mut seen = HashSet::new();
+        let mut cluster = first;
+
+        while self.is_valid(cluster) && chain.len() < max && seen.insert(cluster) {
+            chain.push(cluster);
+            cluster = self.fat[cluster as usize];
+        }
+        chain
+    }
+
+    fn is_valid(&self, cluster: u32) -> bool {
+        cluster >= FIRST_CLUSTER && (cluster as usize) < self.fat.len()
+    }
+
+    fn is_free(&self, cluster: u32) -> bool {
+        match &self.bitmap {
+            Some(bitmap) => {
+                let n = (cluster - FIRST_CLUSTER) as usize;
+                bitmap.get(n / 8).is_some_and(|b| b & (1 << (n % 8)) == 0)
+            }
+            None => self.fat[cluster as usize] == 0,
+        }
+    }
+
+    fn offset(&self, cluster: u32) -> u64 {
+        self.data_offset + (cluster - FIRST_CLUSTER) as u64 * self.cluster_size
+    }
+
+    fn read_clusters(&self, clusters: &[u32]) -> std::io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        for cluster in clusters {
+            data.extend(
+                self.volume
+                    .read_vec(self.offset(*cluster), self.cluster_size as usize)?,
+            );
+        }
+        Ok(data)
+    }
+
+    // the last cluster being truncated to the file size, lost clusters aren't read as zeros
+    fn runs(&self, clusters: &[u32], len: u64) -> Vec<Run> {
+        let mut runs = Runs::new(len.min(clusters.len() as u64 * self.cluster_size));
+        for cluster in clusters {
+            runs.push(Some(self.offset(*cluster)), self.cluster_size);
+        }
+        runs.finish()
+    }
+}
+
+fn fat_entries(data: &[u8]) -> Vec<DirEntry> {
+    let mut entries = Vec::new();
+
+    // long name parts, in reverse order, and the checksum of their short name
+    let mut long_name: Vec<u16> = Vec::new();
+    let mut checksum = None;
+
+    for e in data.chunks_exact(DIR_ENTRY_SIZE) {
+        if e[0] == 0 {
+            break;
+        }
+
+        if e[11] & ATTR_LONG_NAME == ATTR_LONG_NAME {
+            if e[0] & LFN_LAST != 0 && e[0] != FAT_DELETED {
+                long_name.clear();
+            }
+            let chars = [&e[1..11], &e[14..26], &e[28..32]].concat();
+            let part: Vec<u16> = chars
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|c| *c != 0)
+                .collect();
+            long_name.splice(0..0, part);
+            checksum = Some(e[13]);
+            continue;
+        }
+
+        let deleted = e[0] == FAT_DELETED;
+        let lfn = std::mem::take(&mut long_name);
+        let lfn_checksum = checksum.take();
+
+        if e[11] & ATTR_VOLUME_ID != 0 || &e[0..2] == b". " || &e[0..3] == b".. " {
+            continue;
+        }
+
+        // the checksum can't be verified for deleted entries, the first character being lost
+        let name = if !lfn.is_empty() && (deleted || lfn_checksum == Some(short_checksum(e))) {
+            String::from_utf16_lossy(&lfn)
+        } else {
+            short_name(e)
+        };
+
+        entries.push(DirEntry {
+            name,
+            is_dir: e[11] & ATTR_DIRECTORY != 0,
+            deleted,
+            len: le_u32(e, 28) as u64,
+            first_cluster: (le_u16(e, 20) as u32) << 16 | le_u16(e, 26) as u32,
+            contiguous: false,
+            created: fat_time(le_u16(e, 16), le_u16(e, 14), e[13]),
+            accessed: fat_time(le_u16(e, 18), 0, 0),
+            modified: fat_time(le_u16(e, 24), le_u16(e, 22), 0),
+        });
+    }
+
+    entries
+}
+
+// 8.3 name, deleted entries having their first character replaced by _
+fn short_name(e: &[u8]) -> String {
+    let mut raw = e[0..11].to_vec();
+    match raw[0] {
+        FAT_DELETED => raw[0] = b'_',
+        FAT_KANJI_E5 => raw[0] = FAT_DELETED,
+        _ => (),
+    }
+
+    // code page characters are kept as Latin-1
+    let part = |bytes: &[u8], lowercase: bool| -> String {
+        let s: String = bytes.iter().map(|b| *b as char).collect();
+        let s = s.trim_end().to_string();
+        if lowercase {
+            s.to_lowercase()
+        } else {
+            s
+        }
+    };
+    let base = part(&raw[0..8], e[12] & LOWERCASE_BASE != 0);
+    let ext = part(&raw[8..11], e[12] & LOWERCASE_EXT != 0);
+
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{base}.{ext}")
+    }
+}
+
+fn short_checksum(e: &[u8]) -> u8 {
+    e[0..11]
+        .iter()
+        .fold(0u8, |sum, b| sum.rotate_right(1).wrapping_add(*b))
+}
+
+// local time, there's no timezone information. Creation time has 10 ms units
+fn fat_time(date: u16, time: u16, centiseconds: u8) -> Option<SystemTime> {
+    let date = NaiveDate::from_ymd_opt(
+        1980 + (date >> 9) as i32,
+        ((date >> 5) & 0x0F) as u32,
+        (date & 0x1F) as u32,
+    )?;
+    let time = date.and_hms_milli_opt(
+        (time >> 11) as u32,
+        ((time >> 5) & 0x3F) as u32,
+        (time & 0x1F) as u32 * 2,
+        centiseconds as u32 * 10,
+    )?;
+    system_time(time)
+}
+
+// entry sets: a file entry followed by a stream extension and file name entries
+fn exfat_entries(data: &[u8]) -> Vec<DirEntry> {
+    let mut entries = Vec::new();
+    let raw: Vec<&[u8]> = data.chunks_exact(DIR_ENTRY_SIZE).collect();
+
+    let mut i = 0;
+    while i < raw.len() {
+        let e = raw[i];
+        i += 1;
+        if e[0] == 0 {
+            break;
+        }
+        if e[0] & !EXFAT_IN_USE != EXFAT_FILE {
+            continue;
+        }
+
+        let deleted = e[0] & EXFAT_IN_USE == 0;
+        let secondary = &raw[i..(i + e[1] as usize).min(raw.len())];
+        let Some(stream) = secondary
+            .first()
+            .filter(|s| s[0] & !EXFAT_IN_USE == EXFAT_STREAM)
+        else {
+            continue;
+        };
+        i += secondary.len();
+
+        let name_len = stream[3] as usize;
+        let name: Vec<u16> = secondary
+            .iter()
+            .skip(1)
+            .take_while(|n| n[0] & !EXFAT_IN_USE == EXFAT_NAME)
+            .flat_map(|n| n[2..2 + 2 * EXFAT_NAME_CHARS].chunks_exact(2))
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take(name_len)
+            .collect();
+
+        entries.push(DirEntry {
+            name: String::from_utf16_lossy(&name),
+            is_dir: le_u16(e, 4) as u8 & ATTR_DIRECTORY != 0,
+            deleted,
+            len: le_u64(stream, 24),
+            first_cluster: le_u32(stream, 20),
+            contiguous: stream[1] & EXFAT_NO_FAT_CHAIN != 0,
+            created: exfat_time(le_u32(e, 8), e[20], e[22]),
+            modified: exfat_time(le_u32(e, 12), e[21], e[23]),
+            accessed: exfat_time(le_u32(e, 16), 0, e[24]),
+        });
+    }
+
+    entries
+}
+
+// same bit layout as FAT date and time, with an optional offset from UTC in 15 minutes units
+fn exfat_time(timestamp: u32, centiseconds: u8, utc_offset: u8) -> Option<SystemTime> {
+    let local = fat_time((timestamp >> 16) as u16, timestamp as u16, 0)?
+        + Duration::from_millis(centiseconds as u64 * 10);
+
+    if utc_offset & 0x80 == 0 {
+        return Some(local);
+    }
+    // 7 bits signed value
+    let minutes = (((utc_offset << 1) as i8) >> 1) as i64 * 15;
+    let offset = Duration::from_secs(minutes.unsigned_abs() * 60);
+    if minutes >= 0 {
+        local.checked_sub(offset)
+    } else {
+        local.checked_add(offset)
+    }
+}
+
+fn system_time(time: NaiveDateTime) -> Option<SystemTime> {
+    let secs = time.and_utc().timestamp();
+    let since = Duration::new(secs.unsigned_abs(), time.and_utc().timestamp_subsec_nanos());
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(since)
+    } else {
+        UNIX_EPOCH.checked_sub(since)
+    }
+}
+
+// FAT12 entries could straddle the end of the table
+fn u16_at(b: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([
+        b.get(offset).copied().unwrap_or_default(),
+        b.get(offset + 1).copied().unwrap_or_default(),
+    ])
+}
+
+fn u32_at(b: &[u8], offset: usize) -> u32 {
+    (u16_at(b, offset + 2) as u32) << 16 | u16_at(b, offset) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, sync::Arc};
+
+    use super::*;
+    use crate::{fileinfo::FileInfo, image::Source};
+
+    fn dir_entry(name: &[u8; 11], attr: u8, cluster: u16, len: u32) -> Vec<u8> {
+        let mut e = vec![0; DIR_ENTRY_SIZE];
+        e[0..11].copy_from_slice(name);
+        e[11] = attr;
+        // 2024-03-15 10:20:30
+        e[22..24].copy_from_slice(&(10u16 << 11 | 20 << 5 | 15).to_le_bytes());
+        e[24..26].copy_from_slice(&((2024u16 - 1980) << 9 | 3 << 5 | 15).to_le_bytes());
+        e[26..28].copy_from_slice(&cluster.to_le_bytes());
+        e[28..32].copy_from_slice(&len.to_le_bytes());
+        e
+    }
+
+    // FAT12: 512 bytes sectors and clusters, 1 reserved sector, 1 FAT, 16 root entries
+    fn image() -> Vec<u8> {
+        let mut img = vec![0u8; 64 * 512];
+        img[11..13].copy_from_slice(&512u16.to_le_bytes());
+        img[13] = 1;
+        img[14..16].copy_from_slice(&1u16.to_le_bytes());
+        img[16] = 1;
+        img[17..19].copy_from_slice(&16u16.to_le_bytes());
+        img[19..21].copy_from_slice(&64u16.to_le_bytes());
+        img[22..24].copy_from_slice(&1u16.to_le_bytes());
+        img[54..59].copy_from_slice(b"FAT12");
+        img[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        // clusters 2 and 3: hello.txt, 4: dir, 5: deleted file (free)
+        let fat = &mut img[512..1024];
+        fat[0..3].copy_from_slice(&[0xF8, 0xFF, 0xFF]);
+        fat[3..6].copy_from_slice(&[0x03, 0xF0, 0xFF]);
+        fat[6..9].copy_from_slice(&[0xFF, 0x0F, 0x00]);
+
+        // root directory: long name entry then its short entry
+        let short = *b"HELLOW~1TXT";
+        let mut lfn = vec![0xFFu8; DIR_ENTRY_SIZE];
+        lfn[0] = LFN_LAST | 1;
+        lfn[11] = ATTR_LONG_NAME;
+        lfn[12] = 0;
+        lfn[13] = short_checksum(&short);
+        lfn[26..28].fill(0);
+        let name: Vec<u16> = "Hello W.txt".encode_utf16().chain([0]).collect();
+        let slots = (1..11)
+            .step_by(2)
+            .chain((14..26).step_by(2))
+            .chain((28..32).step_by(2));
+        for (slot, c) in slots.zip(name) {
+            lfn[slot..slot + 2].copy_from_slice(&c.to_le_bytes());
+        }
+
+        let mut root = lfn;
+        root.extend(dir_entry(&short, 0x20, 2, 600));
+        root.extend(dir_entry(b"DIR        ", ATTR_DIRECTORY, 4, 0));
+        let mut deleted = dir_entry(b"XGONE   TXT", 0x20, 5, 4);
+        deleted[0] = FAT_DELETED;
+        root.extend(deleted);
+        img[1024..1024 + root.len()].copy_from_slice(&root);
+
+        // data starts at sector 3 with cluster 2
+        let data = 3 * 512;
+        img[data..data + 600].fill(b'h');
+        let mut sub = dir_entry(b".          ", ATTR_DIRECTORY, 4, 0);
+        sub.extend(dir_entry(b"INNER   BIN", 0x20, 0, 0));
+        img[data + 2 * 512..data + 2 * 512 + sub.len()].copy_from_slice(&sub);
+        img[data + 3 * 512..data + 3 * 512 + 4].copy_from_slice(b"gone");
+        img
+    }
+
+    #[test]
+    fn fat12() {
+        let volume = Volume::from(Arc::new(image()) as Arc<dyn Source>);
+        let mut files = Vec::new();
+        Fat::new(volume, "img!/p0")
+            .walk(&mut |file| {
+                let mut data = Vec::new();
+                if let Some(content) = &file.content {
+                    content.reader().read_to_end(&mut data).unwrap();
+                }
+                files.push((file.fi, file.depth, data));
+                Walk::Continue
+            })
+            .unwrap();
+
+        let paths: Vec<_> = files.iter().map(|(fi, ..)| fi.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "img!/p0/Hello W.txt",
+                "img!/p0/DIR",
+                "img!/p0/DIR/INNER.BIN",
+                "img!/p0/_GONE.TXT"
+            ]
+        );
+
+        let (hello, _, data) = &files[0];
+        assert_eq!(data, &vec![b'h'; 600]);
+        assert_eq!(hello.allocation.as_deref(), Some(ALLOCATED));
+        assert_eq!(
+            hello.modified,
+            fat_time((2024 - 1980) << 9 | 3 << 5 | 15, 10 << 11 | 20 << 5 | 15, 0).unwrap()
+        );
+        assert_eq!(files[2].1, 2);
+
+        // deleted file content is recovered from its free cluster
+        let (gone, _, data) = &files[3];
+        assert_eq!(gone.allocation.as_deref(), Some(DELETED));
+        assert_eq!(data, b"gone");
+    }
+
+    // entries and their content, as found by a walk
+    fn walk(img: Vec<u8>) -> Vec<(FileInfo, Option<Vec<u8>>)> {
+        let volume = Volume::from(Arc::new(img) as Arc<dyn Source>);
+        let mut files = Vec::new();
+        Fat::new(volume, "img!/p0")
+            .walk(&mut |file| {
+                let data = file.content.as_ref().map(|content| {
+                    let mut data = Vec::new();
+                    content.reader().read_to_end(&mut data).unwrap();
+                    data
+                });
+                files.push((file.fi, data));
+                Walk::Continue
+            })
+            .unwrap();
+        files
+    }
+
+    // exFAT entry set: file, stream extension and name entries
+    fn exfat_set(name: &str, attr: u8, flags: u8, cluster: u32, len: u64) -> Vec<u8> {
+        let name: Vec<u16> = name.encode_utf16().collect();
+        let names = name.len().div_ceil(EXFAT_NAME_CHARS);
+
+        let mut file = vec![0; DIR_ENTRY_SIZE];
+        file[0] = EXFAT_IN_USE | EXFAT_FILE;
+        file[1] = 1 + names as u8;
+        file[4] = attr;
+        // 2024-03-15 10:20:30 for all timestamps, modified one being UTC+1 and 1.5 s later
+        let timestamp = ((2024u32 - 1980) << 9 | 3 << 5 | 15) << 16 | 10 << 11 | 20 << 5 | 15;
+        for offset in [8, 12, 16] {
+            file[offset..offset + 4].copy_from_slice(&timestamp.to_le_bytes());
+        }
+        file[21] = 150;
+        file[23] = 0x80 | 4;
+
+        let mut stream = vec![0; DIR_ENTRY_SIZE];
+        stream[0] = EXFAT_IN_USE | EXFAT_STREAM;
+        stream[1] = flags;
+        stream[3] = name.len() as u8;
+        stream[20..24].copy_from_slice(&cluster.to_le_bytes());
+        stream[24..32].copy_from_slice(&len.to_le_bytes());
+
+        let mut set = [file, stream].concat();
+        for part in name.chunks(EXFAT_NAME_CHARS) {
+            let mut entry = vec![0; DIR_ENTRY_SIZE];
+            entry[0] = EXFAT_IN_USE | EXFAT_NAME;
+            for (i, c) in part.iter().enumerate() {
+                entry[2 + 2 * i..4 + 2 * i].copy_from_slice(&c.to_le_bytes());
+            }
+            set.extend(entry);
+        }
+        set
+    }
+
+    // the in use bit is cleared in all the entries of a deleted set
+    fn deleted(mut set: Vec<u8>) -> Vec<u8> {
+        set.chunks_exact_mut(DIR_ENTRY_SIZE)
+            .for_each(|e| e[0] &= !EXFAT_IN_USE);
+        set
+    }
+
+    // exFAT: 512 bytes sectors, 1 KiB clusters, FAT at sector 1, cluster heap at sector 2
+    #[test]
+    fn exfat() {
+        let mut img = vec![0u8; 20 * 1024];
+        img[3..11].copy_from_slice(b"EXFAT   ");
+        img[80..84].copy_from_slice(&1u32.to_le_bytes());
+        img[84..88].copy_from_slice(&1u32.to_le_bytes());
+        img[88..92].copy_from_slice(&2u32.to_le_bytes());
+        img[92..96].copy_from_slice(&16u32.to_le_bytes());
+        img[96..100].copy_from_slice(&2u32.to_le_bytes());
+        img[108] = 9;
+        img[109] = 1;
+        img[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        // clusters 2: root, 3: bitmap, 4 and 5: a.txt, 6 and 7: b.bin without FAT chain,
+        // 8: deleted c.txt, 9: reused since d.txt was deleted, 10: sub, 11: deleted old,
+        // 12: reused since old/f.txt was deleted
+        let cluster = |n: usize| 1024 * (n - 1);
+        let eoc = 0xFFFF_FFFFu32.to_le_bytes();
+        let fat = &mut img[512..1024];
+        for n in [2, 3, 5, 8, 9, 10, 11, 12] {
+            fat[4 * n..4 * n + 4].copy_from_slice(&eoc);
+        }
+        fat[16..20].copy_from_slice(&5u32.to_le_bytes());
+
+        let mut bitmap = vec![0; DIR_ENTRY_SIZE];
+        bitmap[0] = EXFAT_BITMAP;
+        bitmap[20..24].copy_from_slice(&3u32.to_le_bytes());
+        bitmap[24..32].copy_from_slice(&2u64.to_le_bytes());
+        let root = [
+            bitmap,
+            exfat_set("a.txt", 0x20, 0x01, 4, 1500),
+            exfat_set("b.bin", 0x20, 0x01 | EXFAT_NO_FAT_CHAIN, 6, 2048),
+            deleted(exfat_set("c.txt", 0x20, 0x01, 8, 5)),
+            deleted(exfat_set("d.txt", 0x20, 0x01, 9, 5)),
+            exfat_set("sub", ATTR_DIRECTORY, 0x01, 10, 1024),
+            deleted(exfat_set("old", ATTR_DIRECTORY, 0x01, 11, 1024)),
+        ]
+        .concat();
+
+        let offset = cluster(2);
+        img[offset..offset + root.len()].copy_from_slice(&root);
+        let offset = cluster(3);
+        img[offset..offset + 2].copy_from_slice(&[0b1011_1111, 0b0000_0101]);
+        let offset = cluster(4);
+        img[offset..offset + 1500].fill(b'a');
+        let offset = cluster(6);
+        img[offset..offset + 2048].fill(b'b');
+        let offset = cluster(8);
+        img[offset..offset + 5].copy_from_slice(b"cdata");
+        let offset = cluster(9);
+        img[offset..offset + 5].copy_from_slice(b"reuse");
+        let sub = exfat_set("e.txt", 0x20, 0x01, 0, 0);
+        let offset = cluster(10);
+        img[offset..offset + sub.len()].copy_from_slice(&sub);
+        let old = exfat_set("f.txt", 0x20, 0x01, 12, 5);
+        let offset = cluster(11);
+        img[offset..offset + old.len()].copy_from_slice(&old);
+        let offset = cluster(12);
+        img[offset..offset + 5].copy_from_slice(b"reuse");
+
+        let files = walk(img);
+        let found: Vec<_> = files
+            .iter()
+            .map(|(fi, data)| {
+                (
+                    fi.path.strip_prefix("img!/p0/").unwrap(),
+                    fi.allocation.as_deref().unwrap(),
+                    data.as_ref().map(Vec::len),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("a.txt", ALLOCATED, Some(1500)),
+                ("b.bin", ALLOCATED, Some(2048)),
+                ("c.txt", DELETED, Some(5)),
+                ("d.txt", DELETED, None),
+                ("sub", ALLOCATED, None),
+                ("sub/e.txt", ALLOCATED, None),
+                ("old", DELETED, None),
+                ("old/f.txt", DELETED, None),
+            ]
+        );
+        assert_eq!(files[0].1.as_deref(), Some(&[b'a'; 1500][..]));
+        assert_eq!(files[1].1.as_deref(), Some(&[b'b'; 2048][..]));
+        assert_eq!(files[2].1.as_deref(), Some(&b"cdata"[..]));
+
+        // local time, modified one being converted from UTC+1
+        let local = fat_time((2024 - 1980) << 9 | 3 << 5 | 15, 10 << 11 | 20 << 5 | 15, 0).unwrap();
+        let (a, _) = &files[0];
+        assert_eq!(a.created, Some(local));
+        assert_eq!(
+            a.modified,
+            local + Duration::from_millis(1500) - Duration::from_secs(3600)
+        );
+    }
+
+    // FAT32 with 64 KiB clusters, its boot sector claiming many more clusters than the
+    // 1 MiB volume holds, and its root directory cluster linked to itself
+    #[test]
+    fn fat32_loop() {
+        let mut img = vec![0u8; 1 << 20];
+        img[11..13].copy_from_slice(&512u16.to_le_bytes());
+        img[13] = 128;
+        img[14..16].copy_from_slice(&32u16.to_le_bytes());
+        img[16] = 1;
+        img[32..36].copy_from_slice(&(545u32 + 65600 * 128).to_le_bytes());
+        img[36..40].copy_from_slice(&513u32.to_le_bytes());
+        img[44..48].copy_from_slice(&2u32.to_le_bytes());
+        img[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        let fat = &mut img[32 * 512..];
+        fat[8..12].copy_from_slice(&2u32.to_le_bytes());
+        fat[12..16].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+
+        let data = 545 * 512;
+        let root = dir_entry(b"A       TXT", 0x20, 3, 5);
+        img[data..data + root.len()].copy_from_slice(&root);
+        img[data + 65536..data + 65536 + 5].copy_from_slice(b"hello");
+
+        let volume = Volume::from(Arc::new(img.clone()) as Arc<dyn Source>);
+        let boot = volume.read_vec(0, 512).unwrap();
+        let reader = Reader::fat(&volume, &boot).unwrap();
+        assert_eq!(reader.kind, Kind::Fat32);
+        assert_eq!(reader.chain(2), [2]);
+
+        let files = walk(img);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0.path, "img!/p0/A.TXT");
+        assert_eq!(files[0].1.as_deref(), Some(&b"hello"[..]));
+    }
+
+    // FAT12 with each directory holding the next one, deeper than MAX_WALK_DEPTH
+    #[test]
+    fn deep_dirs() {
+        let dirs = MAX_WALK_DEPTH + 100;
+        let sectors = 6 + dirs;
+        let mut img = vec![0u8; sectors * 512];
+        img[11..13].copy_from_slice(&512u16.to_le_bytes());
+        img[13] = 1;
+        img[14..16].copy_from_slice(&1u16.to_le_bytes());
+        img[16] = 1;
+        img[17..19].copy_from_slice(&16u16.to_le_bytes());
+        img[19..21].copy_from_slice(&(sectors as u16).to_le_bytes());
+        img[22..24].copy_from_slice(&4u16.to_le_bytes());
+        img[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        // all clusters are ends of chain
+        img[512..512 + (dirs + 2) * 3 / 2 + 1].fill(0xFF);
+        let root = dir_entry(b"D          ", ATTR_DIRECTORY, 2, 0);
+        img[5 * 512..5 * 512 + root.len()].copy_from_slice(&root);
+        for n in 0..dirs - 1 {
+            let sub = dir_entry(b"D          ", ATTR_DIRECTORY, n as u16 + 3, 0);
+            let offset = (6 + n) * 512;
+            img[offset..offset + sub.len()].copy_from_slice(&sub);
+        }
+
+        let files = walk(img);
+        assert_eq!(files.len(), MAX_WALK_DEPTH);
+        assert_eq!(
+            files.last().unwrap().0.path.matches('/').count(),
+            MAX_WALK_DEPTH + 1
+        );
+    }
+}
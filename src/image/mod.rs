@@ -0,0 +1,559 @@
+// raw or EWF disk images: partitions are found from the MBR or GPT, and supported filesystems
+// are read directly from the image instead of being mounted
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use log::{error, info, warn};
+use serde_json::{json, Value};
+
+use crate::{
+    fileinfo::{FileInfo, ForensicsFileType, RunHistory},
+    hash::hex,
+    storage::Storage,
+};
+
+pub mod ewf;
+pub mod ext;
+pub mod fat;
+pub mod ntfs;
+pub mod partition;
+
+// where image data is read from
+pub trait Source: Send + Sync {
+    // same as FileExt::read_at: could read less than asked
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    fn len(&self) -> u64;
+}
+
+// dd images
+pub struct RawImage {
+    file: File,
+    len: u64,
+}
+
+impl RawImage {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self { file, len })
+    }
+}
+
+impl Source for RawImage {
+    #[cfg(unix)]
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(&self.file, buf, offset)
+    }
+
+    #[cfg(windows)]
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(&self.file, buf, offset)
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+}
+
+// image already in memory
+impl Source for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let start = (offset as usize).min(self.len());
+        let n = buf.len().min(self.len() - start);
+        buf[..n].copy_from_slice(&self[start..start + n]);
+        Ok(n)
+    }
+
+    fn len(&self) -> u64 {
+        self.len() as u64
+    }
+}
+
+// a range of the image, e.g. a partition
+#[derive(Clone)]
+pub struct Volume {
+    source: Arc<dyn Source>,
+    offset: u64,
+    len: u64,
+}
+
+impl From<Arc<dyn Source>> for Volume {
+    fn from(source: Arc<dyn Source>) -> Self {
+        let len = source.len();
+        Self {
+            source,
+            offset: 0,
+            len,
+        }
+    }
+}
+
+impl Volume {
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    // a sub-range, truncated to this volume
+    pub fn slice(&self, offset: u64, len: u64) -> Self {
+        let offset = offset.min(self.len);
+        Self {
+            source: self.source.clone(),
+            offset: self.offset + offset,
+            len: len.min(self.len - offset),
+        }
+    }
+
+    pub fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        if offset.saturating_add(buf.len() as u64) > self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("read beyond the end of the volume at offset {offset}"),
+            ));
+        }
+
+        while !buf.is_empty() {
+            match self.source.read_at(buf, self.offset + offset) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => {
+                    buf = &mut buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // lengths come from on-disk structures: they are checked before allocating
+    pub fn read_vec(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        if offset
+            .checked_add(len as u64)
+            .is_none_or(|end| end > self.len)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("read of {len} bytes beyond the end of the volume at offset {offset}"),
+            ));
+        }
+
+        let mut buf = vec![0; len];
+        self.read_exact_at(&mut buf, offset)?;
+        Ok(buf)
+    }
+}
+
+// a contiguous part of a file, None being a hole read as zeros
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub offset: Option<u64>,
+    pub len: u64,
+}
+
+// contiguous runs are merged, their total being truncated to the file size
+pub struct Runs {
+    runs: Vec<Run>,
+    remaining: u64,
+}
+
+impl Runs {
+    pub fn new(len: u64) -> Self {
+        Self {
+            runs: Vec::new(),
+            remaining: len,
+        }
+    }
+
+    pub fn push(&mut self, offset: Option<u64>, len: u64) {
+        let len = len.min(self.remaining);
+        if len == 0 {
+            return;
+        }
+        self.remaining -= len;
+
+        if let Some(last) = self.runs.last_mut() {
+            let contiguous = match (last.offset, offset) {
+                (None, None) => true,
+                (Some(end), Some(start)) => end + last.len == start,
+                _ => false,
+            };
+            if contiguous {
+                last.len += len;
+                return;
+            }
+        }
+        self.runs.push(Run { offset, len });
+    }
+
+    // the end of file not covered by runs is sparse
+    pub fn finish(mut self) -> Vec<Run> {
+        let remaining = self.remaining;
+        self.push(None, remaining);
+        self.runs
+    }
+}
+
+// file data, read from the volume by the workers
+pub struct Content {
+    pub volume: Volume,
+    pub runs: Vec<Run>,
+}
+
+impl Content {
+    // data location as byte ranges of the image, e.g. [{"offset": 1052672, "len": 4096}]
+    pub fn extents(&self) -> Value {
+        self.runs
+            .iter()
+            .map(|run| json!({ "offset": run.offset.map(|o| self.volume.offset + o), "len": run.len }))
+            .collect()
+    }
+
+    // same as Volume::read_exact_at, offset being in the file data
+    pub fn read_exact_at(&self, mut buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let mut start = 0;
+        for run in &self.runs {
+            if buf.is_empty() {
+                break;
+            }
+            let end = start + run.len;
+            if offset < end {
+                let pos = offset.max(start) - start;
+                let n = buf.len().min((run.len - pos) as usize);
+                match run.offset {
+                    Some(o) => self.volume.read_exact_at(&mut buf[..n], o + pos)?,
+                    None => buf[..n].fill(0),
+                }
+                buf = &mut buf[n..];
+            }
+            start = end;
+        }
+
+        if buf.is_empty() {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::UnexpectedEof.into())
+        }
+    }
+
+    pub fn reader(&self) -> ContentReader<'_> {
+        ContentReader {
+            content: self,
+            run: 0,
+            pos: 0,
+        }
+    }
+}
+
+pub struct ContentReader<'a> {
+    content: &'a Content,
+
+    // current run and position inside it
+    run: usize,
+    pos: u64,
+}
+
+impl Read for ContentReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(run) = self.content.runs.get(self.run) else {
+                return Ok(0);
+            };
+            if self.pos == run.len {
+                self.run += 1;
+                self.pos = 0;
+                continue;
+            }
+
+            let n = buf.len().min((run.len - self.pos) as usize);
+            match run.offset {
+                Some(offset) => self
+                    .content
+                    .volume
+                    .read_exact_at(&mut buf[..n], offset + self.pos)?,
+                None => buf[..n].fill(0),
+            }
+            self.pos += n as u64;
+            return Ok(n);
+        }
+    }
+}
+
+// an entry found by a filesystem reader, content being None for directories
+pub struct ImageFile {
+    pub fi: FileInfo,
+    pub content: Option<Content>,
+
+    // same as DirEntry::depth: 0 for the partition root, 1 for its entries
+    pub depth: usize,
+}
+
+impl ImageFile {
+    // path is the full path, e.g. disk.raw!/p1/etc/passwd
+    pub fn new(path: String, r#type: ForensicsFileType, len: u64) -> Self {
+        let name = path.rsplit('/').next().unwrap_or_default().to_string();
+        let fi = FileInfo {
+            winpath: path.as_str().into(),
+            winname: name.as_str().into(),
+            ext: Path::new(&name)
+                .extension()
+                .map(OsStr::to_string_lossy)
+                .unwrap_or_default()
+                .into_owned(),
+            path,
+            name,
+            r#type,
+            len: len as i64,
+            ..Default::default()
+        };
+
+        Self {
+            fi,
+            content: None,
+            depth: 0,
+        }
+    }
+}
+
+// what to do after an entry has been visited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Walk {
+    Continue,
+
+    // don't descend into this directory
+    Skip,
+
+    Stop,
+}
+
+// directories deeper than this aren't walked: each level of the recursive walks takes a
+// few KiB of stack, which nested or looping corrupted directories would exhaust
+pub const MAX_WALK_DEPTH: usize = 128;
+
+// filesystem readers walk all entries of a volume
+pub trait FileSystem {
+    // returns true when the walk has been stopped by visit
+    fn walk(&mut self, visit: &mut dyn FnMut(ImageFile) -> Walk) -> anyhow::Result<bool>;
+}
+
+// filesystem type from its boot sector or superblock
+fn detect(volume: &Volume) -> Option<&'static str> {
+    let boot = volume.read_vec(0, 2048).ok()?;
+
+    if &boot[3..11] == b"NTFS    " {
+        Some("ntfs")
+    } else if &boot[3..11] == b"EXFAT   " {
+        Some("exfat")
+    } else if &boot[82..87] == b"FAT32" {
+        Some("fat32")
+    } else if &boot[54..59] == b"FAT12" || &boot[54..59] == b"FAT16" {
+        Some("fat")
+    } else if boot[1080..1082] == [0x53, 0xEF] {
+        Some("ext")
+    } else if &boot[..4] == b"FILE" {
+        // an exported $MFT rather than a disk image
+        Some("mft")
+    } else {
+        None
+    }
+}
+
+// reader for a filesystem type, None if not supported
+fn reader(filesystem: &str, volume: &Volume, root: &str) -> Option<Box<dyn FileSystem>> {
+    match filesystem {
+        "fat" | "fat32" | "exfat" => Some(Box::new(fat::Fat::new(volume.clone(), root))),
+        "ext" => Some(Box::new(ext::Ext::new(volume.clone(), root))),
+        "ntfs" => Some(Box::new(ntfs::Ntfs::new(volume.clone(), root))),
+        "mft" => Some(Box::new(ntfs::Ntfs::from_mft(volume.clone(), root))),
+        _ => None,
+    }
+}
+
+// raw or EWF image. Case metadata and stored hashes of the latter are kept in the history,
+// the hashes being checked against the acquired data if asked
+fn open(path: &Path, history: &mut RunHistory, verify: bool) -> anyhow::Result<Arc<dyn Source>> {
+    let image = RawImage::open(path)?;
+    let mut signature = [0; 8];
+    let n = image.read_at(&mut signature, 0)?;
+    if signature[..n] != *ewf::EWF_SIGNATURE {
+        if verify {
+            warn!("only EWF images can be verified");
+        }
+        return Ok(Arc::new(image));
+    }
+
+    let ewf = ewf::Ewf::open(path)?;
+    info!("EWF image of {} bytes", ewf.len());
+    history.examiner = ewf.header.examiner.clone();
+    history.case_number = ewf.header.case_number.clone();
+    history.acquired = ewf.header.acquired;
+    history.image_md5 = ewf.md5.map(|h| hex(&h));
+    history.image_sha1 = ewf.sha1.map(|h| hex(&h));
+
+    if verify {
+        info!("verifying '{}'", path.display());
+        history.verified = ewf.verify()?;
+        match history.verified {
+            Some(true) => info!("acquired data matches the stored hashes"),
+            Some(false) => error!("acquired data doesn't match the stored hashes"),
+            None => warn!("no hash stored in '{}'", path.display()),
+        }
+    }
+    Ok(Arc::new(ewf))
+}
+
+// find partitions, save them and walk the supported ones. Other partitions are
+// visited as a single file, e.g. to get their entropy
+pub fn scan(
+    path: &Path,
+    history: &mut RunHistory,
+    verify: bool,
+    storage: &mut dyn Storage,
+    visit: &mut dyn FnMut(ImageFile) -> Walk,
+) -> anyhow::Result<()> {
+    let source = open(path, history, verify)?;
+    storage.save_history(history)?;
+
+    let run_id = history.run_id.as_str();
+    let disk = Volume::from(source);
+    let image = path.to_string_lossy();
+    let modified = std::fs::metadata(path)?.modified()?;
+
+    let mut partitions = partition::find(&disk)?;
+    for p in &mut partitions {
+        p.run_id = run_id.to_string();
+        p.image = image.to_string();
+        p.filesystem = detect(&disk.slice(p.start as u64, p.len as u64)).map(String::from);
+        info!(
+            "partition {} at offset {}: {} bytes, {}",
+            p.number,
+            p.start,
+            p.len,
+            p.filesystem.as_deref().unwrap_or("unknown filesystem")
+        );
+    }
+    storage.save_partitions(run_id, &partitions)?;
+
+    for p in &partitions {
+        let volume = disk.slice(p.start as u64, p.len as u64);
+        let root = format!("{image}!/p{}", p.number);
+
+        let filesystem = p.filesystem.as_deref().unwrap_or_default();
+        if let Some(mut reader) = reader(filesystem, &volume, &root) {
+            // other partitions could still be read
+            match reader.walk(visit) {
+                Ok(true) => break,
+                Ok(false) => (),
+                Err(e) => error!("unable to read partition {}: {e}", p.number),
+            }
+            continue;
+        }
+
+        if !filesystem.is_empty() {
+            warn!("no reader for {filesystem} partition {}", p.number);
+        }
+        if visit(partition_file(root, volume, modified)) == Walk::Stop {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// little-endian integers of on-disk structures, bytes beyond the end being read as zeros
+// so that truncated or corrupted structures don't panic
+fn le_bytes<const N: usize>(b: &[u8], offset: usize) -> [u8; N] {
+    let mut bytes = [0; N];
+    if let Some(b) = b.get(offset..) {
+        let n = N.min(b.len());
+        bytes[..n].copy_from_slice(&b[..n]);
+    }
+    bytes
+}
+
+fn le_u16(b: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(le_bytes(b, offset))
+}
+
+fn le_u32(b: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(le_bytes(b, offset))
+}
+
+fn le_u64(b: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(le_bytes(b, offset))
+}
+
+// the whole partition as a file
+fn partition_file(root: String, volume: Volume, modified: SystemTime) -> ImageFile {
+    let len = volume.len();
+    let mut file = ImageFile::new(root, ForensicsFileType::File, len);
+    file.fi.accessed = modified;
+    file.fi.modified = modified;
+    file.content = Some(Content {
+        volume,
+        runs: vec![Run {
+            offset: Some(0),
+            len,
+        }],
+    });
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content() {
+        let data: Vec<u8> = (0..100).collect();
+        let content = Content {
+            volume: Volume::from(Arc::new(data) as Arc<dyn Source>).slice(10, 50),
+            runs: vec![
+                Run {
+                    offset: Some(5),
+                    len: 3,
+                },
+                Run {
+                    offset: None,
+                    len: 2,
+                },
+                Run {
+                    offset: Some(0),
+                    len: 2,
+                },
+            ],
+        };
+
+        let mut read = Vec::new();
+        content.reader().read_to_end(&mut read).unwrap();
+        assert_eq!(read, [15, 16, 17, 0, 0, 10, 11]);
+
+        let mut buf = [0; 4];
+        content.read_exact_at(&mut buf, 2).unwrap();
+        assert_eq!(buf, [17, 0, 0, 10]);
+        assert!(content.read_exact_at(&mut buf, 4).is_err());
+
+        // beyond the volume
+        let mut buf = [0; 10];
+        assert!(content.volume.read_exact_at(&mut buf, 45).is_err());
+        assert!(content.volume.read_vec(45, usize::MAX).is_err());
+        assert!(content.volume.read_vec(u64::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn le() {
+        let b = [1, 2, 3, 4, 5];
+        assert_eq!(le_u16(&b, 0), 0x0201);
+        assert_eq!(le_u32(&b, 1), 0x05040302);
+
+        // truncated
+        assert_eq!(le_u32(&b, 3), 0x0504);
+        assert_eq!(le_u64(&b, 10), 0);
+    }
+}
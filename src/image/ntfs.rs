@@ -0,0 +1,971 @@
+// NTFS, read-only, from a volume or from an exported $MFT alone. Paths are rebuilt from the
+// $FILE_NAME parent references, so that deleted records are named too. Both $STANDARD_INFORMATION
+// and $FILE_NAME timestamps are kept, named data streams being reported as path:stream
+use std::{
+    collections::HashMap,
+    io::Read,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::bail;
+use log::warn;
+
+use crate::{
+    fileinfo::{ForensicsFileType, ALLOCATED, DELETED},
+    image::{
+        le_u16, le_u32, le_u64, Content, FileSystem, ImageFile, Run, Runs, Source, Volume, Walk,
+        MAX_WALK_DEPTH,
+    },
+};
+
+// well-known records
+const ROOT_RECORD: u64 = 5;
+const BITMAP_RECORD: u64 = 6;
+
+const RECORD_MAGIC: &[u8] = b"FILE";
+
+// update sequence fixups are applied every 512 bytes, whatever the sector size
+const FIXUP_STRIDE: usize = 512;
+
+// record flags
+const RECORD_IN_USE: u16 = 0x1;
+const RECORD_DIRECTORY: u16 = 0x2;
+
+// attribute types
+const STANDARD_INFORMATION: u32 = 0x10;
+const FILE_NAME: u32 = 0x30;
+const DATA: u32 = 0x80;
+const END_MARKER: u32 = 0xFFFF_FFFF;
+
+// attribute flags
+const ATTR_COMPRESSED: u16 = 0x1;
+const ATTR_ENCRYPTED: u16 = 0x4000;
+
+// namespace of short names, a long one existing too
+const DOS_NAMESPACE: u8 = 2;
+
+// references are a 48-bit record number and a 16-bit sequence number
+const RECORD_MASK: u64 = 0xFFFF_FFFF_FFFF;
+
+// same as ext
+const ORPHAN_FILES: &str = "$OrphanFiles";
+
+// against loops in parent references
+const MAX_DEPTH: usize = 1024;
+
+// seconds between 1601-01-01 and the Unix epoch
+const NT_EPOCH_OFFSET: u64 = 11_644_473_600;
+
+pub struct Ntfs {
+    volume: Volume,
+    root: String,
+
+    // the volume is an exported $MFT: only resident data can be read
+    mft_only: bool,
+}
+
+impl Ntfs {
+    pub fn new(volume: Volume, root: &str) -> Self {
+        Self {
+            volume,
+            root: root.to_string(),
+            mft_only: false,
+        }
+    }
+
+    pub fn from_mft(volume: Volume, root: &str) -> Self {
+        Self {
+            mft_only: true,
+            ..Self::new(volume, root)
+        }
+    }
+}
+
+impl FileSystem for Ntfs {
+    fn walk(&mut self, visit: &mut dyn FnMut(ImageFile) -> Walk) -> anyhow::Result<bool> {
+        let mut reader = if self.mft_only {
+            Reader::from_mft(&self.volume)?
+        } else {
+            Reader::new(&self.volume)?
+        };
+        reader.index()?;
+
+        if reader.walk_dir(ROOT_RECORD, &self.root, 1, visit)? {
+            return Ok(true);
+        }
+        reader.walk_others(&self.root, visit)
+    }
+}
+
+// MFT entry with its fixups applied
+struct Record {
+    data: Vec<u8>,
+    sequence: u16,
+    links: u16,
+    flags: u16,
+
+    // for extension records, reference of the base record
+    base: u64,
+}
+
+impl Record {
+    fn parse(mut data: Vec<u8>) -> Option<Self> {
+        if data.get(..4)? != RECORD_MAGIC {
+            return None;
+        }
+
+        // the last 2 bytes of each 512-byte block were replaced by the update sequence number
+        let usa = le_u16(&data, 4) as usize;
+        let count = le_u16(&data, 6) as usize;
+        if count == 0 || usa + 2 * count > data.len() || (count - 1) * FIXUP_STRIDE > data.len() {
+            return None;
+        }
+        for i in 1..count {
+            let end = i * FIXUP_STRIDE;
+            if data[end - 2..end] != data[usa..usa + 2] {
+                return None;
+            }
+            data[end - 2] = data[usa + 2 * i];
+            data[end - 1] = data[usa + 2 * i + 1];
+        }
+
+        Some(Self {
+            sequence: le_u16(&data, 16),
+            links: le_u16(&data, 18),
+            flags: le_u16(&data, 22),
+            base: le_u64(&data, 32),
+            data,
+        })
+    }
+
+    fn attributes(&self) -> Vec<Attribute<'_>> {
+        let d = &self.data;
+        let used = (le_u32(d, 24) as usize).min(d.len());
+        let mut pos = le_u16(d, 20) as usize;
+        let mut attributes = Vec::new();
+
+        while pos + 16 <= used {
+            let r#type = le_u32(d, pos);
+            let len = le_u32(d, pos + 4) as usize;
+            if r#type == END_MARKER || len < 16 || pos + len > used {
+                break;
+            }
+            let a = &d[pos..pos + len];
+            pos += len;
+
+            let name_offset = le_u16(a, 10) as usize;
+            let name = a
+                .get(name_offset..name_offset + 2 * a[9] as usize)
+                .map(utf16)
+                .unwrap_or_default();
+
+            let body = if a[8] == 0 && len >= 24 {
+                let offset = le_u16(a, 20) as usize;
+                match a.get(offset..offset + le_u32(a, 16) as usize) {
+                    Some(value) => Body::Resident(value),
+                    None => continue,
+                }
+            } else if a[8] != 0 && len >= 64 {
+                Body::NonResident {
+                    first_vcn: le_u64(a, 16),
+                    runlist: a.get(le_u16(a, 32) as usize..).unwrap_or_default(),
+                    size: le_u64(a, 48),
+                    initialized: le_u64(a, 56),
+                }
+            } else {
+                continue;
+            };
+
+            attributes.push(Attribute {
+                r#type,
+                name,
+                flags: le_u16(a, 12),
+                body,
+            });
+        }
+
+        attributes
+    }
+}
+
+struct Attribute<'a> {
+    r#type: u32,
+    name: String,
+    flags: u16,
+    body: Body<'a>,
+}
+
+enum Body<'a> {
+    Resident(&'a [u8]),
+    NonResident {
+        first_vcn: u64,
+        runlist: &'a [u8],
+        size: u64,
+        initialized: u64,
+    },
+}
+
+impl Attribute<'_> {
+    fn first_vcn(&self) -> u64 {
+        match self.body {
+            Body::Resident(_) => 0,
+            Body::NonResident { first_vcn, .. } => first_vcn,
+        }
+    }
+}
+
+struct FileName {
+    parent: u64,
+    namespace: u8,
+    name: String,
+
+    created: Option<SystemTime>,
+    modified: Option<SystemTime>,
+    changed: Option<SystemTime>,
+    accessed: Option<SystemTime>,
+}
+
+impl FileName {
+    fn parse(value: &[u8]) -> Option<Self> {
+        let name = value.get(66..66 + 2 * *value.get(64)? as usize)?;
+        Some(Self {
+            parent: le_u64(value, 0),
+            namespace: value[65],
+            name: utf16(name),
+            created: nt_time(value, 8),
+            modified: nt_time(value, 16),
+            changed: nt_time(value, 24),
+            accessed: nt_time(value, 32),
+        })
+    }
+}
+
+// long name rather than the short one
+fn file_name(attributes: &[Attribute]) -> Option<FileName> {
+    let mut names = attributes.iter().filter_map(|a| match a.body {
+        Body::Resident(value) if a.r#type == FILE_NAME => FileName::parse(value),
+        _ => None,
+    });
+    let first = names.next()?;
+    if first.namespace != DOS_NAMESPACE {
+        return Some(first);
+    }
+    names.find(|n| n.namespace != DOS_NAMESPACE).or(Some(first))
+}
+
+// what's needed of each record to rebuild paths
+struct Node {
+    sequence: u16,
+    flags: u16,
+    parent: u64,
+    name: String,
+}
+
+struct Reader {
+    // None for an exported $MFT
+    volume: Option<Volume>,
+    cluster_size: u64,
+    record_size: u64,
+    mft: Content,
+
+    // indexed by record number, None for invalid or extension records
+    nodes: Vec<Option<Node>>,
+
+    // records holding attributes which didn't fit in their base record
+    extensions: HashMap<u64, Vec<u64>>,
+
+    // live entries of each live directory
+    children: HashMap<u64, Vec<u64>>,
+
+    // cluster allocation, read for deleted records only
+    bitmap: Option<Vec<u8>>,
+}
+
+impl Reader {
+    fn new(volume: &Volume) -> anyhow::Result<Self> {
+        let boot = volume.read_vec(0, 512)?;
+        if &boot[3..11] != b"NTFS    " {
+            bail!("invalid NTFS boot sector");
+        }
+
+        // large sizes are given as negative powers of 2
+        let sector_size = le_u16(&boot, 11) as u64;
+        let cluster_size = match boot[13] {
+            n @ 0..=128 => sector_size * n as u64,
+            n => sector_size << (256 - n as u32).min(32),
+        };
+        let record_size = match boot[64] as i8 {
+            n if n < 0 => 1 << (n.unsigned_abs() as u32).min(32),
+            n => n as u64 * cluster_size,
+        };
+        if !cluster_size.is_power_of_two()
+            || !record_size.is_power_of_two()
+            || !(512..=1 << 21).contains(&cluster_size)
+            || !(1024..=1 << 16).contains(&record_size)
+        {
+            bail!("invalid NTFS boot sector");
+        }
+
+        let mut reader = Self {
+            volume: Some(volume.clone()),
+            cluster_size,
+            record_size,
+            mft: Content {
+                volume: volume.clone(),
+                runs: Vec::new(),
+            },
+            nodes: Vec::new(),
+            extensions: HashMap::new(),
+            children: HashMap::new(),
+            bitmap: None,
+        };
+
+        // the $MFT location is given by its own first record. A $MFT so fragmented that its
+        // runs don't fit in it isn't supported
+        let mft_offset = le_u64(&boot, 48).saturating_mul(cluster_size);
+        let raw = volume.read_vec(mft_offset, record_size as usize)?;
+        let Some(record) = Record::parse(raw) else {
+            bail!("invalid $MFT record");
+        };
+        let attributes = record.attributes();
+        match reader.data(&unnamed_data(&attributes), false) {
+            (_, Some(content)) => reader.mft = content,
+            _ => bail!("no $MFT data"),
+        }
+        Ok(reader)
+    }
+
+    fn from_mft(volume: &Volume) -> anyhow::Result<Self> {
+        let first = volume.read_vec(0, 32)?;
+        let record_size = le_u32(&first, 28) as u64;
+        if &first[..4] != RECORD_MAGIC || !(1024..=1 << 16).contains(&record_size) {
+            bail!("invalid $MFT record");
+        }
+
+        Ok(Self {
+            volume: None,
+            cluster_size: 0,
+            record_size,
+            mft: Content {
+                volume: volume.clone(),
+                runs: vec![Run {
+                    offset: Some(0),
+                    len: volume.len(),
+                }],
+            },
+            nodes: Vec::new(),
+            extensions: HashMap::new(),
+            children: HashMap::new(),
+            bitmap: None,
+        })
+    }
+
+    // reads all records once, to be able to rebuild paths
+    fn index(&mut self) -> anyhow::Result<()> {
+        // a corrupted $DATA size shouldn't make records up: those after the last
+        // allocated run would only be zeros
+        let mut len = 0;
+        let mut allocated = 0;
+        for run in &self.mft.runs {
+            len += run.len;
+            if run.offset.is_some() {
+                allocated = len;
+            }
+        }
+        let count = allocated / self.record_size;
+        let mut reader = self.mft.reader();
+
+        for n in 0..count {
+            let mut raw = vec![0; self.record_size as usize];
+            reader.read_exact(&mut raw)?;
+
+            let node = Record::parse(raw).and_then(|record| {
+                if record.base != 0 {
+                    self.extensions
+                        .entry(record.base & RECORD_MASK)
+                        .or_default()
+                        .push(n);
+                    return None;
+                }
+                let name = file_name(&record.attributes())?;
+                Some(Node {
+                    sequence: record.sequence,
+                    flags: record.flags,
+                    parent: name.parent,
+                    name: name.name,
+                })
+            });
+            self.nodes.push(node);
+        }
+
+        for n in 0..count {
+            let in_use = self.node(n).is_some_and(|n| n.flags & RECORD_IN_USE != 0);
+            if n == ROOT_RECORD || !in_use {
+                continue;
+            }
+            if let Some(parent) = self.live_parent(n) {
+                self.children.entry(parent).or_default().push(n);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn node(&self, n: u64) -> Option<&Node> {
+        self.nodes.get(n as usize)?.as_ref()
+    }
+
+    // parent directory if the reference still matches it, None if it was reused since
+    fn parent(&self, n: u64) -> Option<u64> {
+        let reference = self.node(n)?.parent;
+        let parent = reference & RECORD_MASK;
+        let node = self.node(parent)?;
+        let sequence = (reference >> 48) as u16;
+
+        // deleting a record increments its sequence number
+        let same = sequence == 0
+            || sequence == node.sequence
+            || node.flags & RECORD_IN_USE == 0 && sequence.wrapping_add(1) == node.sequence;
+        (node.flags & RECORD_DIRECTORY != 0 && same).then_some(parent)
+    }
+
+    fn live_parent(&self, n: u64) -> Option<u64> {
+        self.parent(n)
+            .filter(|&p| self.node(p).is_some_and(|p| p.flags & RECORD_IN_USE != 0))
+    }
+
+    // path relative to the root with its depth, None if an ancestor is missing
+    fn path(&self, n: u64) -> Option<(String, usize)> {
+        let mut names = Vec::new();
+        let mut current = n;
+        while current != ROOT_RECORD {
+            if names.len() >= MAX_DEPTH {
+                return None;
+            }
+            names.push(self.node(current)?.name.as_str());
+            current = self.parent(current)?;
+        }
+
+        names.reverse();
+        Some((names.join("/"), names.len()))
+    }
+
+    fn record(&self, n: u64) -> anyhow::Result<Record> {
+        let mut raw = vec![0; self.record_size as usize];
+        self.mft.read_exact_at(&mut raw, n * self.record_size)?;
+        match Record::parse(raw) {
+            Some(record) => Ok(record),
+            None => bail!("invalid MFT record {n}"),
+        }
+    }
+
+    // base record followed by its extension records
+    fn records(&self, n: u64) -> anyhow::Result<Vec<Record>> {
+        let base = self.record(n)?;
+        let mut records = Vec::new();
+
+        for &e in self.extensions.get(&n).into_iter().flatten() {
+            let Ok(record) = self.record(e) else {
+                continue;
+            };
+
+            // extension records of a previous use of the base record
+            let sequence = (record.base >> 48) as u16;
+            let deleted = base.flags & RECORD_IN_USE == 0;
+            if sequence == base.sequence || deleted && sequence.wrapping_add(1) == base.sequence {
+                records.push(record);
+            }
+        }
+
+        records.insert(0, base);
+        Ok(records)
+    }
+
+    // returns true when the walk has to stop
+    fn walk_dir(
+        &mut self,
+        n: u64,
+        path: &str,
+        depth: usize,
+        visit: &mut dyn FnMut(ImageFile) -> Walk,
+    ) -> anyhow::Result<bool> {
+        // removed so that each directory is walked once
+        let children = self.children.remove(&n).unwrap_or_default();
+
+        for child in children {
+            let Some(node) = self.node(child) else {
+                continue;
+            };
+            let is_dir = node.flags & RECORD_DIRECTORY != 0;
+            let path = format!("{path}/{}", node.name);
+
+            let files = match self.image_files(child, &path, depth, false) {
+                Ok(files) => files,
+                Err(e) => {
+                    warn!("unable to read '{path}': {e}");
+                    continue;
+                }
+            };
+
+            // the entry itself, then its named streams
+            let mut next = Walk::Continue;
+            for (i, file) in files.into_iter().enumerate() {
+                match visit(file) {
+                    Walk::Stop => return Ok(true),
+                    Walk::Skip if i == 0 => next = Walk::Skip,
+                    _ => (),
+                }
+            }
+
+            if !is_dir || next != Walk::Continue {
+                continue;
+            }
+            if depth >= MAX_WALK_DEPTH {
+                warn!("'{path}' is nested too deeply, not walked");
+                continue;
+            }
+            if self.walk_dir(child, &path, depth + 1, visit)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    // deleted records, and live ones whose parent is unknown. Returns true when the
+    // walk has to stop
+    fn walk_others(
+        &mut self,
+        root: &str,
+        visit: &mut dyn FnMut(ImageFile) -> Walk,
+    ) -> anyhow::Result<bool> {
+        if self.volume.is_some() {
+            self.bitmap = match self.cluster_bitmap() {
+                Ok(bitmap) => Some(bitmap),
+                Err(e) => {
+                    warn!("unable to read $Bitmap: {e}");
+                    None
+                }
+            };
+        }
+
+        for n in 0..self.nodes.len() as u64 {
+            let Some(node) = self.node(n) else {
+                continue;
+            };
+            let deleted = node.flags & RECORD_IN_USE == 0;
+            if n == ROOT_RECORD || !deleted && self.live_parent(n).is_some() {
+                continue;
+            }
+
+            let (path, depth) = match self.path(n) {
+                Some((path, depth)) => (format!("{root}/{path}"), depth),
+                None => (format!("{root}/{ORPHAN_FILES}/{}", node.name), 2),
+            };
+            let files = match self.image_files(n, &path, depth, deleted) {
+                Ok(files) => files,
+                Err(e) => {
+                    warn!("unable to read '{path}': {e}");
+                    continue;
+                }
+            };
+            for file in files {
+                if visit(file) == Walk::Stop {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn cluster_bitmap(&self) -> anyhow::Result<Vec<u8>> {
+        let records = self.records(BITMAP_RECORD)?;
+        let attributes: Vec<_> = records.iter().flat_map(Record::attributes).collect();
+
+        let mut bitmap = Vec::new();
+        if let (_, Some(content)) = self.data(&unnamed_data(&attributes), false) {
+            content.reader().read_to_end(&mut bitmap)?;
+        }
+        Ok(bitmap)
+    }
+
+    // the record as a file or directory, followed by its named data streams
+    fn image_files(
+        &self,
+        n: u64,
+        path: &str,
+        depth: usize,
+        deleted: bool,
+    ) -> anyhow::Result<Vec<ImageFile>> {
+        let records = self.records(n)?;
+        let base = &records[0];
+        let attributes: Vec<_> = records.iter().flat_map(Record::attributes).collect();
+
+        let si = attributes.iter().find_map(|a| match a.body {
+            Body::Resident(value) if a.r#type == STANDARD_INFORMATION && value.len() >= 32 => {
+                Some(value)
+            }
+            _ => None,
+        });
+        let name = file_name(&attributes);
+
+        // data attributes by stream name, the unnamed one first
+        let mut streams: Vec<(&str, Vec<&Attribute>)> = Vec::new();
+        for a in attributes.iter().filter(|a| a.r#type == DATA) {
+            match streams.iter_mut().find(|(name, _)| *name == a.name) {
+                Some((_, parts)) => parts.push(a),
+                None => streams.push((&a.name, vec![a])),
+            }
+        }
+        streams.sort_by_key(|(name, _)| !name.is_empty());
+
+        let is_dir = base.flags & RECORD_DIRECTORY != 0;
+        let mut files = Vec::new();
+        if streams.first().is_none_or(|(name, _)| !name.is_empty()) {
+            streams.insert(0, ("", Vec::new()));
+        }
+
+        for (stream, parts) in streams {
+            let (path, r#type) = if stream.is_empty() {
+                let r#type = if is_dir {
+                    ForensicsFileType::Directory
+                } else {
+                    ForensicsFileType::File
+                };
+                (path.to_string(), r#type)
+            } else {
+                (format!("{path}:{stream}"), ForensicsFileType::File)
+            };
+
+            let (len, content) = self.data(&parts, deleted);
+            let mut file = ImageFile::new(path, r#type, len);
+            file.depth = depth;
+
+            let fi = &mut file.fi;
+            fi.inode = Some(n as i64);
+            fi.sequence = Some(base.sequence as i32);
+            fi.nlink = Some(base.links as i64);
+            if let Some(si) = si {
+                fi.created = nt_time(si, 0);
+                fi.modified = nt_time(si, 8).unwrap_or(UNIX_EPOCH);
+                fi.changed = nt_time(si, 16);
+                fi.accessed = nt_time(si, 24).unwrap_or(UNIX_EPOCH);
+            }
+            if let Some(name) = &name {
+                fi.fn_created = name.created;
+                fi.fn_modified = name.modified;
+                fi.fn_changed = name.changed;
+                fi.fn_accessed = name.accessed;
+            }
+            fi.allocation = Some(if deleted { DELETED } else { ALLOCATED }.to_string());
+
+            // resident data isn't at a single place in the image, because of fixups
+            if parts
+                .iter()
+                .all(|a| matches!(a.body, Body::NonResident { .. }))
+            {
+                fi.extents = content.as_ref().map(Content::extents);
+            }
+            file.content = content;
+            files.push(file);
+        }
+
+        Ok(files)
+    }
+
+    // size and content of a data stream, given as one resident attribute or as
+    // non-resident ones for consecutive VCN ranges
+    fn data(&self, parts: &[&Attribute], deleted: bool) -> (u64, Option<Content>) {
+        let mut parts = parts.to_vec();
+        parts.sort_by_key(|a| a.first_vcn());
+        let Some(first) = parts.first() else {
+            return (0, None);
+        };
+
+        let (size, initialized) = match first.body {
+            Body::Resident(value) => {
+                let len = value.len() as u64;
+                let content = Content {
+                    volume: Volume::from(Arc::new(value.to_vec()) as Arc<dyn Source>),
+                    runs: vec![Run {
+                        offset: Some(0),
+                        len,
+                    }],
+                };
+                return (len, (len != 0).then_some(content));
+            }
+            Body::NonResident {
+                size, initialized, ..
+            } => (size, initialized),
+        };
+
+        let Some(volume) = &self.volume else {
+            return (size, None);
+        };
+
+        // compressed or encrypted data can't be read as is
+        if first.flags & (ATTR_COMPRESSED | ATTR_ENCRYPTED) != 0 {
+            return (size, None);
+        }
+
+        let cluster_runs: Vec<_> = parts
+            .iter()
+            .filter_map(|a| match a.body {
+                Body::NonResident { runlist, .. } => Some(data_runs(runlist)),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        // the size of a corrupted record could be anything: what isn't allocated isn't read.
+        // Data beyond the initialized size is read as zeros
+        let allocated = cluster_runs.iter().fold(0u64, |total, (_, count)| {
+            total.saturating_add(count.saturating_mul(self.cluster_size))
+        });
+        let clusters = volume.len() / self.cluster_size;
+        let mut runs = Runs::new(size.min(allocated));
+        let mut pos = 0;
+        for (lcn, count) in cluster_runs {
+            let len = count.saturating_mul(self.cluster_size);
+            let valid = len.min(initialized.saturating_sub(pos));
+            let lcn = lcn.filter(|&lcn| lcn.saturating_add(count) <= clusters);
+            runs.push(lcn.map(|lcn| lcn * self.cluster_size), valid);
+            runs.push(None, len - valid);
+            pos = pos.saturating_add(len);
+        }
+        let content = Content {
+            volume: volume.clone(),
+            runs: runs.finish(),
+        };
+
+        // fully sparse streams, e.g. $BadClus:$Bad as large as the volume, aren't read.
+        // Clusters of deleted records could have been reused since
+        let sparse = content.runs.iter().all(|r| r.offset.is_none());
+        if sparse || deleted && !self.is_recoverable(&content) {
+            return (size, None);
+        }
+        (size, Some(content))
+    }
+
+    fn is_recoverable(&self, content: &Content) -> bool {
+        let Some(bitmap) = &self.bitmap else {
+            return false;
+        };
+        let is_free = |cluster: u64| {
+            bitmap
+                .get((cluster / 8) as usize)
+                .is_some_and(|b| b & (1 << (cluster % 8)) == 0)
+        };
+
+        content
+            .runs
+            .iter()
+            .filter_map(|r| Some((r.offset?, r.len)))
+            .all(|(offset, len)| {
+                let first = offset / self.cluster_size;
+                (first..(offset + len).div_ceil(self.cluster_size)).all(is_free)
+            })
+    }
+}
+
+fn unnamed_data<'a, 'b>(attributes: &'b [Attribute<'a>]) -> Vec<&'b Attribute<'a>> {
+    attributes
+        .iter()
+        .filter(|a| a.r#type == DATA && a.name.is_empty())
+        .collect()
+}
+
+// (first cluster, cluster count) of a runlist, None being sparse. Each run gives its length
+// then its offset relative to the previous run, sizes of both being in its header byte
+fn data_runs(runlist: &[u8]) -> Vec<(Option<u64>, u64)> {
+    let mut runs = Vec::new();
+    let mut lcn = 0i64;
+    let mut pos = 0;
+
+    while let Some(&header) = runlist.get(pos).filter(|&&h| h != 0) {
+        let len_size = (header & 0xF) as usize;
+        let offset_size = (header >> 4) as usize;
+        if len_size == 0 || len_size > 8 || offset_size > 8 {
+            break;
+        }
+        let Some(run) = runlist.get(pos + 1..pos + 1 + len_size + offset_size) else {
+            break;
+        };
+        pos += 1 + len_size + offset_size;
+
+        let count = le_var(&run[..len_size], false) as u64;
+        if offset_size == 0 {
+            runs.push((None, count));
+            continue;
+        }
+        lcn = lcn.wrapping_add(le_var(&run[len_size..], true));
+        if lcn < 0 {
+            break;
+        }
+        runs.push((Some(lcn as u64), count));
+    }
+
+    runs
+}
+
+// little-endian integer of up to 8 bytes, sign extended if asked
+fn le_var(b: &[u8], signed: bool) -> i64 {
+    let mut buf = if signed && b.last().is_some_and(|&last| last & 0x80 != 0) {
+        [0xFF; 8]
+    } else {
+        [0; 8]
+    };
+    buf[..b.len()].copy_from_slice(b);
+    i64::from_le_bytes(buf)
+}
+
+// 100 ns intervals since 1601-01-01, 0 being unset
+fn nt_time(b: &[u8], offset: usize) -> Option<SystemTime> {
+    let time = le_u64(b, offset);
+    if time == 0 {
+        return None;
+    }
+    let since = Duration::new(time / 10_000_000, (time % 10_000_000) as u32 * 100);
+    UNIX_EPOCH
+        .checked_sub(Duration::from_secs(NT_EPOCH_OFFSET))?
+        .checked_add(since)
+}
+
+fn utf16(b: &[u8]) -> String {
+    let units: Vec<u16> = b
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1 MiB volume without indexes: docs/small.txt with a timestomped $SI and a Zone.Identifier
+    // stream, docs/big.bin fragmented and sparse, docs/split.bin continued in an extension
+    // record, docs/init.bin half initialized, and deleted docs/gone.txt, docs/old.txt and
+    // olddir/inner.txt
+    fn image() -> Vec<u8> {
+        let file = std::fs::File::open("tests/test.ntfs.zip").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        let mut data = Vec::new();
+        zip.by_index(0).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    fn walk(mut fs: Ntfs) -> Vec<(crate::fileinfo::FileInfo, Vec<u8>)> {
+        let mut files = Vec::new();
+        fs.walk(&mut |file| {
+            let mut data = Vec::new();
+            if let Some(content) = &file.content {
+                content.reader().read_to_end(&mut data).unwrap();
+            }
+            files.push((file.fi, data));
+            Walk::Continue
+        })
+        .unwrap();
+        files
+    }
+
+    #[test]
+    fn ntfs() {
+        let volume = Volume::from(Arc::new(image()) as Arc<dyn Source>);
+        let files = walk(Ntfs::new(volume, "img!/p0"));
+        let find = |path: &str| files.iter().find(|(fi, _)| fi.path == path).unwrap();
+
+        let (small, data) = find("img!/p0/docs/small.txt");
+        assert_eq!(data, b"hello ntfs\n");
+        assert_eq!(small.inode, Some(17));
+        assert_eq!(small.sequence, Some(1));
+        assert!(small.created < small.fn_created);
+
+        let (stream, data) = find("img!/p0/docs/small.txt:Zone.Identifier");
+        assert_eq!(stream.inode, Some(17));
+        assert!(data.starts_with(b"[ZoneTransfer]"));
+
+        let (big, data) = find("img!/p0/docs/big.bin");
+        assert_eq!(big.len, 7 * 4096 - 100);
+        assert_eq!(data.len(), big.len as usize);
+        assert!(data[8192..16384].iter().all(|&b| b == 0));
+        assert_eq!(data[16384], (16384 * 7 % 251) as u8);
+
+        let (_, data) = find("img!/p0/docs/split.bin");
+        assert_eq!(data.len(), 8192);
+        assert_eq!(data[4097], 1);
+
+        let (_, data) = find("img!/p0/docs/init.bin");
+        assert!(data[..4096].iter().all(|&b| b == 0xAA));
+        assert!(data[4096..].iter().all(|&b| b == 0));
+
+        let (gone, data) = find("img!/p0/docs/gone.txt");
+        assert_eq!(gone.allocation.as_deref(), Some(DELETED));
+        assert_eq!(data, b"gone but here");
+
+        let (_, data) = find("img!/p0/olddir/inner.txt");
+        assert_eq!(data, b"inner\n");
+        find("img!/p0/$OrphanFiles/lost.txt");
+    }
+
+    #[test]
+    fn mft() {
+        // $MFT is 64 records from cluster 4
+        let image = image()[4 * 4096..4 * 4096 + 64 * 1024].to_vec();
+        let volume = Volume::from(Arc::new(image) as Arc<dyn Source>);
+        let files = walk(Ntfs::from_mft(volume, "$MFT!/p0"));
+        let find = |path: &str| files.iter().find(|(fi, _)| fi.path == path).unwrap();
+
+        // only resident data can be read
+        let (_, data) = find("$MFT!/p0/docs/small.txt");
+        assert_eq!(data, b"hello ntfs\n");
+        let (big, data) = find("$MFT!/p0/docs/big.bin");
+        assert_eq!(big.len, 7 * 4096 - 100);
+        assert!(data.is_empty());
+        let (_, data) = find("$MFT!/p0/docs/old.txt");
+        assert_eq!(data, b"deleted resident\n");
+    }
+
+    #[test]
+    fn corrupted() {
+        // $MFT $DATA as large as 1 TiB, its runs being left as is
+        let mut data = image();
+        let record = &mut data[4 * 4096..5 * 4096];
+        let mut offset = le_u16(record, 20) as usize;
+        while le_u32(record, offset) != 0x80 {
+            offset += le_u32(record, offset + 4) as usize;
+        }
+        record[offset + 48..offset + 56].copy_from_slice(&(1u64 << 40).to_le_bytes());
+
+        // its content stops at the end of its allocated runs
+        let len = data.len() as u64;
+        let volume = Volume::from(Arc::new(data) as Arc<dyn Source>);
+        let mut files = Vec::new();
+        Ntfs::new(volume, "img!/p0")
+            .walk(&mut |file| {
+                let content = file
+                    .content
+                    .map(|c| c.runs.iter().map(|r| r.len).sum::<u64>());
+                files.push((file.fi.path, file.fi.len, content));
+                Walk::Continue
+            })
+            .unwrap();
+        assert!(files
+            .iter()
+            .any(|(path, ..)| path == "img!/p0/docs/small.txt"));
+        let (_, size, content) = files
+            .iter()
+            .find(|(path, ..)| path == "img!/p0/$MFT")
+            .unwrap();
+        assert_eq!(*size, 1 << 40);
+        assert!(content.is_some_and(|content| content >= 64 * 1024 && content <= len));
+
+        // 2^128 bytes per MFT record, the shift being capped
+        let mut boot = image()[..512].to_vec();
+        boot[64] = 0x80;
+        let volume = Volume::from(Arc::new(boot) as Arc<dyn Source>);
+        assert!(Reader::new(&volume).is_err());
+    }
+}
@@ -0,0 +1,393 @@
+// MBR and GPT partition tables. Images without a partition table are seen as a
+// single partition numbered 0
+use std::io;
+
+use log::warn;
+
+use crate::{
+    fileinfo::Partition,
+    image::{le_u32, le_u64, Volume},
+};
+
+const SECTOR_SIZE: u64 = 512;
+
+// MBR
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_ENTRIES_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_GPT_PROTECTIVE: u8 = 0xEE;
+const MBR_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+
+// logical partitions are numbered from 5, whatever the number of primary ones
+const FIRST_LOGICAL: i32 = 5;
+
+// a loop in the extended partitions chain shouldn't hang the scan
+const MAX_LOGICAL: i32 = 128;
+
+// GPT
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+const GPT_MAX_ENTRIES: u32 = 1024;
+const GPT_MIN_ENTRY_SIZE: usize = 128;
+
+// the specification allows larger entries, but real ones never exceed a sector
+const GPT_MAX_ENTRY_SIZE: usize = 4096;
+
+pub const SCHEME_MBR: &str = "mbr";
+pub const SCHEME_GPT: &str = "gpt";
+pub const SCHEME_NONE: &str = "none";
+
+// partitions found on the disk, run_id, image and filesystem being set by the caller
+pub fn find(disk: &Volume) -> io::Result<Vec<Partition>> {
+    let mbr = disk.read_vec(0, SECTOR_SIZE as usize)?;
+
+    let partitions = if mbr[510..512] != MBR_SIGNATURE {
+        Vec::new()
+    } else if mbr_entries(&mbr).any(|e| e[4] == MBR_GPT_PROTECTIVE) {
+        gpt(disk)
+    } else {
+        self::mbr(disk, &mbr)
+    };
+
+    // e.g. a FAT boot sector has the MBR signature too: the whole disk is then a single volume
+    if partitions.is_empty() {
+        return Ok(vec![Partition {
+            number: 0,
+            scheme: SCHEME_NONE.to_string(),
+            start: 0,
+            len: disk.len() as i64,
+            ..Default::default()
+        }]);
+    }
+
+    Ok(partitions)
+}
+
+fn mbr_entries(sector: &[u8]) -> impl Iterator<Item = &[u8]> {
+    sector[MBR_ENTRIES_OFFSET..MBR_ENTRIES_OFFSET + 4 * MBR_ENTRY_SIZE].chunks_exact(MBR_ENTRY_SIZE)
+}
+
+// primary partitions, then logical ones found in the extended partition chain
+fn mbr(disk: &Volume, sector: &[u8]) -> Vec<Partition> {
+    let mut partitions = Vec::new();
+
+    for (i, entry) in mbr_entries(sector).enumerate() {
+        let Some(mut partition) = mbr_partition(entry, 0, disk) else {
+            continue;
+        };
+        partition.number = i as i32 + 1;
+
+        if MBR_EXTENDED.contains(&entry[4]) {
+            let extended = partition.start as u64 / SECTOR_SIZE;
+            partitions.extend(logical(disk, extended));
+        }
+        partitions.push(partition);
+    }
+
+    partitions.sort_by_key(|p| p.number);
+    partitions
+}
+
+// each EBR holds a logical partition, relative to the EBR itself, and the next EBR,
+// relative to the extended partition start
+fn logical(disk: &Volume, extended: u64) -> Vec<Partition> {
+    let mut partitions = Vec::new();
+    let mut ebr = extended;
+
+    for number in FIRST_LOGICAL..FIRST_LOGICAL + MAX_LOGICAL {
+        // partitions found so far are kept
+        let sector = match disk.read_vec(ebr * SECTOR_SIZE, SECTOR_SIZE as usize) {
+            Ok(sector) => sector,
+            Err(e) => {
+                warn!("unable to read the EBR at sector {ebr}: {e}");
+                break;
+            }
+        };
+        if sector[510..512] != MBR_SIGNATURE {
+            break;
+        }
+
+        let mut entries = mbr_entries(&sector);
+        let (Some(first), Some(next)) = (entries.next(), entries.next()) else {
+            break;
+        };
+
+        if let Some(mut partition) = mbr_partition(first, ebr, disk) {
+            partition.number = number;
+            partitions.push(partition);
+        }
+
+        let next_lba = le_u32(next, 8) as u64;
+        if next[4] == 0 || next_lba == 0 {
+            break;
+        }
+        ebr = extended + next_lba;
+    }
+
+    partitions
+}
+
+// None for unused entries or those outside of the disk
+fn mbr_partition(entry: &[u8], base: u64, disk: &Volume) -> Option<Partition> {
+    let kind = entry[4];
+    let start = base + le_u32(entry, 8) as u64;
+    let sectors = le_u32(entry, 12) as u64;
+    if kind == 0 || sectors == 0 || start * SECTOR_SIZE >= disk.len() {
+        return None;
+    }
+
+    Some(Partition {
+        scheme: SCHEME_MBR.to_string(),
+        start: (start * SECTOR_SIZE) as i64,
+        len: (sectors * SECTOR_SIZE) as i64,
+        type_id: format!("0x{kind:02x}"),
+        type_name: mbr_type_name(kind).map(String::from),
+        ..Default::default()
+    })
+}
+
+// unreadable tables are warned about and seen as empty, not failing the whole image
+fn gpt(disk: &Volume) -> Vec<Partition> {
+    // the header is in the second sector, whatever the sector size
+    let Some(sector_size) = [SECTOR_SIZE, 4096].into_iter().find(|size| {
+        disk.read_vec(*size, GPT_SIGNATURE.len())
+            .is_ok_and(|s| s == GPT_SIGNATURE)
+    }) else {
+        return Vec::new();
+    };
+
+    let header = match disk.read_vec(sector_size, 92) {
+        Ok(header) => header,
+        Err(e) => {
+            warn!("unable to read the GPT header: {e}");
+            return Vec::new();
+        }
+    };
+    let entries_lba = le_u64(&header, 72);
+    let count = le_u32(&header, 80).min(GPT_MAX_ENTRIES);
+    let entry_size = le_u32(&header, 84) as usize;
+    if !(GPT_MIN_ENTRY_SIZE..=GPT_MAX_ENTRY_SIZE).contains(&entry_size) {
+        warn!("invalid GPT entry size {entry_size}");
+        return Vec::new();
+    }
+
+    let Some(offset) = entries_lba.checked_mul(sector_size) else {
+        warn!("invalid GPT entries LBA {entries_lba}");
+        return Vec::new();
+    };
+    let entries = match disk.read_vec(offset, count as usize * entry_size) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("unable to read {count} GPT entries at offset {offset}: {e}");
+            return Vec::new();
+        }
+    };
+    let mut partitions = Vec::new();
+
+    for (i, entry) in entries.chunks_exact(entry_size).enumerate() {
+        let type_guid = &entry[0..16];
+        if type_guid.iter().all(|b| *b == 0) {
+            continue;
+        }
+
+        // bad entries are skipped, e.g. ending before they start or past the disk
+        let first = le_u64(entry, 32);
+        let last = le_u64(entry, 40);
+        let Some((start, len)) = first
+            .checked_mul(sector_size)
+            .zip(
+                last.checked_sub(first)
+                    .and_then(|n| n.checked_add(1))
+                    .and_then(|n| n.checked_mul(sector_size)),
+            )
+            .filter(|(start, len)| start.checked_add(*len).is_some_and(|end| end <= disk.len()))
+        else {
+            warn!("invalid GPT entry {}: sectors {first} to {last}", i + 1);
+            continue;
+        };
+
+        // UTF-16LE, null padded
+        let name: Vec<u16> = entry[56..128]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0)
+            .collect();
+        let name = String::from_utf16_lossy(&name);
+
+        let type_id = guid(type_guid);
+        partitions.push(Partition {
+            number: i as i32 + 1,
+            scheme: SCHEME_GPT.to_string(),
+            start: start as i64,
+            len: len as i64,
+            type_name: gpt_type_name(&type_id).map(String::from),
+            type_id,
+            label: (!name.is_empty()).then_some(name),
+            guid: Some(guid(&entry[16..32])),
+            ..Default::default()
+        });
+    }
+
+    partitions
+}
+
+// first 3 fields are little endian
+fn guid(b: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+        le_u32(b, 0),
+        u16::from_le_bytes([b[4], b[5]]),
+        u16::from_le_bytes([b[6], b[7]]),
+        b[8],
+        b[9],
+        b[10],
+        b[11],
+        b[12],
+        b[13],
+        b[14],
+        b[15]
+    )
+}
+
+fn mbr_type_name(kind: u8) -> Option<&'static str> {
+    let name = match kind {
+        0x01 => "FAT12",
+        0x04 | 0x06 | 0x0E => "FAT16",
+        0x05 | 0x0F | 0x85 => "Extended",
+        0x07 => "NTFS/exFAT",
+        0x0B | 0x0C => "FAT32",
+        0x27 => "Windows recovery",
+        0x82 => "Linux swap",
+        0x83 => "Linux",
+        0x8E => "Linux LVM",
+        0xA5 => "FreeBSD",
+        0xAF => "HFS+",
+        0xEE => "GPT protective",
+        0xEF => "EFI system",
+        0xFD => "Linux RAID",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn gpt_type_name(guid: &str) -> Option<&'static str> {
+    let name = match guid {
+        "C12A7328-F81F-11D2-BA4B-00A0C93EC93B" => "EFI system",
+        "21686148-6449-6E6F-744E-656564454649" => "BIOS boot",
+        "E3C9E316-0B5C-4DB8-817D-F92DF00215AE" => "Microsoft reserved",
+        "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7" => "Microsoft basic data",
+        "DE94BBA4-06D1-4D40-A16A-BFD50179D6AC" => "Windows recovery",
+        "5808C8AA-7E8F-42E0-85D2-E1E90434CFB3" => "LDM metadata",
+        "AF9B60A0-1431-4F62-BC68-3311714A69AD" => "LDM data",
+        "0FC63DAF-8483-4772-8E79-3D69D8477DE4" => "Linux filesystem",
+        "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F" => "Linux swap",
+        "E6D6D379-F507-44C2-A23C-238F2A3DF928" => "Linux LVM",
+        "A19D880F-05FC-4D3B-A006-743F0F84911E" => "Linux RAID",
+        "4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709" => "Linux root (x86-64)",
+        "933AC7E1-2EB4-4F13-B844-0E14E2AEF915" => "Linux home",
+        "CA7D7CCB-63ED-4C53-861C-1742536059CC" => "LUKS",
+        "48465300-0000-11AA-AA11-00306543ECAC" => "HFS+",
+        "7C3457EF-0000-11AA-AA11-00306543ECAC" => "APFS",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn mbr_entry(sector: &mut [u8], i: usize, kind: u8, start: u32, sectors: u32) {
+        let e = MBR_ENTRIES_OFFSET + i * MBR_ENTRY_SIZE;
+        sector[e + 4] = kind;
+        sector[e + 8..e + 12].copy_from_slice(&start.to_le_bytes());
+        sector[e + 12..e + 16].copy_from_slice(&sectors.to_le_bytes());
+        sector[510..512].copy_from_slice(&MBR_SIGNATURE);
+    }
+
+    #[test]
+    fn mbr() {
+        let mut disk = vec![0u8; 4096 * 512];
+
+        // a primary partition and an extended one holding 2 logical partitions
+        mbr_entry(&mut disk, 0, 0x83, 2048, 1000);
+        mbr_entry(&mut disk, 1, 0x05, 3072, 1024);
+        mbr_entry(&mut disk[3072 * 512..], 0, 0x07, 1, 100);
+        mbr_entry(&mut disk[3072 * 512..], 1, 0x05, 200, 300);
+        mbr_entry(&mut disk[3272 * 512..], 0, 0x0C, 1, 200);
+
+        let partitions = find(&Volume::from(Arc::new(disk) as Arc<_>)).unwrap();
+        let found: Vec<_> = partitions
+            .iter()
+            .map(|p| (p.number, p.start / 512, p.len / 512, p.type_id.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (1, 2048, 1000, "0x83"),
+                (2, 3072, 1024, "0x05"),
+                (5, 3073, 100, "0x07"),
+                (6, 3273, 200, "0x0c"),
+            ]
+        );
+        assert_eq!(partitions[3].type_name.as_deref(), Some("FAT32"));
+
+        // no partition table
+        let partitions = find(&Volume::from(Arc::new(vec![0u8; 1024]) as Arc<_>)).unwrap();
+        assert_eq!(partitions[0].scheme, SCHEME_NONE);
+        assert_eq!(partitions[0].len, 1024);
+    }
+
+    #[test]
+    fn gpt() {
+        let mut disk = vec![0u8; 128 * 512];
+        mbr_entry(&mut disk, 0, MBR_GPT_PROTECTIVE, 1, 127);
+
+        let header = &mut disk[512..];
+        header[..8].copy_from_slice(GPT_SIGNATURE);
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&128u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+
+        // EFI system partition, as written on disk
+        let entry = &mut disk[1024 + 128..];
+        entry[..16].copy_from_slice(&hex_literal::hex!("28732AC11FF8D211BA4B00A0C93EC93B"));
+        entry[16] = 1;
+        entry[32..40].copy_from_slice(&34u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&99u64.to_le_bytes());
+        for (i, c) in "EFI".encode_utf16().enumerate() {
+            entry[56 + 2 * i..58 + 2 * i].copy_from_slice(&c.to_le_bytes());
+        }
+
+        // ending before it starts
+        let entry = &mut disk[1024 + 256..];
+        entry[0] = 1;
+        entry[32..40].copy_from_slice(&50u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&10u64.to_le_bytes());
+
+        // ending past the disk, its length not fitting in an i64
+        let entry = &mut disk[1024 + 384..];
+        entry[0] = 1;
+        entry[32..40].copy_from_slice(&100u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&(u64::MAX / 512).to_le_bytes());
+
+        let partitions = find(&Volume::from(Arc::new(disk.clone()) as Arc<_>)).unwrap();
+        assert_eq!(partitions.len(), 1);
+        let p = &partitions[0];
+        assert_eq!((p.number, p.start, p.len), (2, 34 * 512, 66 * 512));
+        assert_eq!(p.type_id, "C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+        assert_eq!(p.type_name.as_deref(), Some("EFI system"));
+        assert_eq!(p.label.as_deref(), Some("EFI"));
+        assert_eq!(
+            p.guid.as_deref(),
+            Some("00000001-0000-0000-0000-000000000000")
+        );
+
+        // entries past the end of the disk: unreadable, the disk is then seen as unpartitioned
+        disk[512 + 72..512 + 80].copy_from_slice(&1000u64.to_le_bytes());
+        let partitions = find(&Volume::from(Arc::new(disk) as Arc<_>)).unwrap();
+        assert_eq!(partitions.len(), 1);
+        assert_eq!(partitions[0].scheme, SCHEME_NONE);
+    }
+}
@@ -1,36 +1,52 @@
+// file type names (PNG, ZIP, ...) are kept uppercase on purpose
+#![allow(clippy::upper_case_acronyms)]
+
 use std::{
-    sync::Arc,
+    sync::{atomic::Ordering, Arc},
     time::{Instant, SystemTime},
-    u64,
 };
 
 // crates
 use crossbeam_channel as channel;
-use diesel::RunQueryDsl;
 use humantime::format_duration;
-use log::{debug, error, info, trace};
-
-use walkdir::{DirEntry, WalkDir};
+use log::{debug, error, info, warn};
 
 // local modules
 mod args;
 use args::get_args;
 
 mod worker;
-use worker::thread_pool;
+use worker::{thread_pool, Entry};
 
 mod fileinfo;
 mod hash;
 mod memory;
 mod pool;
 mod schema;
-use pool::establish_pool;
+mod storage;
+use storage::Backend;
 mod config;
 mod discoverer;
+mod resume;
+use resume::PreviousRuns;
+mod filter;
+use filter::WalkFilter;
+mod fuzzy;
+mod hashset;
+mod image;
+use image::{ImageFile, Walk};
+mod randomness;
+#[cfg(test)]
+mod testing;
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+mod xattrs;
 
-use schema::artefact::dsl::artefact;
-
-use crate::{args::raw_args, fileinfo::RunHistory, schema::run_history::dsl::run_history};
+use crate::{
+    args::raw_args,
+    fileinfo::{RunHistory, KNOWN_BAD, KNOWN_GOOD, RUN_COMPLETED, RUN_INTERRUPTED, RUN_RUNNING},
+};
 
 fn main() -> anyhow::Result<()> {
     let now = Instant::now();
@@ -39,59 +55,208 @@ fn main() -> anyhow::Result<()> {
     // get cli options
     //───────────────────────────────────────────────────────────────────────────────────
     let command_line = raw_args();
-    let args = get_args()?;
+    let mut args = get_args()?;
     debug!("options: {:?}", args);
 
-    let max_count = args.n.unwrap_or_else(|| u64::MAX);
+    let max_count = args.n.unwrap_or(u64::MAX);
+
+    // invalid globs are reported before anything is done
+    let filter = WalkFilter::new(&args)?;
 
     //───────────────────────────────────────────────────────────────────────────────────
-    // start recording history
+    // select the storage backend: one connection per thread plus one for history
     //───────────────────────────────────────────────────────────────────────────────────
-    let mut history = RunHistory::default();
-    history.args = command_line;
+    let backend = Backend::new(
+        args.output.as_deref().unwrap(),
+        args.threads.unwrap() as u32 + 1,
+        args.copy,
+    )?;
 
     //───────────────────────────────────────────────────────────────────────────────────
-    // create a connection pool for PG
+    // only look for artefacts similar to a file if requested
     //───────────────────────────────────────────────────────────────────────────────────
-    let pool = establish_pool(&args.db.clone().unwrap(), args.threads.unwrap() as u32);
+    if let Some(path) = &args.similar {
+        return fuzzy::search(path, backend.connect()?.as_mut(), &args);
+    }
 
     //───────────────────────────────────────────────────────────────────────────────────
     // delete all rows first if requested
     //───────────────────────────────────────────────────────────────────────────────────
     if args.overwrite {
-        let mut conn = pool.get()?;
-        diesel::delete(artefact).execute(&mut conn)?;
+        backend.connect()?.clear()?;
     }
 
+    //───────────────────────────────────────────────────────────────────────────────────
+    // start recording history, or take it back when resuming a run
+    //───────────────────────────────────────────────────────────────────────────────────
+    let mut history_storage = backend.connect()?;
+    let source = std::fs::canonicalize(args.dir.as_deref().or(args.image.as_deref()).unwrap())?
+        .to_string_lossy()
+        .into_owned();
+
+    let mut history = match &args.resume {
+        Some(run_id) => {
+            let mut history = history_storage
+                .history(run_id)?
+                .ok_or_else(|| anyhow::anyhow!("run {run_id} not found"))?;
+            if history.status == RUN_COMPLETED {
+                warn!("run {run_id} is already completed");
+            }
+            if history.source != source {
+                warn!("run {run_id} was started on '{}'", history.source);
+            }
+            history.status = RUN_RUNNING.to_string();
+            history
+        }
+        None => {
+            // incremental scans are based on the last completed run on the same directory
+            let base_run = if args.incremental {
+                let base_run = history_storage.last_history(&source)?.map(|h| h.run_id);
+                if base_run.is_none() {
+                    warn!("no previous completed run on '{source}': full scan");
+                }
+                base_run
+            } else {
+                None
+            };
+
+            RunHistory {
+                run_id: args.run_id.clone(),
+                case_name: args.case.clone(),
+                args: command_line,
+                tags: args.tag.join(","),
+                source,
+                base_run,
+                ..Default::default()
+            }
+        }
+    };
+    info!("starting run {}", history.run_id);
+
+    // saved now so that the run could be resumed if interrupted
+    history_storage.save_history(&history)?;
+
+    //───────────────────────────────────────────────────────────────────────────────────
+    // load hash sets: artefacts are matched against them if any
+    //───────────────────────────────────────────────────────────────────────────────────
+    let hash_set_files = args
+        .known_good
+        .iter()
+        .map(|path| (path, KNOWN_GOOD))
+        .chain(args.known_bad.iter().map(|path| (path, KNOWN_BAD)));
+    for (path, kind) in hash_set_files {
+        let count = hashset::import(path, kind, history_storage.as_mut())?;
+        info!(
+            "loaded {count} known-{kind} hashes from '{}'",
+            path.display()
+        );
+    }
+
+    args.hash_sets = history_storage.has_hash_sets()?;
+    if args.hash_sets && !(args.md5 || args.sha1 || args.sha256) {
+        warn!("hash sets are only matched with --md5, --sha1 or --sha256");
+    }
+    let previous = PreviousRuns::load(history_storage.as_mut(), &history, args.resume.is_some())?;
+
+    // Ctrl-C stops the run cleanly: buffered rows are flushed and it's marked as interrupted
+    ctrlc::set_handler(|| {
+        warn!("interrupted: finishing current files");
+        resume::INTERRUPTED.store(true, Ordering::Relaxed);
+    })?;
+
     //───────────────────────────────────────────────────────────────────────────────────
     // create channels
     //───────────────────────────────────────────────────────────────────────────────────
-    let (job_sender, job_receiver) = channel::unbounded::<DirEntry>();
+    let (job_sender, job_receiver) = channel::unbounded::<Entry>();
 
     //───────────────────────────────────────────────────────────────────────────────────
     // start threads
     //───────────────────────────────────────────────────────────────────────────────────
-    let mut history_conn = pool.get()?;
-    let connection_pool = Arc::new(pool);
+    let backend = Arc::new(backend);
     let args = Arc::new(args);
-    let handles = thread_pool(args.threads.unwrap(), job_receiver, &connection_pool, &args)?;
+    let previous = Arc::new(previous);
+    let handles = thread_pool(
+        args.threads.unwrap(),
+        job_receiver,
+        &backend,
+        &args,
+        &previous,
+    )?;
     info!("created {} threads", args.threads.unwrap());
 
     //───────────────────────────────────────────────────────────────────────────────────
-    // walk through directory
+    // walk through directory, or through the disk image partitions
     //───────────────────────────────────────────────────────────────────────────────────
     let mut file_count = 0u64;
-    for entry in WalkDir::new(&args.dir) {
-        if let Ok(entry) = entry {
+    if let Some(path) = &args.image {
+        let mut visit = |file: ImageFile| {
+            if resume::interrupted() {
+                return Walk::Stop;
+            }
+
+            // same as the walker: entries below max depth are skipped, those at max depth not descended
+            if args.max_depth.is_some_and(|max| file.depth > max) || filter.is_image_excluded(&file)
+            {
+                return Walk::Skip;
+            }
+            let next = if args.max_depth.is_some_and(|max| file.depth >= max) {
+                Walk::Skip
+            } else {
+                Walk::Continue
+            };
+
+            if !filter.is_image_selected(&file) || previous.is_done(&file.fi.path) {
+                return next;
+            }
+
             file_count += 1;
-            job_sender.send(entry)?;
+            if job_sender.send(Entry::Image(Box::new(file))).is_err() {
+                return Walk::Stop;
+            }
 
             // stops after n rounds
             if file_count >= max_count {
-                return Ok(());
+                Walk::Stop
+            } else {
+                next
+            }
+        };
+        image::scan(
+            path,
+            &mut history,
+            args.verify,
+            history_storage.as_mut(),
+            &mut visit,
+        )?;
+    } else {
+        let walker = WalkFilter::walker(&args)
+            .into_iter()
+            .filter_entry(|e| !filter.is_excluded(e));
+        for entry in walker {
+            if resume::interrupted() {
+                break;
+            }
+
+            if let Ok(entry) = entry {
+                if !filter.is_selected(&entry) {
+                    continue;
+                }
+
+                // already inserted by the run being resumed
+                if previous.is_done(&entry.path().to_string_lossy()) {
+                    continue;
+                }
+
+                file_count += 1;
+                job_sender.send(Entry::Dir(entry))?;
+
+                // stops after n rounds
+                if file_count >= max_count {
+                    break;
+                }
+            } else {
+                error!("error processing entry '{:?}'", entry);
             }
-        } else {
-            error!("error processing entry '{:?}'", entry);
         }
     }
     drop(job_sender);
@@ -99,9 +264,13 @@ fn main() -> anyhow::Result<()> {
     //───────────────────────────────────────────────────────────────────────────────────
     // wait for threads to finish
     //───────────────────────────────────────────────────────────────────────────────────
+    // only entries actually processed are counted, the walker being ahead when interrupted
+    let mut processed = 0u64;
     for id in handles {
-        id.join()
-            .map_err(|e| format!("error {:?}: unable to join thread", e));
+        match id.join() {
+            Ok(count) => processed += count,
+            Err(e) => error!("error {:?}: unable to join thread", e),
+        }
     }
 
     //───────────────────────────────────────────────────────────────────────────────────
@@ -109,20 +278,28 @@ fn main() -> anyhow::Result<()> {
     //───────────────────────────────────────────────────────────────────────────────────
     let elapsed = now.elapsed();
     info!(
-        "took {} for {file_count} artefacts",
+        "took {} for {processed} artefacts",
         format_duration(elapsed)
     );
 
     //───────────────────────────────────────────────────────────────────────────────────
     // end up history
     //───────────────────────────────────────────────────────────────────────────────────
-    history.nb_files = file_count as i64;
+    history.nb_files += processed as i64;
     history.end_time = SystemTime::now();
     history.elapsed = format_duration(elapsed).to_string();
+    history.status = if resume::interrupted() {
+        warn!(
+            "run {} interrupted, resume it with --resume",
+            history.run_id
+        );
+        RUN_INTERRUPTED
+    } else {
+        RUN_COMPLETED
+    }
+    .to_string();
 
-    diesel::insert_into(run_history)
-        .values(&history)
-        .execute(&mut history_conn)?;
+    history_storage.save_history(&history)?;
 
     Ok(())
 }
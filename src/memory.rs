@@ -2,16 +2,21 @@ use std::{fs::File, io, ops::Deref, path::Path};
 
 use memmap::Mmap;
 
+use crate::fuzzy::{ssdeep, tlsh};
+
 use crate::discoverer::{
     bmp::{BitmapFileHeaderAndCore, BMP},
+    elf::ELF,
     gif::{GIF87a, GIF89a},
     ico::{IconDir, ICO},
+    jpeg::JPEG,
+    pe::PE,
     png::{IHDR, PNG},
     regf::{RegistryBaseBlock, REGF},
     sqlite3::SQLITE3,
     wav::{WavHeader, WAV},
     zip::ZIP,
-    Discoverer,
+    ArchiveMember, Discoverer,
 };
 
 macro_rules! try_discover {
@@ -42,6 +47,16 @@ macro_rules! try_discover {
         }
     };
 
+    // same, but the signature is too weak on its own (e.g.: "MZ"): the type is only
+    // reported if metadata could be extracted
+    ($Struct:ident, $Self:ident, $MetaFunc:expr, required) => {
+        if let Some(value) = $Struct::mime($Self) {
+            if let Some(metadata) = $MetaFunc($Self) {
+                return (Some(value), Some(metadata));
+            }
+        }
+    };
+
     // case of metadata are possible to extract using
     // custom metadata() but metatdata() uses Path
     ($Struct:ident, $Self:ident, $path:ident, $MetaFunc:expr) => {
@@ -55,7 +70,13 @@ macro_rules! try_discover {
 
 // as we have to calculate hashes, magic number etc, w use memmap
 // to load data in to memory
-pub struct MappedFile(Mmap);
+pub enum MappedFile {
+    // file mapped from disk
+    Mapped(Mmap),
+
+    // data already in memory, e.g. extracted from an archive
+    Memory(Vec<u8>),
+}
 
 // load file from path into memory
 impl TryFrom<&Path> for MappedFile {
@@ -68,26 +89,26 @@ impl TryFrom<&Path> for MappedFile {
         // mmemap is unsafe by nature
         let mmap = unsafe { Mmap::map(&file)? };
 
-        Ok(Self(mmap))
+        Ok(Self::Mapped(mmap))
     }
 }
 
-// now we have mapped the file, we have data in memory
-impl MappedFile {
-    // Blake3 hash
-    pub fn blake3(&self) -> String {
-        blake3::hash(self).to_string()
+impl From<Vec<u8>> for MappedFile {
+    fn from(data: Vec<u8>) -> Self {
+        Self::Memory(data)
     }
+}
 
-    // sah256 hash
-    pub fn sha256(&self) -> String {
-        // load data from file
-        sha256::digest(self.as_ref())
+// now we have mapped the file, we have data in memory
+impl MappedFile {
+    // ssdeep context triggered piecewise hash
+    pub fn ssdeep(&self) -> String {
+        ssdeep::hash(self)
     }
 
-    // calculate the Shannon entropy
-    pub fn entropy(&self) -> f32 {
-        entropy::shannon_entropy(self.as_ref())
+    // TLSH locality sensitive hash, only for data long and diverse enough
+    pub fn tlsh(&self) -> Option<String> {
+        tlsh::hash(self)
     }
 
     // try to discover mime type from magic numbers
@@ -96,6 +117,7 @@ impl MappedFile {
     // as a JSONB postgres column type
     pub fn discover(&self) -> (Option<&'static str>, Option<serde_json::Value>) {
         try_discover!(PNG, self, IHDR);
+        try_discover!(JPEG, self, JPEG::exif);
         try_discover!(GIF87a, self);
         try_discover!(GIF89a, self);
         try_discover!(WAV, self, WavHeader);
@@ -103,6 +125,8 @@ impl MappedFile {
         try_discover!(BMP, self, BitmapFileHeaderAndCore);
         try_discover!(ICO, self, IconDir);
         try_discover!(ZIP, self, ZIP::files);
+        try_discover!(ELF, self, ELF::elf);
+        try_discover!(PE, self, PE::pe, required);
 
         //try_discover!(SQLITE3, self, path, SQLITE3::tables);
 
@@ -111,18 +135,33 @@ impl MappedFile {
 
     // same but sometimes, we need the full path and not the memmap because specific crate can't handle it
     // (e.g.: sqlite)
-    pub fn discover_path(&self, path: &Path) -> anyhow::Result<(Option<&'static str>, Option<serde_json::Value>)> {
+    pub fn discover_path(
+        &self,
+        path: &Path,
+    ) -> anyhow::Result<(Option<&'static str>, Option<serde_json::Value>)> {
         try_discover!(SQLITE3, self, path, SQLITE3::tables);
 
         Ok((None, None))
     }
 
+    // if data is a supported archive, extract its members in memory
+    // budget is the remaining number of bytes allowed to be extracted
+    pub fn members(&self, budget: &mut u64) -> anyhow::Result<Vec<ArchiveMember>> {
+        if ZIP::mime(self).is_some() {
+            return ZIP::members(self, budget);
+        }
+
+        Ok(Vec::new())
+    }
 }
 
 impl Deref for MappedFile {
-    type Target = Mmap;
+    type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        match self {
+            Self::Mapped(mmap) => mmap,
+            Self::Memory(data) => data,
+        }
     }
 }
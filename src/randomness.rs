@@ -0,0 +1,231 @@
+// entropy profile of the data, to find compressed or encrypted content even
+// when it's only a part of a file (e.g. an encrypted blob appended to a document)
+use crate::fileinfo::{FileInfo, ENTROPY_COMPRESSED, ENTROPY_ENCRYPTED, ENTROPY_PLAIN};
+
+// below this, data is considered as plain. Blocks at least this high are compressed
+// or encrypted
+const HIGH_ENTROPY: f64 = 7.5;
+
+// for 255 degrees of freedom, random data has a chi-square around 255: above this
+// value, byte distribution is unlikely to be uniform (p < 0.0001)
+const MAX_UNIFORM_CHI_SQUARE: f64 = 340.0;
+
+// the chi-square test is meaningless when the expected frequencies are below 5
+const MIN_CHI_SQUARE_LEN: u64 = 256 * 5;
+
+// Shannon entropy of the whole data and of each block, calculated from the byte
+// frequencies so that data can be fed by chunks
+pub struct Entropy {
+    block_size: u64,
+
+    // byte frequencies of the current block and of the previous ones
+    block: [u64; 256],
+    block_len: u64,
+    counts: [u64; 256],
+    len: u64,
+
+    // byte frequencies of the high entropy blocks, classified apart so that a compressed
+    // or encrypted region is found even when it's only a part of the data
+    high: [u64; 256],
+    high_len: u64,
+
+    // entropy of the complete blocks
+    blocks: u64,
+    min: f64,
+    max: f64,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl Entropy {
+    pub fn new(block_size: u64) -> Self {
+        Self {
+            block_size,
+            block: [0; 256],
+            block_len: 0,
+            counts: [0; 256],
+            len: 0,
+            high: [0; 256],
+            high_len: 0,
+            blocks: 0,
+            min: f64::MAX,
+            max: 0.0,
+            sum: 0.0,
+            sum_squares: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let n = data.len().min((self.block_size - self.block_len) as usize);
+            for b in &data[..n] {
+                self.block[*b as usize] += 1;
+            }
+            self.block_len += n as u64;
+            data = &data[n..];
+
+            if self.block_len == self.block_size {
+                self.end_block();
+            }
+        }
+    }
+
+    // set entropy, block statistics, chi-square and the derived classification
+    pub fn finalize(mut self, fi: &mut FileInfo) {
+        // the last partial block is only accounted for when it's the only one
+        if self.blocks == 0 {
+            self.end_block();
+        } else {
+            self.merge_block();
+        }
+
+        let entropy = shannon(&self.counts, self.len);
+        let chi_square = chi_square(&self.counts, self.len);
+        let mean = self.sum / self.blocks as f64;
+        let variance = self.sum_squares / self.blocks as f64 - mean * mean;
+
+        fi.entropy = Some(entropy);
+        fi.entropy_min = Some(self.min);
+        fi.entropy_max = Some(self.max);
+        fi.entropy_mean = Some(mean);
+        fi.entropy_stddev = Some(variance.max(0.0).sqrt());
+        fi.chi_square = Some(chi_square);
+
+        // blocks too small to reach a high entropy: the whole data is classified
+        let class = if self.block_size < MIN_CHI_SQUARE_LEN {
+            classify(&self.counts, self.len)
+        } else {
+            classify(&self.high, self.high_len)
+        };
+        fi.entropy_class = Some(class.to_string());
+    }
+
+    fn end_block(&mut self) {
+        let e = shannon(&self.block, self.block_len);
+        self.blocks += 1;
+        self.min = self.min.min(e);
+        self.max = self.max.max(e);
+        self.sum += e;
+        self.sum_squares += e * e;
+
+        if e >= HIGH_ENTROPY {
+            for (count, b) in self.high.iter_mut().zip(&self.block) {
+                *count += b;
+            }
+            self.high_len += self.block_len;
+        }
+        self.merge_block();
+    }
+
+    fn merge_block(&mut self) {
+        for (count, b) in self.counts.iter_mut().zip(&mut self.block) {
+            *count += std::mem::take(b);
+        }
+        self.len += self.block_len;
+        self.block_len = 0;
+    }
+}
+
+// in bits per byte, from 0 to 8
+fn shannon(counts: &[u64; 256], len: u64) -> f64 {
+    let len = len as f64;
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// distance between the byte frequencies and a uniform distribution
+fn chi_square(counts: &[u64; 256], len: u64) -> f64 {
+    let expected = len as f64 / 256.0;
+    counts
+        .iter()
+        .map(|c| (*c as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+// high entropy data is either compressed or encrypted, only the latter having
+// uniformly distributed bytes
+fn classify(counts: &[u64; 256], len: u64) -> &'static str {
+    if len == 0 || shannon(counts, len) < HIGH_ENTROPY {
+        ENTROPY_PLAIN
+    } else if len >= MIN_CHI_SQUARE_LEN && chi_square(counts, len) <= MAX_UNIFORM_CHI_SQUARE {
+        ENTROPY_ENCRYPTED
+    } else {
+        ENTROPY_COMPRESSED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(data: &[u8], block_size: u64, chunk: usize) -> FileInfo {
+        let mut entropy = Entropy::new(block_size);
+        data.chunks(chunk).for_each(|c| entropy.update(c));
+
+        let mut fi = FileInfo::default();
+        entropy.finalize(&mut fi);
+        fi
+    }
+
+    #[test]
+    fn entropy() {
+        // pseudo random bytes
+        let mut x = 1u32;
+        let random: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect();
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(1500);
+
+        let fi = profile(&random, 4096, 1000);
+        assert!(fi.entropy.unwrap() > 7.99);
+        assert_eq!(fi.entropy_class.as_deref(), Some(ENTROPY_ENCRYPTED));
+
+        // same values whatever the chunks
+        let other = profile(&random, 4096, 4096 * 3 + 1);
+        assert_eq!(fi.entropy, other.entropy);
+        assert_eq!(fi.entropy_mean, other.entropy_mean);
+
+        let expected = ::entropy::shannon_entropy(&text) as f64;
+        let fi = profile(&text, 4096, 1000);
+        assert!((fi.entropy.unwrap() - expected).abs() < 1e-4);
+        assert_eq!(fi.entropy_class.as_deref(), Some(ENTROPY_PLAIN));
+
+        // an encrypted blob appended to a document shows up in the blocks, whole data
+        // entropy being low
+        let mixed = [text.clone(), random.clone()].concat();
+        let fi = profile(&mixed, 4096, 1000);
+        assert!(fi.entropy.unwrap() < HIGH_ENTROPY);
+        assert_eq!(fi.entropy_class.as_deref(), Some(ENTROPY_ENCRYPTED));
+        assert!(fi.entropy_min.unwrap() < 4.5);
+        assert!(fi.entropy_max.unwrap() > 7.9);
+        assert!(fi.entropy_stddev.unwrap() > 1.0);
+
+        // skewed high entropy data
+        let compressed: Vec<u8> = random.iter().map(|b| b.saturating_add(20)).collect();
+        let fi = profile(&compressed, 4096, 1000);
+        assert_eq!(fi.entropy_class.as_deref(), Some(ENTROPY_COMPRESSED));
+        let mixed = [text.clone(), compressed, text.clone()].concat();
+        let fi = profile(&mixed, 4096, 1000);
+        assert_eq!(fi.entropy_class.as_deref(), Some(ENTROPY_COMPRESSED));
+
+        // too small blocks: the whole data is classified
+        assert_eq!(
+            profile(&random, 64, 1000).entropy_class.as_deref(),
+            Some(ENTROPY_ENCRYPTED)
+        );
+
+        let fi = profile(b"abc", 4096, 1000);
+        assert_eq!(fi.entropy_min, fi.entropy_max);
+    }
+}
@@ -0,0 +1,156 @@
+// state from previous runs, used to skip files when resuming an interrupted run
+// or when doing an incremental scan
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+
+use crate::{fileinfo::RunHistory, storage::Storage};
+
+// set when Ctrl-C is hit: the walker and workers stop and the run can be resumed later
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Default)]
+pub struct PreviousRuns {
+    // paths already inserted by the run being resumed
+    done: HashSet<String>,
+
+    // path => (len, modified) of the base runs for incremental scans
+    base: HashMap<String, (i64, SystemTime)>,
+}
+
+impl PreviousRuns {
+    // load paths of the run itself if resumed, and of its base runs if incremental
+    pub fn load(
+        storage: &mut dyn Storage,
+        history: &RunHistory,
+        resume: bool,
+    ) -> anyhow::Result<Self> {
+        let mut previous = Self::default();
+
+        if resume {
+            previous.done = storage.artefacts(&history.run_id)?.into_keys().collect();
+            info!(
+                "resuming run {}: {} artefacts already inserted",
+                history.run_id,
+                previous.done.len()
+            );
+        }
+
+        // an incremental run only holds changed files: unchanged ones are found
+        // by following its own base runs, the most recent being kept. A cycle in
+        // the run history stops at the first run seen twice
+        let mut visited = HashSet::from([history.run_id.clone()]);
+        let mut base_run = history.base_run.clone();
+        while let Some(run_id) = base_run {
+            if !visited.insert(run_id.clone()) {
+                warn!(
+                    "loop in the base runs of {}, stopping at {run_id}",
+                    history.run_id
+                );
+                break;
+            }
+            for (path, state) in storage.artefacts(&run_id)? {
+                previous.base.entry(path).or_insert(state);
+            }
+            base_run = storage.history(&run_id)?.and_then(|h| h.base_run);
+        }
+
+        if let Some(base_run) = &history.base_run {
+            info!(
+                "incremental scan based on run {base_run}: {} artefacts",
+                previous.base.len()
+            );
+        }
+
+        Ok(previous)
+    }
+
+    // already inserted by the resumed run
+    pub fn is_done(&self, path: &str) -> bool {
+        self.done.contains(path)
+    }
+
+    // same size and modification time as in the base run
+    pub fn is_unchanged(&self, path: &str, len: i64, modified: SystemTime) -> bool {
+        self.base
+            .get(path)
+            .is_some_and(|(l, m)| *l == len && *m == truncate_micros(modified))
+    }
+}
+
+// databases only keep microseconds
+fn truncate_micros(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => UNIX_EPOCH + Duration::from_micros(d.as_micros() as u64),
+        Err(_) => time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fileinfo::FileInfo, testing::TestDb};
+
+    #[test]
+    fn previous_runs() -> anyhow::Result<()> {
+        let test = TestDb::new()?;
+        let mut storage = test.storage()?;
+
+        let modified = UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789);
+        let artefact = |path: &str, run_id: &str, len: i64| FileInfo {
+            path: path.to_string(),
+            run_id: run_id.to_string(),
+            len,
+            modified,
+            ..Default::default()
+        };
+        let history = |run_id: &str, base_run: Option<&str>| RunHistory {
+            run_id: run_id.to_string(),
+            base_run: base_run.map(str::to_string),
+            ..Default::default()
+        };
+
+        // full run, then an incremental one based on it, being interrupted
+        storage.insert_artefacts(&[artefact("/a", "full", 1), artefact("/b", "full", 2)])?;
+        storage.save_history(&history("full", None))?;
+        storage.insert_artefacts(&[artefact("/c", "incr", 3)])?;
+        storage.save_history(&history("incr", Some("full")))?;
+
+        // resuming it
+        let resumed = history("incr", Some("full"));
+        let previous = PreviousRuns::load(storage.as_mut(), &resumed, true)?;
+        assert!(previous.is_done("/c"));
+        assert!(!previous.is_done("/a"));
+
+        // unchanged files of the base runs, the database keeping microseconds only
+        assert!(previous.is_unchanged("/a", 1, modified));
+        assert!(!previous.is_unchanged("/c", 3, modified));
+        assert!(!previous.is_unchanged("/b", 3, modified));
+        assert!(!previous.is_unchanged("/b", 2, modified + Duration::from_secs(1)));
+
+        // a new incremental run follows the chain of base runs
+        let next = history("next", Some("incr"));
+        let previous = PreviousRuns::load(storage.as_mut(), &next, false)?;
+        assert!(!previous.is_done("/c"));
+        assert!(previous.is_unchanged("/a", 1, modified));
+        assert!(previous.is_unchanged("/c", 3, modified));
+
+        // not incremental
+        let previous = PreviousRuns::load(storage.as_mut(), &history("other", None), false)?;
+        assert!(!previous.is_unchanged("/a", 1, modified));
+
+        // full being based on incr, itself based on full
+        storage.save_history(&history("full", Some("incr")))?;
+        let previous = PreviousRuns::load(storage.as_mut(), &next, false)?;
+        assert!(previous.is_unchanged("/a", 1, modified));
+        Ok(())
+    }
+}
@@ -7,25 +7,106 @@ diesel::table! {
         ext -> Text,
         r#type -> Text,
         len -> BigInt,
-        created -> Timestamp,
+        created -> Nullable<Timestamp>,
         accessed -> Timestamp,
         modified -> Timestamp,
         sha256 -> Text,
         blake3 -> Text,
-        entropy -> Float,
-        mime -> Text,
-        metadata -> Jsonb
+        entropy -> Nullable<Double>,
+        mime -> Nullable<Text>,
+        metadata -> Nullable<Jsonb>,
+        parent -> Nullable<Text>,
+        run_id -> Text,
+        ssdeep -> Nullable<Text>,
+        tlsh -> Nullable<Text>,
+        md5 -> Text,
+        sha1 -> Text,
+        known -> Nullable<Text>,
+        known_label -> Nullable<Text>,
+        entropy_min -> Nullable<Double>,
+        entropy_max -> Nullable<Double>,
+        entropy_mean -> Nullable<Double>,
+        entropy_stddev -> Nullable<Double>,
+        chi_square -> Nullable<Double>,
+        entropy_class -> Nullable<Text>,
+        ext_mismatch -> Nullable<Bool>,
+        mismatch_reason -> Nullable<Text>,
+        inode -> Nullable<BigInt>,
+        dev -> Nullable<BigInt>,
+        uid -> Nullable<BigInt>,
+        gid -> Nullable<BigInt>,
+        user_name -> Nullable<Text>,
+        group_name -> Nullable<Text>,
+        mode -> Nullable<Integer>,
+        permissions -> Nullable<Text>,
+        nlink -> Nullable<BigInt>,
+        changed -> Nullable<Timestamp>,
+        xattrs -> Nullable<Jsonb>,
+        link_target -> Nullable<Text>,
+        link_resolved -> Nullable<Text>,
+        link_dangling -> Nullable<Bool>,
+        hardlink_of -> Nullable<Text>,
+        created_ns -> Nullable<BigInt>,
+        accessed_ns -> Nullable<BigInt>,
+        modified_ns -> Nullable<BigInt>,
+        changed_ns -> Nullable<BigInt>,
+        allocation -> Nullable<Text>,
+        extents -> Nullable<Jsonb>,
+        deleted -> Nullable<Timestamp>,
+        sequence -> Nullable<Integer>,
+        fn_created -> Nullable<Timestamp>,
+        fn_modified -> Nullable<Timestamp>,
+        fn_accessed -> Nullable<Timestamp>,
+        fn_changed -> Nullable<Timestamp>
     }
 }
 
 // run history
 diesel::table! {
-    run_history (start_time) {
+    run_history (run_id) {
+        run_id -> Text,
+        case_name -> Nullable<Text>,
         start_time -> Timestamp,
         end_time -> Timestamp,
         elapsed -> Text,
         nb_files -> BigInt,
         args -> Text,
-        tags -> Text
+        tags -> Text,
+        status -> Text,
+        source -> Text,
+        base_run -> Nullable<Text>,
+        examiner -> Nullable<Text>,
+        case_number -> Nullable<Text>,
+        acquired -> Nullable<Timestamp>,
+        image_md5 -> Nullable<Text>,
+        image_sha1 -> Nullable<Text>,
+        verified -> Nullable<Bool>
+    }
+}
+
+// known-good and known-bad hashes
+diesel::table! {
+    hash_set (hash) {
+        hash -> Text,
+        kind -> Text,
+        label -> Nullable<Text>,
+        source -> Text
+    }
+}
+
+// partitions found in disk images
+diesel::table! {
+    partition (run_id, number) {
+        run_id -> Text,
+        image -> Text,
+        number -> Integer,
+        scheme -> Text,
+        start -> BigInt,
+        len -> BigInt,
+        type_id -> Text,
+        type_name -> Nullable<Text>,
+        label -> Nullable<Text>,
+        guid -> Nullable<Text>,
+        filesystem -> Nullable<Text>
     }
 }
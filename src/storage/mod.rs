@@ -0,0 +1,266 @@
+// storage abstraction: artefacts and run history can be saved either into a
+// PostgreSQL database or into a local SQLite file
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::anyhow;
+use diesel::{
+    r2d2::{ConnectionManager, Pool},
+    PgConnection,
+};
+use log::error;
+
+use crate::{
+    fileinfo::{FileInfo, KnownHash, Partition, RunHistory},
+    pool::establish_pool,
+};
+
+pub mod postgres;
+pub mod sqlite;
+
+// what a backend has to provide. Each worker thread gets its own storage
+pub trait Storage: Send {
+    // delete all artefacts
+    fn clear(&mut self) -> anyhow::Result<()>;
+
+    // save a batch of artefacts
+    fn insert_artefacts(&mut self, rows: &[FileInfo]) -> anyhow::Result<()>;
+
+    // save the run history: inserted at start, updated at the end
+    fn save_history(&mut self, history: &RunHistory) -> anyhow::Result<()>;
+
+    // get the history of a previous run
+    fn history(&mut self, run_id: &str) -> anyhow::Result<Option<RunHistory>>;
+
+    // get the last completed run for the same source directory
+    fn last_history(&mut self, source: &str) -> anyhow::Result<Option<RunHistory>>;
+
+    // path => (len, modified) of all artefacts of a run
+    fn artefacts(&mut self, run_id: &str) -> anyhow::Result<HashMap<String, (i64, SystemTime)>>;
+
+    // all artefacts having a fuzzy hash
+    fn fuzzy_hashes(&mut self) -> anyhow::Result<Vec<FuzzyHashes>>;
+
+    // delete hashes loaded from a hash set file
+    fn clear_hash_set(&mut self, source: &str) -> anyhow::Result<()>;
+
+    // save a chunk of known hashes
+    fn insert_hash_set(&mut self, rows: &[KnownHash]) -> anyhow::Result<()>;
+
+    // whether some hash sets have been loaded
+    fn has_hash_sets(&mut self) -> anyhow::Result<bool>;
+
+    // kind and label of the first known hash found, known-bad ones first
+    fn known_hash(&mut self, hashes: &[&str]) -> anyhow::Result<Option<(String, Option<String>)>>;
+
+    // save the partitions of a disk image, replacing those of a resumed run
+    fn save_partitions(&mut self, run_id: &str, rows: &[Partition]) -> anyhow::Result<()>;
+}
+
+// path, ssdeep and TLSH hashes
+pub type FuzzyHashes = (String, Option<String>, Option<String>);
+
+// backend selected from the output URL
+pub enum Backend {
+    // copy is set to use COPY ... FROM STDIN (FORMAT binary) instead of INSERT
+    Postgres {
+        pool: Pool<ConnectionManager<PgConnection>>,
+        copy: bool,
+    },
+    Sqlite(PathBuf),
+}
+
+impl Backend {
+    // URL is either postgres://... or sqlite:path
+    pub fn new(url: &str, nb_conn: u32, copy: bool) -> anyhow::Result<Self> {
+        if let Some(path) = url.strip_prefix("sqlite:") {
+            let path = PathBuf::from(path.trim_start_matches("//"));
+
+            // tables are created if not existing
+            sqlite::SqliteStorage::create_tables(&path)?;
+            Ok(Self::Sqlite(path))
+        } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Ok(Self::Postgres {
+                pool: establish_pool(url, nb_conn),
+                copy,
+            })
+        } else {
+            Err(anyhow!("unsupported output '{url}'"))
+        }
+    }
+
+    // get a new storage, e.g. for a thread
+    pub fn connect(&self) -> anyhow::Result<Box<dyn Storage>> {
+        match self {
+            Self::Postgres { pool, copy } => {
+                Ok(Box::new(postgres::PgStorage::new(pool.get()?, *copy)))
+            }
+            Self::Sqlite(path) => Ok(Box::new(sqlite::SqliteStorage::open(path)?)),
+        }
+    }
+}
+
+// rows are buffered and inserted by batches, either when the buffer is full
+// or when rows have been waiting for too long
+pub struct Batch<'a> {
+    storage: &'a mut dyn Storage,
+    rows: Vec<FileInfo>,
+    size: usize,
+    interval: Duration,
+    last_flush: Instant,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(storage: &'a mut dyn Storage, size: usize, interval: Duration) -> Self {
+        Self {
+            storage,
+            rows: Vec::with_capacity(size),
+            size: size.max(1),
+            interval,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, fi: FileInfo) -> anyhow::Result<()> {
+        self.rows.push(fi);
+
+        if self.rows.len() >= self.size || self.last_flush.elapsed() >= self.interval {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    // the underlying storage, e.g. to look up hash sets
+    pub fn storage(&mut self) -> &mut dyn Storage {
+        self.storage
+    }
+
+    // insert all buffered rows
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if !self.rows.is_empty() {
+            // rows are lost anyway if insertion fails
+            let result = self.storage.insert_artefacts(&self.rows);
+            self.rows.clear();
+            result?;
+        }
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+// rows still buffered, e.g. when a worker returns early, are not lost
+impl Drop for Batch<'_> {
+    fn drop(&mut self) {
+        let count = self.rows.len();
+        if let Err(e) = self.flush() {
+            error!("unable to insert {count} buffered artefacts: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDb;
+
+    #[test]
+    fn sqlite() -> anyhow::Result<()> {
+        let test = TestDb::new()?;
+
+        let fi = FileInfo {
+            path: "/tmp/foo.png".to_string(),
+            mime: Some("png"),
+            metadata: Some(serde_json::json!({"width": 32})),
+            ..Default::default()
+        };
+        let mut storage = test.storage()?;
+        storage.insert_artefacts(&[fi])?;
+        storage.save_history(&RunHistory::default())?;
+
+        let conn = test.conn()?;
+        let mut stmt = conn.prepare("SELECT path, mime, metadata, created FROM artefact")?;
+        assert_eq!(stmt.next()?, ::sqlite::State::Row);
+        assert_eq!(stmt.read::<String, _>(0)?, "/tmp/foo.png");
+        assert_eq!(stmt.read::<String, _>(1)?, "png");
+        assert_eq!(stmt.read::<String, _>(2)?, r#"{"width":32}"#);
+        assert_eq!(stmt.read::<Option<String>, _>(3)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_migration() -> anyhow::Result<()> {
+        let test = TestDb::new()?;
+
+        // tables as created by the first version of the SQLite storage
+        let conn = test.conn()?;
+        conn.execute(
+            "CREATE TABLE artefact (id integer, path text, name text, ext text, type text, \
+            len integer, created text, accessed text, modified text, sha256 text, blake3 text, \
+            entropy real, mime text, metadata text, parent text); \
+            CREATE TABLE run_history (start_time text, end_time text, elapsed text, \
+            nb_files integer, args text, tags text); \
+            INSERT INTO artefact (path) VALUES ('/tmp/old');",
+        )?;
+        drop(conn);
+
+        let mut storage = test.storage()?;
+        let fi = FileInfo {
+            path: "/tmp/new".to_string(),
+            run_id: "run".to_string(),
+            sequence: Some(1),
+            ..Default::default()
+        };
+        storage.insert_artefacts(&[fi])?;
+        let history = RunHistory {
+            run_id: "run".to_string(),
+            ..Default::default()
+        };
+        storage.save_history(&history)?;
+        storage.save_history(&history)?;
+        drop(storage);
+
+        // opening an up-to-date database again is a no-op
+        test.backend()?;
+
+        let conn = test.conn()?;
+        let mut stmt = conn.prepare("SELECT path, run_id FROM artefact ORDER BY path")?;
+        assert_eq!(stmt.next()?, ::sqlite::State::Row);
+        assert_eq!(stmt.read::<String, _>(1)?, "run");
+        assert_eq!(stmt.next()?, ::sqlite::State::Row);
+        assert_eq!(stmt.read::<String, _>(0)?, "/tmp/old");
+        assert_eq!(stmt.read::<Option<String>, _>(1)?, None);
+        let mut stmt = conn.prepare("SELECT count(*) FROM run_history")?;
+        stmt.next()?;
+        assert_eq!(stmt.read::<i64, _>(0)?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn batch() -> anyhow::Result<()> {
+        let test = TestDb::new()?;
+        let mut storage = test.storage()?;
+        let count = || -> anyhow::Result<i64> {
+            let conn = test.conn()?;
+            let mut stmt = conn.prepare("SELECT count(*) FROM artefact")?;
+            stmt.next()?;
+            Ok(stmt.read(0)?)
+        };
+
+        let mut batch = Batch::new(storage.as_mut(), 3, Duration::from_secs(3600));
+        for _ in 0..4 {
+            batch.push(FileInfo::default())?;
+        }
+        assert_eq!(count()?, 3);
+        drop(batch);
+        assert_eq!(count()?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported() {
+        assert!(Backend::new("mysql://localhost/iaa", 1, false).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+// PostgreSQL storage using diesel
+use std::{collections::HashMap, time::SystemTime};
+
+use diesel::{
+    pg::Pg,
+    query_builder::QueryFragment,
+    r2d2::{ConnectionManager, PooledConnection},
+    BoolExpressionMethods, ExecuteCopyFromDsl, ExpressionMethods, OptionalExtension, PgConnection,
+    QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+use crate::{
+    fileinfo::{FileInfo, KnownHash, Partition, RunHistory, RUN_COMPLETED},
+    schema::{
+        artefact, artefact::dsl::artefact as artefacts, hash_set,
+        hash_set::dsl::hash_set as hash_sets, partition, partition::dsl::partition as partitions,
+        run_history, run_history::dsl::run_history as histories,
+    },
+    storage::{FuzzyHashes, Storage},
+};
+
+// Postgres limits the number of bind parameters of a single statement
+const MAX_BIND_PARAMS: usize = 65535;
+
+// rows inserted by a single statement, each one binding at most a parameter per column
+fn max_rows(columns: impl QueryFragment<Pg>) -> usize {
+    // e.g. "artefact"."id", "artefact"."path" -- binds: []
+    let sql = diesel::debug_query::<Pg, _>(&columns).to_string();
+    let count = sql
+        .split(" -- ")
+        .next()
+        .unwrap_or_default()
+        .split(", ")
+        .count();
+    MAX_BIND_PARAMS / count
+}
+
+pub struct PgStorage {
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+
+    // use COPY ... FROM STDIN (FORMAT binary) instead of INSERT
+    copy: bool,
+}
+
+impl PgStorage {
+    pub fn new(conn: PooledConnection<ConnectionManager<PgConnection>>, copy: bool) -> Self {
+        Self { conn, copy }
+    }
+}
+
+impl Storage for PgStorage {
+    fn clear(&mut self) -> anyhow::Result<()> {
+        diesel::delete(artefacts).execute(&mut self.conn)?;
+        Ok(())
+    }
+
+    fn insert_artefacts(&mut self, rows: &[FileInfo]) -> anyhow::Result<()> {
+        if self.copy {
+            diesel::copy_from(artefacts)
+                .from_insertable(rows)
+                .execute(&mut self.conn)?;
+        } else {
+            for chunk in rows.chunks(max_rows(artefact::all_columns)) {
+                diesel::insert_into(artefacts)
+                    .values(chunk)
+                    .execute(&mut self.conn)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save_history(&mut self, history: &RunHistory) -> anyhow::Result<()> {
+        diesel::insert_into(histories)
+            .values(history)
+            .on_conflict(run_history::run_id)
+            .do_update()
+            .set(history)
+            .execute(&mut self.conn)?;
+        Ok(())
+    }
+
+    fn history(&mut self, run_id: &str) -> anyhow::Result<Option<RunHistory>> {
+        let history = histories
+            .filter(run_history::run_id.eq(run_id))
+            .select(RunHistory::as_select())
+            .first(&mut self.conn)
+            .optional()?;
+        Ok(history)
+    }
+
+    fn last_history(&mut self, source: &str) -> anyhow::Result<Option<RunHistory>> {
+        let history = histories
+            .filter(run_history::source.eq(source))
+            .filter(run_history::status.eq(RUN_COMPLETED))
+            .order(run_history::start_time.desc())
+            .select(RunHistory::as_select())
+            .first(&mut self.conn)
+            .optional()?;
+        Ok(history)
+    }
+
+    fn artefacts(&mut self, run_id: &str) -> anyhow::Result<HashMap<String, (i64, SystemTime)>> {
+        let rows: Vec<(String, i64, SystemTime)> = artefacts
+            .filter(artefact::run_id.eq(run_id))
+            .select((artefact::path, artefact::len, artefact::modified))
+            .load(&mut self.conn)?;
+        Ok(rows.into_iter().map(|(p, l, m)| (p, (l, m))).collect())
+    }
+
+    fn fuzzy_hashes(&mut self) -> anyhow::Result<Vec<FuzzyHashes>> {
+        let rows = artefacts
+            .filter(
+                artefact::ssdeep
+                    .is_not_null()
+                    .or(artefact::tlsh.is_not_null()),
+            )
+            .select((artefact::path, artefact::ssdeep, artefact::tlsh))
+            .load(&mut self.conn)?;
+        Ok(rows)
+    }
+
+    fn clear_hash_set(&mut self, source: &str) -> anyhow::Result<()> {
+        diesel::delete(hash_sets.filter(hash_set::source.eq(source))).execute(&mut self.conn)?;
+        Ok(())
+    }
+
+    fn insert_hash_set(&mut self, rows: &[KnownHash]) -> anyhow::Result<()> {
+        if self.copy {
+            diesel::copy_from(hash_sets)
+                .from_insertable(rows)
+                .execute(&mut self.conn)?;
+        } else {
+            for chunk in rows.chunks(max_rows(hash_set::all_columns)) {
+                diesel::insert_into(hash_sets)
+                    .values(chunk)
+                    .execute(&mut self.conn)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn has_hash_sets(&mut self) -> anyhow::Result<bool> {
+        let found = diesel::select(diesel::dsl::exists(hash_sets.select(hash_set::hash)))
+            .get_result(&mut self.conn)?;
+        Ok(found)
+    }
+
+    fn known_hash(&mut self, hashes: &[&str]) -> anyhow::Result<Option<(String, Option<String>)>> {
+        // "bad" sorts before "good"
+        let found = hash_sets
+            .filter(hash_set::hash.eq_any(hashes))
+            .order(hash_set::kind.asc())
+            .select((hash_set::kind, hash_set::label))
+            .first(&mut self.conn)
+            .optional()?;
+        Ok(found)
+    }
+
+    fn save_partitions(&mut self, run_id: &str, rows: &[Partition]) -> anyhow::Result<()> {
+        diesel::delete(partitions.filter(partition::run_id.eq(run_id))).execute(&mut self.conn)?;
+        diesel::insert_into(partitions)
+            .values(rows)
+            .execute(&mut self.conn)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_params() {
+        let rows = max_rows(artefact::all_columns);
+        assert!(rows * 50 < MAX_BIND_PARAMS);
+        assert_eq!(
+            max_rows((artefact::path, artefact::name)),
+            MAX_BIND_PARAMS / 2
+        );
+    }
+}
@@ -0,0 +1,564 @@
+// local SQLite storage, for when no PostgreSQL server is available
+use std::{collections::HashMap, path::Path, time::SystemTime};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlite::{Connection, State, Statement, Value};
+
+use crate::{
+    fileinfo::{FileInfo, KnownHash, Partition, RunHistory, RUN_COMPLETED},
+    storage::{FuzzyHashes, Storage},
+};
+
+// same tables as scripts/create_all.sql, using SQLite types
+const CREATE_TABLES: &str = r#"
+CREATE TABLE IF NOT EXISTS artefact (
+    id integer,
+    path text,
+    name text,
+    ext text,
+    type text,
+    len integer,
+    created text,
+    accessed text,
+    modified text,
+    sha256 text,
+    blake3 text,
+    entropy real,
+    mime text,
+    metadata text,
+    parent text,
+    run_id text,
+    ssdeep text,
+    tlsh text,
+    md5 text,
+    sha1 text,
+    known text,
+    known_label text,
+    entropy_min real,
+    entropy_max real,
+    entropy_mean real,
+    entropy_stddev real,
+    chi_square real,
+    entropy_class text,
+    ext_mismatch integer,
+    mismatch_reason text,
+    inode integer,
+    dev integer,
+    uid integer,
+    gid integer,
+    user_name text,
+    group_name text,
+    mode integer,
+    permissions text,
+    nlink integer,
+    changed text,
+    xattrs text,
+    link_target text,
+    link_resolved text,
+    link_dangling integer,
+    hardlink_of text,
+    created_ns integer,
+    accessed_ns integer,
+    modified_ns integer,
+    changed_ns integer,
+    allocation text,
+    extents text,
+    deleted text,
+    sequence integer,
+    fn_created text,
+    fn_modified text,
+    fn_accessed text,
+    fn_changed text
+);
+
+CREATE TABLE IF NOT EXISTS hash_set (
+    hash text,
+    kind text,
+    label text,
+    source text
+);
+
+CREATE TABLE IF NOT EXISTS run_history (
+    run_id text PRIMARY KEY,
+    case_name text,
+    start_time text,
+    end_time text,
+    elapsed text,
+    nb_files integer,
+    args text,
+    tags text,
+    status text,
+    source text,
+    base_run text,
+    examiner text,
+    case_number text,
+    acquired text,
+    image_md5 text,
+    image_sha1 text,
+    verified integer
+);
+
+CREATE TABLE IF NOT EXISTS partition (
+    run_id text,
+    image text,
+    number integer,
+    scheme text,
+    start integer,
+    len integer,
+    type_id text,
+    type_name text,
+    label text,
+    guid text,
+    filesystem text
+);
+"#;
+
+// created once the columns are there, run_history.run_id being only a primary key for
+// databases created by this version
+const CREATE_INDEXES: &str = r#"
+CREATE INDEX IF NOT EXISTS artefact_run_id ON artefact (run_id);
+CREATE INDEX IF NOT EXISTS hash_set_hash ON hash_set (hash);
+CREATE UNIQUE INDEX IF NOT EXISTS run_history_run_id ON run_history (run_id);
+"#;
+
+// columns added to the first version of the tables: databases created by an earlier
+// version get the missing ones, user_version then being set to the number of entries.
+// Append new columns here as well as in CREATE_TABLES
+const MIGRATIONS: &[(&str, &str)] = &[
+    ("artefact", "run_id text"),
+    ("artefact", "ssdeep text"),
+    ("artefact", "tlsh text"),
+    ("artefact", "md5 text"),
+    ("artefact", "sha1 text"),
+    ("artefact", "known text"),
+    ("artefact", "known_label text"),
+    ("artefact", "entropy_min real"),
+    ("artefact", "entropy_max real"),
+    ("artefact", "entropy_mean real"),
+    ("artefact", "entropy_stddev real"),
+    ("artefact", "chi_square real"),
+    ("artefact", "entropy_class text"),
+    ("artefact", "ext_mismatch integer"),
+    ("artefact", "mismatch_reason text"),
+    ("artefact", "inode integer"),
+    ("artefact", "dev integer"),
+    ("artefact", "uid integer"),
+    ("artefact", "gid integer"),
+    ("artefact", "user_name text"),
+    ("artefact", "group_name text"),
+    ("artefact", "mode integer"),
+    ("artefact", "permissions text"),
+    ("artefact", "nlink integer"),
+    ("artefact", "changed text"),
+    ("artefact", "xattrs text"),
+    ("artefact", "link_target text"),
+    ("artefact", "link_resolved text"),
+    ("artefact", "link_dangling integer"),
+    ("artefact", "hardlink_of text"),
+    ("artefact", "created_ns integer"),
+    ("artefact", "accessed_ns integer"),
+    ("artefact", "modified_ns integer"),
+    ("artefact", "changed_ns integer"),
+    ("artefact", "allocation text"),
+    ("artefact", "extents text"),
+    ("artefact", "deleted text"),
+    ("artefact", "sequence integer"),
+    ("artefact", "fn_created text"),
+    ("artefact", "fn_modified text"),
+    ("artefact", "fn_accessed text"),
+    ("artefact", "fn_changed text"),
+    ("run_history", "run_id text"),
+    ("run_history", "case_name text"),
+    ("run_history", "status text"),
+    ("run_history", "source text"),
+    ("run_history", "base_run text"),
+    ("run_history", "examiner text"),
+    ("run_history", "case_number text"),
+    ("run_history", "acquired text"),
+    ("run_history", "image_md5 text"),
+    ("run_history", "image_sha1 text"),
+    ("run_history", "verified integer"),
+];
+
+const INSERT_ARTEFACT: &str = "INSERT INTO artefact \
+    (path, name, ext, type, len, created, accessed, modified, sha256, blake3, entropy, mime, metadata, parent, run_id, ssdeep, tlsh, md5, sha1, known, known_label, entropy_min, entropy_max, entropy_mean, entropy_stddev, chi_square, entropy_class, ext_mismatch, mismatch_reason, inode, dev, uid, gid, user_name, group_name, mode, permissions, nlink, changed, xattrs, link_target, link_resolved, link_dangling, hardlink_of, created_ns, accessed_ns, modified_ns, changed_ns, allocation, extents, deleted, sequence, fn_created, fn_modified, fn_accessed, fn_changed) \
+    VALUES (:path, :name, :ext, :type, :len, :created, :accessed, :modified, :sha256, :blake3, :entropy, :mime, :metadata, :parent, :run_id, :ssdeep, :tlsh, :md5, :sha1, :known, :known_label, :entropy_min, :entropy_max, :entropy_mean, :entropy_stddev, :chi_square, :entropy_class, :ext_mismatch, :mismatch_reason, :inode, :dev, :uid, :gid, :user_name, :group_name, :mode, :permissions, :nlink, :changed, :xattrs, :link_target, :link_resolved, :link_dangling, :hardlink_of, :created_ns, :accessed_ns, :modified_ns, :changed_ns, :allocation, :extents, :deleted, :sequence, :fn_created, :fn_modified, :fn_accessed, :fn_changed)";
+
+const INSERT_HASH_SET: &str =
+    "INSERT INTO hash_set (hash, kind, label, source) VALUES (:hash, :kind, :label, :source)";
+
+const INSERT_PARTITION: &str = "INSERT INTO partition \
+    (run_id, image, number, scheme, start, len, type_id, type_name, label, guid, filesystem) \
+    VALUES (:run_id, :image, :number, :scheme, :start, :len, :type_id, :type_name, :label, :guid, :filesystem)";
+
+const HISTORY_COLUMNS: &str = "run_id, case_name, start_time, end_time, elapsed, nb_files, args, tags, status, source, base_run, \
+    examiner, case_number, acquired, image_md5, image_sha1, verified";
+
+const UPSERT_HISTORY: &str = "INSERT INTO run_history \
+    (run_id, case_name, start_time, end_time, elapsed, nb_files, args, tags, status, source, base_run, examiner, case_number, acquired, image_md5, image_sha1, verified) \
+    VALUES (:run_id, :case_name, :start_time, :end_time, :elapsed, :nb_files, :args, :tags, :status, :source, :base_run, :examiner, :case_number, :acquired, :image_md5, :image_sha1, :verified) \
+    ON CONFLICT (run_id) DO UPDATE SET \
+    case_name = excluded.case_name, start_time = excluded.start_time, end_time = excluded.end_time, \
+    elapsed = excluded.elapsed, nb_files = excluded.nb_files, args = excluded.args, tags = excluded.tags, \
+    status = excluded.status, source = excluded.source, base_run = excluded.base_run, \
+    examiner = excluded.examiner, case_number = excluded.case_number, acquired = excluded.acquired, \
+    image_md5 = excluded.image_md5, image_sha1 = excluded.image_sha1, verified = excluded.verified";
+
+// several threads write into the same file: wait for the lock rather than failing
+const BUSY_TIMEOUT_MS: usize = 60_000;
+
+pub struct SqliteStorage(Connection);
+
+impl SqliteStorage {
+    // create the database file and tables if not existing
+    pub fn create_tables(path: &Path) -> anyhow::Result<()> {
+        let conn = Connection::open(path)?;
+
+        // WAL mode is persistent and allows concurrent readers during a scan
+        conn.execute("PRAGMA journal_mode = WAL")?;
+        conn.execute(CREATE_TABLES)?;
+        migrate(&conn)?;
+        conn.execute(CREATE_INDEXES)?;
+        Ok(())
+    }
+
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut conn = Connection::open(path)?;
+        conn.set_busy_timeout(BUSY_TIMEOUT_MS)?;
+        Ok(Self(conn))
+    }
+
+    // a single transaction per batch is much faster
+    fn transaction(&self, f: impl FnOnce(&Self) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        self.0.execute("BEGIN")?;
+        match f(self) {
+            Ok(()) => self.0.execute("COMMIT")?,
+            Err(e) => {
+                self.0.execute("ROLLBACK")?;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    // statement is prepared once for all rows
+    fn insert_rows(&self, rows: &[FileInfo]) -> anyhow::Result<()> {
+        let mut stmt = self.0.prepare(INSERT_ARTEFACT)?;
+        for fi in rows {
+            stmt.reset()?;
+            stmt.bind::<&[(_, Value)]>(&[
+                (":path", fi.path.as_str().into()),
+                (":name", fi.name.as_str().into()),
+                (":ext", fi.ext.as_str().into()),
+                (":type", fi.r#type.as_str().into()),
+                (":len", fi.len.into()),
+                (":created", optional(fi.created.as_ref().map(timestamp))),
+                (":accessed", timestamp(&fi.accessed).into()),
+                (":modified", timestamp(&fi.modified).into()),
+                (":sha256", fi.sha256.as_str().into()),
+                (":blake3", fi.blake3.as_str().into()),
+                (":entropy", optional(fi.entropy)),
+                (":mime", optional(fi.mime)),
+                (
+                    ":metadata",
+                    optional(fi.metadata.as_ref().map(|m| m.to_string())),
+                ),
+                (":parent", optional(fi.parent.as_deref())),
+                (":run_id", fi.run_id.as_str().into()),
+                (":ssdeep", optional(fi.ssdeep.as_deref())),
+                (":tlsh", optional(fi.tlsh.as_deref())),
+                (":md5", fi.md5.as_str().into()),
+                (":sha1", fi.sha1.as_str().into()),
+                (":known", optional(fi.known.as_deref())),
+                (":known_label", optional(fi.known_label.as_deref())),
+                (":entropy_min", optional(fi.entropy_min)),
+                (":entropy_max", optional(fi.entropy_max)),
+                (":entropy_mean", optional(fi.entropy_mean)),
+                (":entropy_stddev", optional(fi.entropy_stddev)),
+                (":chi_square", optional(fi.chi_square)),
+                (":entropy_class", optional(fi.entropy_class.as_deref())),
+                (":ext_mismatch", optional(fi.ext_mismatch.map(i64::from))),
+                (":mismatch_reason", optional(fi.mismatch_reason.as_deref())),
+                (":inode", optional(fi.inode)),
+                (":dev", optional(fi.dev)),
+                (":uid", optional(fi.uid)),
+                (":gid", optional(fi.gid)),
+                (":user_name", optional(fi.user_name.as_deref())),
+                (":group_name", optional(fi.group_name.as_deref())),
+                (":mode", optional(fi.mode.map(i64::from))),
+                (":permissions", optional(fi.permissions.as_deref())),
+                (":nlink", optional(fi.nlink)),
+                (":changed", optional(fi.changed.as_ref().map(timestamp))),
+                (
+                    ":xattrs",
+                    optional(fi.xattrs.as_ref().map(|x| x.to_string())),
+                ),
+                (":link_target", optional(fi.link_target.as_deref())),
+                (":link_resolved", optional(fi.link_resolved.as_deref())),
+                (":link_dangling", optional(fi.link_dangling.map(i64::from))),
+                (":hardlink_of", optional(fi.hardlink_of.as_deref())),
+                (":created_ns", optional(fi.created_ns)),
+                (":accessed_ns", optional(fi.accessed_ns)),
+                (":modified_ns", optional(fi.modified_ns)),
+                (":changed_ns", optional(fi.changed_ns)),
+                (":allocation", optional(fi.allocation.as_deref())),
+                (
+                    ":extents",
+                    optional(fi.extents.as_ref().map(|e| e.to_string())),
+                ),
+                (":deleted", optional(fi.deleted.as_ref().map(timestamp))),
+                (":sequence", optional(fi.sequence.map(i64::from))),
+                (
+                    ":fn_created",
+                    optional(fi.fn_created.as_ref().map(timestamp)),
+                ),
+                (
+                    ":fn_modified",
+                    optional(fi.fn_modified.as_ref().map(timestamp)),
+                ),
+                (
+                    ":fn_accessed",
+                    optional(fi.fn_accessed.as_ref().map(timestamp)),
+                ),
+                (
+                    ":fn_changed",
+                    optional(fi.fn_changed.as_ref().map(timestamp)),
+                ),
+            ])?;
+            stmt.next()?;
+        }
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn clear(&mut self) -> anyhow::Result<()> {
+        self.0.execute("DELETE FROM artefact")?;
+        Ok(())
+    }
+
+    fn insert_artefacts(&mut self, rows: &[FileInfo]) -> anyhow::Result<()> {
+        self.transaction(|s| s.insert_rows(rows))
+    }
+
+    fn save_history(&mut self, history: &RunHistory) -> anyhow::Result<()> {
+        let mut stmt = self.0.prepare(UPSERT_HISTORY)?;
+        stmt.bind::<&[(_, Value)]>(&[
+            (":run_id", history.run_id.as_str().into()),
+            (":case_name", optional(history.case_name.as_deref())),
+            (":start_time", timestamp(&history.start_time).into()),
+            (":end_time", timestamp(&history.end_time).into()),
+            (":elapsed", history.elapsed.as_str().into()),
+            (":nb_files", history.nb_files.into()),
+            (":args", history.args.as_str().into()),
+            (":tags", history.tags.as_str().into()),
+            (":status", history.status.as_str().into()),
+            (":source", history.source.as_str().into()),
+            (":base_run", optional(history.base_run.as_deref())),
+            (":examiner", optional(history.examiner.as_deref())),
+            (":case_number", optional(history.case_number.as_deref())),
+            (
+                ":acquired",
+                optional(history.acquired.as_ref().map(timestamp)),
+            ),
+            (":image_md5", optional(history.image_md5.as_deref())),
+            (":image_sha1", optional(history.image_sha1.as_deref())),
+            (":verified", optional(history.verified.map(i64::from))),
+        ])?;
+        stmt.next()?;
+        Ok(())
+    }
+
+    fn history(&mut self, run_id: &str) -> anyhow::Result<Option<RunHistory>> {
+        let query = format!("SELECT {HISTORY_COLUMNS} FROM run_history WHERE run_id = ?");
+        let mut stmt = self.0.prepare(query)?;
+        stmt.bind((1, run_id))?;
+        read_history(&mut stmt)
+    }
+
+    fn last_history(&mut self, source: &str) -> anyhow::Result<Option<RunHistory>> {
+        let query = format!(
+            "SELECT {HISTORY_COLUMNS} FROM run_history WHERE source = ? AND status = ? \
+            ORDER BY start_time DESC LIMIT 1"
+        );
+        let mut stmt = self.0.prepare(query)?;
+        stmt.bind(&[source, RUN_COMPLETED][..])?;
+        read_history(&mut stmt)
+    }
+
+    fn artefacts(&mut self, run_id: &str) -> anyhow::Result<HashMap<String, (i64, SystemTime)>> {
+        let mut stmt = self
+            .0
+            .prepare("SELECT path, len, modified FROM artefact WHERE run_id = ?")?;
+        stmt.bind((1, run_id))?;
+
+        let mut artefacts = HashMap::new();
+        while let State::Row = stmt.next()? {
+            let modified = parse_timestamp(&stmt.read::<String, _>(2)?)?;
+            artefacts.insert(
+                stmt.read::<String, _>(0)?,
+                (stmt.read::<i64, _>(1)?, modified),
+            );
+        }
+        Ok(artefacts)
+    }
+
+    fn fuzzy_hashes(&mut self) -> anyhow::Result<Vec<FuzzyHashes>> {
+        let mut stmt = self.0.prepare(
+            "SELECT path, ssdeep, tlsh FROM artefact WHERE ssdeep IS NOT NULL OR tlsh IS NOT NULL",
+        )?;
+
+        let mut hashes = Vec::new();
+        while let State::Row = stmt.next()? {
+            hashes.push((stmt.read(0)?, stmt.read(1)?, stmt.read(2)?));
+        }
+        Ok(hashes)
+    }
+
+    fn clear_hash_set(&mut self, source: &str) -> anyhow::Result<()> {
+        let mut stmt = self.0.prepare("DELETE FROM hash_set WHERE source = ?")?;
+        stmt.bind((1, source))?;
+        stmt.next()?;
+        Ok(())
+    }
+
+    fn insert_hash_set(&mut self, rows: &[KnownHash]) -> anyhow::Result<()> {
+        self.transaction(|s| {
+            let mut stmt = s.0.prepare(INSERT_HASH_SET)?;
+            for row in rows {
+                stmt.reset()?;
+                stmt.bind::<&[(_, Value)]>(&[
+                    (":hash", row.hash.as_str().into()),
+                    (":kind", row.kind.into()),
+                    (":label", optional(row.label.as_deref())),
+                    (":source", row.source.as_str().into()),
+                ])?;
+                stmt.next()?;
+            }
+            Ok(())
+        })
+    }
+
+    fn has_hash_sets(&mut self) -> anyhow::Result<bool> {
+        let mut stmt = self.0.prepare("SELECT 1 FROM hash_set LIMIT 1")?;
+        Ok(stmt.next()? == State::Row)
+    }
+
+    fn known_hash(&mut self, hashes: &[&str]) -> anyhow::Result<Option<(String, Option<String>)>> {
+        let params = vec!["?"; hashes.len()].join(", ");
+        let query = format!(
+            "SELECT kind, label FROM hash_set WHERE hash IN ({params}) ORDER BY kind LIMIT 1"
+        );
+        let mut stmt = self.0.prepare(query)?;
+        stmt.bind(hashes)?;
+
+        if stmt.next()? != State::Row {
+            return Ok(None);
+        }
+        Ok(Some((stmt.read(0)?, stmt.read(1)?)))
+    }
+
+    fn save_partitions(&mut self, run_id: &str, rows: &[Partition]) -> anyhow::Result<()> {
+        self.transaction(|s| {
+            let mut stmt = s.0.prepare("DELETE FROM partition WHERE run_id = ?")?;
+            stmt.bind((1, run_id))?;
+            stmt.next()?;
+
+            let mut stmt = s.0.prepare(INSERT_PARTITION)?;
+            for row in rows {
+                stmt.reset()?;
+                stmt.bind::<&[(_, Value)]>(&[
+                    (":run_id", row.run_id.as_str().into()),
+                    (":image", row.image.as_str().into()),
+                    (":number", i64::from(row.number).into()),
+                    (":scheme", row.scheme.as_str().into()),
+                    (":start", row.start.into()),
+                    (":len", row.len.into()),
+                    (":type_id", row.type_id.as_str().into()),
+                    (":type_name", optional(row.type_name.as_deref())),
+                    (":label", optional(row.label.as_deref())),
+                    (":guid", optional(row.guid.as_deref())),
+                    (":filesystem", optional(row.filesystem.as_deref())),
+                ])?;
+                stmt.next()?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// add the columns missing from tables created by an earlier version
+fn migrate(conn: &Connection) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare("PRAGMA user_version")?;
+    stmt.next()?;
+    if stmt.read::<i64, _>(0)? >= MIGRATIONS.len() as i64 {
+        return Ok(());
+    }
+
+    let mut columns: HashMap<&str, Vec<String>> = HashMap::new();
+    for (table, column) in MIGRATIONS {
+        if !columns.contains_key(table) {
+            let mut stmt = conn.prepare(format!("PRAGMA table_info({table})"))?;
+            let mut names = Vec::new();
+            while stmt.next()? == State::Row {
+                names.push(stmt.read::<String, _>("name")?);
+            }
+            columns.insert(table, names);
+        }
+
+        let name = column.split(' ').next().unwrap_or_default();
+        if !columns[table].iter().any(|c| c == name) {
+            conn.execute(format!("ALTER TABLE {table} ADD COLUMN {column}"))?;
+        }
+    }
+
+    conn.execute(format!("PRAGMA user_version = {}", MIGRATIONS.len()))?;
+    Ok(())
+}
+
+// build a RunHistory from the first row selected with HISTORY_COLUMNS
+fn read_history(stmt: &mut Statement) -> anyhow::Result<Option<RunHistory>> {
+    if stmt.next()? != State::Row {
+        return Ok(None);
+    }
+
+    Ok(Some(RunHistory {
+        run_id: stmt.read(0)?,
+        case_name: stmt.read(1)?,
+        start_time: parse_timestamp(&stmt.read::<String, _>(2)?)?,
+        end_time: parse_timestamp(&stmt.read::<String, _>(3)?)?,
+        elapsed: stmt.read(4)?,
+        nb_files: stmt.read(5)?,
+        args: stmt.read(6)?,
+        tags: stmt.read(7)?,
+        status: stmt.read(8)?,
+        source: stmt.read(9)?,
+        base_run: stmt.read(10)?,
+        examiner: stmt.read(11)?,
+        case_number: stmt.read(12)?,
+        acquired: stmt
+            .read::<Option<String>, _>(13)?
+            .map(|s| parse_timestamp(&s))
+            .transpose()?,
+        image_md5: stmt.read(14)?,
+        image_sha1: stmt.read(15)?,
+        verified: stmt.read::<Option<i64>, _>(16)?.map(|v| v != 0),
+    }))
+}
+
+// same format as a Postgres timestamp, in UTC
+fn timestamp(time: &SystemTime) -> String {
+    DateTime::<Utc>::from(*time)
+        .format("%Y-%m-%d %H:%M:%S%.6f")
+        .to_string()
+}
+
+fn parse_timestamp(s: &str) -> anyhow::Result<SystemTime> {
+    let ndt = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")?;
+    Ok(ndt.and_utc().into())
+}
+
+// None is bound as NULL
+fn optional<T: Into<Value>>(value: Option<T>) -> Value {
+    value.map(Into::into).unwrap_or(Value::Null)
+}
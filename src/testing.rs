@@ -0,0 +1,41 @@
+// test fixture: a temporary directory holding the files to scan and a SQLite output,
+// both removed on drop even if an assertion fails
+use std::path::PathBuf;
+
+use tempfile::TempDir;
+
+use crate::storage::{Backend, Storage};
+
+pub struct TestDb {
+    dir: TempDir,
+
+    // SQLite file, created with its tables by backend()
+    pub db: PathBuf,
+}
+
+impl TestDb {
+    pub fn new() -> anyhow::Result<Self> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("files"))?;
+        let db = dir.path().join("iaa.db");
+        Ok(Self { dir, db })
+    }
+
+    // where to create the files to scan, the database being outside of it
+    pub fn files(&self) -> PathBuf {
+        self.dir.path().join("files")
+    }
+
+    pub fn backend(&self) -> anyhow::Result<Backend> {
+        Backend::new(&format!("sqlite:{}", self.db.display()), 1, false)
+    }
+
+    pub fn storage(&self) -> anyhow::Result<Box<dyn Storage>> {
+        self.backend()?.connect()
+    }
+
+    // to read back what has been saved
+    pub fn conn(&self) -> anyhow::Result<sqlite::Connection> {
+        Ok(sqlite::open(&self.db)?)
+    }
+}
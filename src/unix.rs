@@ -0,0 +1,181 @@
+// unix metadata: inode, device, owner, permissions, hard links and inode change time
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::Metadata,
+    os::unix::fs::MetadataExt,
+    path::Path,
+    sync::{LazyLock, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use nix::unistd::{Gid, Group, Uid, User};
+
+use crate::fileinfo::FileInfo;
+
+// names are only resolved once per id, as lookups could go through NSS (e.g. LDAP)
+static USERS: LazyLock<Mutex<HashMap<u32, Option<String>>>> = LazyLock::new(Default::default);
+static GROUPS: LazyLock<Mutex<HashMap<u32, Option<String>>>> = LazyLock::new(Default::default);
+
+// file types and special bits of st_mode
+const S_IFMT: u32 = 0o170000;
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+const S_ISVTX: u32 = 0o1000;
+
+// follow is true when meta was obtained by following symbolic links
+pub fn set_metadata(fi: &mut FileInfo, meta: &Metadata, path: &Path, follow: bool) {
+    fi.inode = Some(meta.ino() as i64);
+    fi.dev = Some(meta.dev() as i64);
+    fi.uid = Some(meta.uid() as i64);
+    fi.gid = Some(meta.gid() as i64);
+    fi.user_name = user_name(meta.uid());
+    fi.group_name = group_name(meta.gid());
+    fi.mode = Some(meta.mode() as i32);
+    fi.permissions = Some(permissions(meta.mode()));
+    fi.nlink = Some(meta.nlink() as i64);
+    fi.changed = time(meta.ctime(), meta.ctime_nsec());
+
+    #[cfg(target_os = "linux")]
+    if fi.created.is_none() {
+        fi.created = birth_time(path, follow);
+    }
+}
+
+// std doesn't always give the birth time (e.g. with older libc), even if the
+// filesystem stores it
+#[cfg(target_os = "linux")]
+pub fn birth_time(path: &Path, follow: bool) -> Option<SystemTime> {
+    use rustix::fs::{statx, AtFlags, StatxFlags, CWD};
+
+    let flags = if follow {
+        AtFlags::empty()
+    } else {
+        AtFlags::SYMLINK_NOFOLLOW
+    };
+    let stx = statx(CWD, path, flags, StatxFlags::BTIME).ok()?;
+
+    // not all filesystems have a birth time
+    if stx.stx_mask & StatxFlags::BTIME.bits() == 0 {
+        return None;
+    }
+    time(stx.stx_btime.tv_sec, stx.stx_btime.tv_nsec as i64)
+}
+
+// first path seen for each (device, inode) having several hard links, shared by the
+// workers of a run
+#[derive(Debug, Default)]
+pub struct HardLinks(Mutex<HashMap<(u64, u64), String>>);
+
+impl HardLinks {
+    // hard links are only hashed once: the other ones reference the first path seen
+    pub fn hardlink_of(&self, path: &str, meta: &Metadata) -> Option<String> {
+        if !meta.is_file() || meta.nlink() < 2 {
+            return None;
+        }
+
+        let mut links = self.0.lock().unwrap();
+        match links.entry((meta.dev(), meta.ino())) {
+            Entry::Occupied(first) => Some(first.get().clone()),
+            Entry::Vacant(e) => {
+                e.insert(path.to_string());
+                None
+            }
+        }
+    }
+}
+
+fn user_name(uid: u32) -> Option<String> {
+    let mut users = USERS.lock().unwrap();
+    users
+        .entry(uid)
+        .or_insert_with(|| {
+            User::from_uid(Uid::from_raw(uid))
+                .ok()
+                .flatten()
+                .map(|u| u.name)
+        })
+        .clone()
+}
+
+fn group_name(gid: u32) -> Option<String> {
+    let mut groups = GROUPS.lock().unwrap();
+    groups
+        .entry(gid)
+        .or_insert_with(|| {
+            Group::from_gid(Gid::from_raw(gid))
+                .ok()
+                .flatten()
+                .map(|g| g.name)
+        })
+        .clone()
+}
+
+// same as ls -l, e.g. -rwsr-xr-x
+pub fn permissions(mode: u32) -> String {
+    let file_type = match mode & S_IFMT {
+        0o100000 => '-',
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '?',
+    };
+
+    let mut s = String::from(file_type);
+    for (shift, special, special_char) in [(6, S_ISUID, 's'), (3, S_ISGID, 's'), (0, S_ISVTX, 't')]
+    {
+        let bits = (mode >> shift) & 0o7;
+        s.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+
+        // special bits replace the execute one, uppercase when not executable
+        s.push(match (mode & special != 0, bits & 0o1 != 0) {
+            (true, true) => special_char,
+            (true, false) => special_char.to_ascii_uppercase(),
+            (false, true) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+// seconds and nanoseconds since the epoch, possibly before it
+fn time(secs: i64, nsecs: i64) -> Option<SystemTime> {
+    let since = Duration::new(secs.unsigned_abs(), 0);
+    let time = if secs >= 0 {
+        UNIX_EPOCH.checked_add(since)?
+    } else {
+        UNIX_EPOCH.checked_sub(since)?
+    };
+    time.checked_add(Duration::from_nanos(nsecs as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode() {
+        assert_eq!(permissions(0o100644), "-rw-r--r--");
+        assert_eq!(permissions(0o104755), "-rwsr-xr-x");
+        assert_eq!(permissions(0o102644), "-rw-r-Sr--");
+        assert_eq!(permissions(0o041777), "drwxrwxrwt");
+        assert_eq!(permissions(0o120777), "lrwxrwxrwx");
+    }
+
+    #[test]
+    fn owner() -> anyhow::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let mut fi = FileInfo::default();
+        set_metadata(&mut fi, &file.as_file().metadata()?, file.path(), false);
+
+        // names depend on NSS: only check they agree with it
+        let uid = nix::unistd::getuid();
+        assert_eq!(fi.uid, Some(uid.as_raw() as i64));
+        let name = User::from_uid(uid)?.map(|u| u.name);
+        assert_eq!(fi.user_name, name);
+        Ok(())
+    }
+}
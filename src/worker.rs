@@ -1,120 +1,757 @@
 // module for main worker
 use std::{
+    borrow::Cow,
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
     sync::Arc,
     thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use crossbeam_channel as channel;
-use diesel::{
-    r2d2::{ConnectionManager, PooledConnection},
-    PgConnection, RunQueryDsl,
-};
-use log::trace;
+use crossbeam_channel::{self as channel, RecvTimeoutError};
+use log::{error, trace};
 use walkdir::DirEntry;
 
+use crate::hash::Digests;
+use crate::memory::MappedFile;
+use crate::randomness::Entropy;
 use crate::{
     args::Args,
-    fileinfo::{FileInfo, ForensicsFileType},
+    fileinfo::{FileInfo, ForensicsFileType, KNOWN_GOOD, KNOWN_UNKNOWN},
+    image::ImageFile,
+    resume::{self, PreviousRuns},
+    storage::{Backend, Batch, Storage},
 };
-use crate::{memory::MappedFile, schema::artefact::dsl::artefact};
 
-pub type ChanReceiver = channel::Receiver<DirEntry>;
+#[cfg(unix)]
+use crate::unix::HardLinks;
+
+// what the workers receive: either walker entries or files found in a disk image
+pub enum Entry {
+    Dir(DirEntry),
+    Image(Box<ImageFile>),
+}
+
+impl Entry {
+    fn path(&self) -> Cow<'_, str> {
+        match self {
+            Self::Dir(entry) => entry.path().to_string_lossy(),
+            Self::Image(file) => Cow::from(file.fi.path.as_str()),
+        }
+    }
+}
+
+pub type ChanReceiver = channel::Receiver<Entry>;
 
-// buld a thread pool: each thread will start a worker aimed at inserting data into PG
+// buld a thread pool: each thread will start a worker aimed at inserting data into the storage
 pub fn thread_pool(
     n: usize,
     rec: ChanReceiver,
-    pool: &Arc<diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<PgConnection>>>,
+    backend: &Arc<Backend>,
     args: &Arc<Args>,
-) -> anyhow::Result<Vec<JoinHandle<()>>> {
+    previous: &Arc<PreviousRuns>,
+) -> anyhow::Result<Vec<JoinHandle<u64>>> {
     // create n threads for handle workers
     let mut handles = Vec::new();
 
+    // hard links already seen by any of the workers, only for this run
+    #[cfg(unix)]
+    let hard_links = Arc::new(HardLinks::default());
+
     for i in 0..n {
-        let pool = pool.clone();
+        let backend = backend.clone();
         let args = args.clone();
+        let previous = previous.clone();
+        #[cfg(unix)]
+        let hard_links = hard_links.clone();
 
         let rx = rec.clone();
         let id = thread::spawn(move || {
-            let mut conn = pool.get().expect("db conn error");
+            let mut storage = backend.connect().expect("db conn error");
             trace!("starting thread {}", i);
-            if let Err(e) = worker(rx, &mut conn, &args) {
-                eprintln!("error '{e}' in closure");
+            match worker(
+                rx,
+                storage.as_mut(),
+                &args,
+                &previous,
+                #[cfg(unix)]
+                &hard_links,
+            ) {
+                Ok(count) => count,
+                Err(e) => {
+                    eprintln!("error '{e}' in closure");
+                    0
+                }
             }
         });
         handles.push(id);
     }
 
-    return Ok(handles);
+    Ok(handles)
 }
 
-// worker receiving DirEntry and inserting it into the PG table
+// worker receiving entries and inserting them into the storage,
+// returning the number of entries processed
 pub fn worker(
     rx: ChanReceiver,
-    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+    storage: &mut dyn Storage,
     args: &Arc<Args>,
+    previous: &PreviousRuns,
+    #[cfg(unix)] hard_links: &HardLinks,
+) -> anyhow::Result<u64> {
+    let mut batch = Batch::new(storage, args.batch_size, args.flush_interval);
+    let mut count = 0u64;
+
+    loop {
+        // remaining entries will be processed when the run is resumed
+        if resume::interrupted() {
+            break;
+        }
+
+        // don't keep rows buffered forever if the walker is slow
+        let entry = match rx.recv_timeout(args.flush_interval) {
+            Ok(entry) => entry,
+            Err(RecvTimeoutError::Timeout) => {
+                batch.flush()?;
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        // an error on a single file should not stop the thread
+        let path = entry.path().into_owned();
+        let result = match entry {
+            Entry::Dir(entry) => process(
+                &entry,
+                &mut batch,
+                args,
+                previous,
+                #[cfg(unix)]
+                hard_links,
+            ),
+            Entry::Image(file) => process_image(*file, &mut batch, args, previous),
+        };
+        if let Err(e) = result {
+            error!("error '{e}' processing '{path}'");
+        }
+        count += 1;
+    }
+
+    // insert remaining rows
+    batch.flush()?;
+    Ok(count)
+}
+
+// get all information on a single entry and add it to the batch
+fn process(
+    entry: &DirEntry,
+    batch: &mut Batch,
+    args: &Args,
+    previous: &PreviousRuns,
+    #[cfg(unix)] hard_links: &HardLinks,
 ) -> anyhow::Result<()> {
-    for entry in &rx {
-        // get metadata on this file
-        let mut fi = FileInfo::default();
-
-        // copy path, name and extension
-        // manage cases of Windows for UTF-16 strings
-        fi.path = entry.path().to_string_lossy().into_owned();
-        fi.winpath = entry.path().as_os_str().into();
-        fi.ext = entry
+    // get metadata on this file
+    // copy path, name and extension
+    // manage cases of Windows for UTF-16 strings
+    let mut fi = FileInfo {
+        path: entry.path().to_string_lossy().into_owned(),
+        winpath: entry.path().as_os_str().into(),
+        ext: entry
             .path()
             .extension()
             .unwrap_or_default()
             .to_string_lossy()
-            .into_owned();
+            .into_owned(),
+        name: entry.file_name().to_string_lossy().into_owned(),
+        winname: entry.file_name().into(),
+        r#type: ForensicsFileType::from(&entry.file_type()),
+        run_id: args.run_id.clone(),
+        ..Default::default()
+    };
 
-        fi.name = entry.file_name().to_string_lossy().into_owned();
-        fi.winname = entry.file_name().into();
+    // get metadata
+    let meta = entry.metadata()?;
+    fi.len = meta.len() as i64;
 
-        fi.r#type = ForensicsFileType::from(&entry.file_type());
+    // timestamps
+    if let Ok(time) = meta.created() {
+        fi.created = Some(time);
+    }
+    fi.accessed = meta.accessed()?;
+    fi.modified = meta.modified()?;
+
+    #[cfg(unix)]
+    crate::unix::set_metadata(&mut fi, &meta, entry.path(), args.follow_symlinks);
 
-        // get metadata
-        let meta = entry.metadata()?;
-        fi.len = meta.len() as i64;
+    set_nanoseconds(&mut fi);
 
-        // timestamps
-        if let Ok(time) = meta.created() {
-            fi.created = Some(time);
+    // some filesystems don't support extended attributes
+    #[cfg(unix)]
+    if args.xattrs {
+        match crate::xattrs::xattrs(entry.path()) {
+            Ok(xattrs) => fi.xattrs = xattrs,
+            Err(e) if e.kind() == ErrorKind::Unsupported => (),
+            Err(e) => error!("unable to get extended attributes of '{}': {e}", fi.path),
         }
-        fi.accessed = meta.accessed()?;
-        fi.modified = meta.modified()?;
+    }
 
-        // calculate hashes, only for files
-        if fi.r#type == ForensicsFileType::File && fi.len != 0 {
-            // for other operations, we need to open and read files
-            let mapped = MappedFile::try_from(entry.path())?;
+    // incremental scan: only changed files are processed
+    if previous.is_unchanged(&fi.path, fi.len, fi.modified) {
+        trace!("'{}' unchanged", fi.path);
+        return Ok(());
+    }
 
-            // according to options, call whatever is asked
-            if args.blake3 {
-                fi.blake3 = mapped.blake3();
-            }
+    if fi.r#type == ForensicsFileType::Symlink {
+        symlink(&mut fi, entry.path());
+    }
 
-            if args.sha256 {
-                fi.sha256 = mapped.sha256();
-            }
+    #[cfg(unix)]
+    {
+        fi.hardlink_of = hard_links.hardlink_of(&fi.path, &meta);
+    }
+
+    // calculate hashes, only for files
+    if fi.r#type == ForensicsFileType::File && fi.len != 0 && fi.hardlink_of.is_none() {
+        // for other operations, we need to open and read files
+        let Some(mapped) = map(entry.path(), fi.len as u64, args) else {
+            stream(&mut fi, File::open(entry.path())?, args, batch)?;
+            trace!("{:?}", fi);
+            return batch.push(fi);
+        };
+        analyze(&mut fi, &mapped, args, batch);
 
-            if args.entropy {
-                fi.entropy = Some(mapped.entropy());
+        // some types can only be analyzed from their path
+        if args.discover
+            && fi.mime.is_none()
+            && args.settings.discover(&fi.ext)
+            && !skip_known_good(&fi, args)
+        {
+            match mapped.discover_path(entry.path()) {
+                Ok(found) => (fi.mime, fi.metadata) = found,
+                Err(e) => error!("unable to discover '{}': {e}", fi.path),
             }
+        }
 
-            // try to guess the mime type and extract some metadata
-            if args.discover {
-                (fi.mime, fi.metadata) = mapped.discover();
+        extract(&fi, &mapped, batch, args, previous)?;
+    }
 
-                (fi.mime, fi.metadata) = mapped.discover_path(entry.path()).unwrap();
-            }
+    check_extension(&mut fi, args);
+
+    // insert data
+    trace!("{:?}", fi);
+    batch.push(fi)
+}
+
+// same as process for a file found in a disk image, read from the image itself
+fn process_image(
+    file: ImageFile,
+    batch: &mut Batch,
+    args: &Args,
+    previous: &PreviousRuns,
+) -> anyhow::Result<()> {
+    let ImageFile {
+        mut fi, content, ..
+    } = file;
+    fi.run_id = args.run_id.clone();
+    set_nanoseconds(&mut fi);
+
+    if previous.is_unchanged(&fi.path, fi.len, fi.modified) {
+        trace!("'{}' unchanged", fi.path);
+        return Ok(());
+    }
+
+    let content = content.filter(|_| {
+        fi.r#type == ForensicsFileType::File && fi.len != 0 && fi.hardlink_of.is_none()
+    });
+    if let Some(content) = content {
+        if fi.len as u64 <= args.mmap_max_size {
+            let mut data = Vec::with_capacity(fi.len as usize);
+            content.reader().read_to_end(&mut data)?;
+
+            let mapped = MappedFile::from(data);
+            analyze(&mut fi, &mapped, args, batch);
+            extract(&fi, &mapped, batch, args, previous)?;
+        } else {
+            stream(&mut fi, content.reader(), args, batch)?;
         }
+    }
+
+    check_extension(&mut fi, args);
+
+    trace!("{:?}", fi);
+    batch.push(fi)
+}
+
+// archive members are processed the same way as regular files
+fn extract(
+    fi: &FileInfo,
+    mapped: &MappedFile,
+    batch: &mut Batch,
+    args: &Args,
+    previous: &PreviousRuns,
+) -> anyhow::Result<()> {
+    if args.archive_depth == 0 || skip_known_good(fi, args) {
+        return Ok(());
+    }
+
+    let mut budget = args.archive_max_size;
+    archive_members(
+        &fi.path,
+        mapped,
+        args.archive_depth,
+        &mut budget,
+        batch,
+        args,
+        previous,
+    )
+}
+
+fn set_nanoseconds(fi: &mut FileInfo) {
+    fi.created_ns = fi.created.and_then(nanoseconds);
+    fi.accessed_ns = nanoseconds(fi.accessed);
+    fi.modified_ns = nanoseconds(fi.modified);
+    fi.changed_ns = fi.changed.and_then(nanoseconds);
+}
+
+// nanoseconds since the epoch, before it being negative
+fn nanoseconds(time: SystemTime) -> Option<i64> {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => i64::try_from(d.as_nanos()).ok(),
+        Err(e) => i64::try_from(e.duration().as_nanos()).ok().map(|n| -n),
+    }
+}
+
+// keep the link target, even if it doesn't exist
+fn symlink(fi: &mut FileInfo, path: &Path) {
+    match std::fs::read_link(path) {
+        Ok(target) => fi.link_target = Some(target.to_string_lossy().into_owned()),
+        Err(e) => error!("unable to read link '{}': {e}", fi.path),
+    }
+
+    let resolved = std::fs::canonicalize(path).ok();
+    fi.link_dangling = Some(resolved.is_none());
+    fi.link_resolved = resolved.map(|p| p.to_string_lossy().into_owned());
+}
+
+// large files are not mapped, nor those for which mmap fails (e.g. on some
+// pseudo or network filesystems)
+fn map(path: &Path, len: u64, args: &Args) -> Option<MappedFile> {
+    if len > args.mmap_max_size {
+        return None;
+    }
+
+    match MappedFile::try_from(path) {
+        Ok(mapped) => Some(mapped),
+        Err(e) => {
+            trace!(
+                "unable to map '{}', reading it by chunks: {e}",
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+// all hashes and entropy are calculated from a single read, content being
+// then unavailable for discover and archive extraction
+fn stream(
+    fi: &mut FileInfo,
+    reader: impl Read,
+    args: &Args,
+    batch: &mut Batch,
+) -> anyhow::Result<()> {
+    let entropy = Digests::from(args)
+        .with_entropy(args.entropy.then_some(args.entropy_block_size))
+        .stream(reader, fi)?;
+
+    if args.hash_sets {
+        match_hash_sets(fi, batch.storage());
+    }
+
+    // entropy comes with the same read but isn't kept for known-good files
+    if let Some(entropy) = entropy.filter(|_| !skip_known_good(fi, args)) {
+        entropy.finalize(fi);
+    }
+
+    Ok(())
+}
+
+// calculate hashes, entropy and try to discover file type from its content
+fn analyze(fi: &mut FileInfo, mapped: &MappedFile, args: &Args, batch: &mut Batch) {
+    // according to options, call whatever is asked: all hashes in a single pass
+    Digests::from(args).digest(mapped, fi);
+
+    if args.hash_sets {
+        match_hash_sets(fi, batch.storage());
+    }
+
+    // no need to go further for known-good files
+    if skip_known_good(fi, args) {
+        return;
+    }
+
+    if args.entropy {
+        let mut entropy = Entropy::new(args.entropy_block_size);
+        entropy.update(mapped);
+        entropy.finalize(fi);
+    }
+
+    // try to guess the mime type and extract some metadata
+    if args.discover && args.settings.discover(&fi.ext) {
+        (fi.mime, fi.metadata) = mapped.discover();
+    }
+}
+
+// flag the file as known-good, known-bad or unknown using its hashes
+fn match_hash_sets(fi: &mut FileInfo, storage: &mut dyn Storage) {
+    let hashes: Vec<&str> = [&fi.md5, &fi.sha1, &fi.sha256]
+        .into_iter()
+        .filter(|h| !h.is_empty())
+        .map(String::as_str)
+        .collect();
+    if hashes.is_empty() {
+        return;
+    }
+
+    match storage.known_hash(&hashes) {
+        Ok(Some((kind, label))) => (fi.known, fi.known_label) = (Some(kind), label),
+        Ok(None) => fi.known = Some(KNOWN_UNKNOWN.to_string()),
+        Err(e) => error!("unable to match '{}' against hash sets: {e}", fi.path),
+    }
+}
+
+// flag files whose extension doesn't match the type discovered from their content
+fn check_extension(fi: &mut FileInfo, args: &Args) {
+    if let Some(mime) = fi.mime {
+        fi.mismatch_reason = args.settings.mismatch(&fi.ext, mime);
+        fi.ext_mismatch = Some(fi.mismatch_reason.is_some());
+    }
+}
+
+fn skip_known_good(fi: &FileInfo, args: &Args) -> bool {
+    args.skip_known_good && fi.known.as_deref() == Some(KNOWN_GOOD)
+}
+
+// extract archive members in memory and insert them as virtual artefacts
+// with a path like outer.zip!/inner/file.exe, recursing into nested archives
+fn archive_members(
+    parent: &str,
+    mapped: &MappedFile,
+    depth: u8,
+    budget: &mut u64,
+    batch: &mut Batch,
+    args: &Args,
+    previous: &PreviousRuns,
+) -> anyhow::Result<()> {
+    let members = match mapped.members(budget) {
+        Ok(members) => members,
+        Err(e) => {
+            error!("unable to extract members from '{parent}': {e}");
+            return Ok(());
+        }
+    };
+
+    for member in members {
+        let path = format!("{parent}!/{}", member.name);
+
+        // already inserted before the resumed run was interrupted
+        if previous.is_done(&path) {
+            continue;
+        }
+        let name = member
+            .name
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let modified = member.modified.unwrap_or(UNIX_EPOCH);
+
+        let mut fi = FileInfo {
+            winpath: path.as_str().into(),
+            winname: name.as_str().into(),
+            ext: Path::new(&name)
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            path,
+            name,
+            r#type: if member.is_dir {
+                ForensicsFileType::Directory
+            } else {
+                ForensicsFileType::File
+            },
+            len: member.data.len() as i64,
+            accessed: modified,
+            modified,
+            parent: Some(parent.to_string()),
+            run_id: args.run_id.clone(),
+            ..Default::default()
+        };
+
+        let mapped = MappedFile::from(member.data);
+        if fi.r#type == ForensicsFileType::File && fi.len != 0 {
+            analyze(&mut fi, &mapped, args, batch);
+        }
+
+        if depth > 1 && fi.len != 0 {
+            archive_members(&fi.path, &mapped, depth - 1, budget, batch, args, previous)?;
+        }
+
+        check_extension(&mut fi, args);
 
-        // insert data
-        diesel::insert_into(artefact).values(&fi).execute(conn)?;
         trace!("{:?}", fi);
+        batch.push(fi)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::testing::TestDb;
+
+    // process files as a worker would, with these command line options
+    fn scan(test: &TestDb, paths: &[impl AsRef<Path>], options: &[&str]) -> anyhow::Result<()> {
+        let args = Args::parse_from(["iaa", "-d", "."].iter().chain(options));
+        let mut storage = test.storage()?;
+        let mut batch = Batch::new(storage.as_mut(), 10, args.flush_interval);
+        #[cfg(unix)]
+        let hard_links = HardLinks::default();
+        for path in paths {
+            let entry = walkdir::WalkDir::new(path.as_ref())
+                .into_iter()
+                .next()
+                .unwrap()?;
+            process(
+                &entry,
+                &mut batch,
+                &args,
+                &PreviousRuns::default(),
+                #[cfg(unix)]
+                &hard_links,
+            )?;
+        }
+        batch.flush()
+    }
+
+    // file read by chunks or mapped, depending on --mmap-max-size
+    #[test]
+    fn mmap_threshold() -> anyhow::Result<()> {
+        let test = TestDb::new()?;
+        for size in ["16", "1024"] {
+            let options = ["--ssdeep", "--tlsh", "--mmap-max-size", size];
+            scan(&test, &["tests/ctph.txt"], &options)?;
+        }
+
+        let conn = test.conn()?;
+        let mut stmt = conn.prepare("SELECT ssdeep, tlsh FROM artefact")?;
+        let mut fuzzy = Vec::new();
+        while stmt.next()? == ::sqlite::State::Row {
+            fuzzy.push((stmt.read::<String, _>(0)?, stmt.read::<String, _>(1)?));
+        }
+        assert_eq!(fuzzy.len(), 2);
+        assert_eq!(fuzzy[0], fuzzy[1]);
+        assert_eq!(fuzzy[0].0, "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C");
+        assert_eq!(
+            fuzzy[0].1,
+            "T1A2A022A3CC0FB00C8C0222228B82082A8E02E0F2C28002A8CC0CAC0E022023E00C30F0"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn extension_mismatch() -> anyhow::Result<()> {
+        let test = TestDb::new()?;
+        let names = [
+            "image.png",
+            "image.PNG",
+            "photo.jpg",
+            "image",
+            "notes.txt",
+            "readme.png",
+        ];
+        let png = std::fs::read("tests/test.png")?;
+        for name in &names[..5] {
+            std::fs::write(test.files().join(name), &png)?;
+        }
+        std::fs::write(test.files().join("readme.png"), b"not an image")?;
+        let paths: Vec<_> = names.iter().map(|n| test.files().join(n)).collect();
+        scan(&test, &paths, &["--discover"])?;
+
+        let conn = test.conn()?;
+        let mut stmt =
+            conn.prepare("SELECT ext_mismatch, mismatch_reason FROM artefact WHERE name = :name")?;
+        let mut mismatch = |name: &str| -> anyhow::Result<(Option<i64>, Option<String>)> {
+            stmt.reset()?;
+            stmt.bind((":name", name))?;
+            assert_eq!(stmt.next()?, ::sqlite::State::Row);
+            Ok((stmt.read(0)?, stmt.read(1)?))
+        };
+
+        assert_eq!(mismatch("image.png")?, (Some(0), None));
+        assert_eq!(mismatch("image.PNG")?, (Some(0), None));
+        assert_eq!(
+            mismatch("photo.jpg")?,
+            (Some(1), Some("png content with .jpg extension".to_string()))
+        );
+        assert_eq!(
+            mismatch("image")?,
+            (Some(1), Some("png content without extension".to_string()))
+        );
+        assert_eq!(
+            mismatch("notes.txt")?,
+            (Some(1), Some("png content with .txt extension".to_string()))
+        );
+
+        // nothing discovered, nothing to compare with
+        assert_eq!(mismatch("readme.png")?, (None, None));
+        Ok(())
+    }
+
+    #[test]
+    fn archive_members() -> anyhow::Result<()> {
+        use std::io::Write;
+
+        fn zip(files: &[(&str, &[u8])]) -> anyhow::Result<Vec<u8>> {
+            let mut writer = ::zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+            for (name, data) in files {
+                writer.start_file(*name, ::zip::write::SimpleFileOptions::default())?;
+                writer.write_all(data)?;
+            }
+            Ok(writer.finish()?.into_inner())
+        }
+
+        // outer.zip holds a.txt and inner.zip, itself holding deep.txt
+        let inner = zip(&[("deep.txt", b"deep")])?;
+        let outer = zip(&[("a.txt", b"a"), ("inner.zip", &inner)])?;
+
+        let members = |depth: &str| -> anyhow::Result<Vec<(String, Option<String>)>> {
+            let test = TestDb::new()?;
+            let path = test.files().join("outer.zip");
+            std::fs::write(&path, &outer)?;
+            let max = u64::MAX.to_string();
+            scan(
+                &test,
+                &[&path],
+                &["--archive-depth", depth, "--archive-max-size", &max],
+            )?;
+
+            let root = path.to_string_lossy();
+            let conn = test.conn()?;
+            let mut stmt = conn.prepare("SELECT path, parent FROM artefact ORDER BY path")?;
+            let mut rows = Vec::new();
+            while stmt.next()? == ::sqlite::State::Row {
+                let path: String = stmt.read(0)?;
+                let parent: Option<String> = stmt.read(1)?;
+                rows.push((
+                    path.replace(&*root, "outer.zip"),
+                    parent.map(|p| p.replace(&*root, "outer.zip")),
+                ));
+            }
+            Ok(rows)
+        };
+        let row = |path: &str, parent: Option<&str>| (path.to_string(), parent.map(String::from));
+
+        assert_eq!(
+            members("1")?,
+            [
+                row("outer.zip", None),
+                row("outer.zip!/a.txt", Some("outer.zip")),
+                row("outer.zip!/inner.zip", Some("outer.zip")),
+            ]
+        );
+        assert_eq!(
+            members("2")?[3],
+            row(
+                "outer.zip!/inner.zip!/deep.txt",
+                Some("outer.zip!/inner.zip")
+            )
+        );
+        assert_eq!(members("0")?, [row("outer.zip", None)]);
+        Ok(())
+    }
+
+    // the second link is only pointing to the first one, which is hashed
+    #[cfg(unix)]
+    #[test]
+    fn hard_links() -> anyhow::Result<()> {
+        let test = TestDb::new()?;
+        let (first, second) = (test.files().join("first"), test.files().join("second"));
+        std::fs::write(&first, b"same content")?;
+        std::fs::hard_link(&first, &second)?;
+        scan(&test, &[&first, &second], &["--sha256"])?;
+
+        let conn = test.conn()?;
+        let mut stmt =
+            conn.prepare("SELECT name, nlink, sha256, hardlink_of FROM artefact ORDER BY name")?;
+        assert_eq!(stmt.next()?, ::sqlite::State::Row);
+        assert_eq!(stmt.read::<i64, _>(1)?, 2);
+        assert_eq!(stmt.read::<String, _>(2)?.len(), 64);
+        assert_eq!(stmt.read::<Option<String>, _>(3)?, None);
+
+        assert_eq!(stmt.next()?, ::sqlite::State::Row);
+        assert_eq!(stmt.read::<String, _>(0)?, "second");
+        assert_eq!(stmt.read::<String, _>(2)?, "");
+        assert_eq!(
+            stmt.read::<Option<String>, _>(3)?,
+            Some(first.to_string_lossy().into_owned())
+        );
+
+        // links seen by a previous run are forgotten: the next one hashes its first path
+        drop(stmt);
+        scan(&test, &[&second], &["--sha256"])?;
+        let mut stmt = conn.prepare(
+            "SELECT sha256, hardlink_of FROM artefact WHERE name = 'second' ORDER BY sha256",
+        )?;
+        assert_eq!(stmt.next()?, ::sqlite::State::Row);
+        assert_eq!(stmt.next()?, ::sqlite::State::Row);
+        assert_eq!(stmt.read::<String, _>(0)?.len(), 64);
+        assert_eq!(stmt.read::<Option<String>, _>(1)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps() -> anyhow::Result<()> {
+        use std::time::Duration;
+
+        assert_eq!(nanoseconds(UNIX_EPOCH), Some(0));
+        assert_eq!(
+            nanoseconds(UNIX_EPOCH - Duration::new(1, 500)),
+            Some(-1_000_000_500)
+        );
+
+        let test = TestDb::new()?;
+        let path = test.files().join("file");
+        let before = SystemTime::now() - Duration::from_secs(1);
+        let file = File::create(&path)?;
+        let modified = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        file.set_modified(modified)?;
+        drop(file);
+        scan(&test, &[&path], &[])?;
+
+        let conn = test.conn()?;
+        let mut stmt = conn.prepare("SELECT modified_ns, created, created_ns FROM artefact")?;
+        assert_eq!(stmt.next()?, ::sqlite::State::Row);
+        assert_eq!(stmt.read::<i64, _>(0)?, 1_700_000_000_123_456_789);
+
+        // not all filesystems have a birth time
+        if let Some(created) = stmt.read::<Option<i64>, _>(2)? {
+            assert!(created >= nanoseconds(before).unwrap());
+            assert!(stmt.read::<Option<String>, _>(1)?.is_some());
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(birth) = crate::unix::birth_time(&path, false) {
+            assert!(birth >= before && birth <= SystemTime::now());
+        }
+        Ok(())
+    }
+}
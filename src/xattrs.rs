@@ -0,0 +1,243 @@
+// extended attributes, with POSIX ACLs and file capabilities decoded, as a JSON value like:
+// {"attributes": {"user.foo": {"text": "bar"}}, "acl": {"access": ["user::rw-", ...]},
+//  "capabilities": {"effective": true, "permitted": ["cap_net_raw"], ...}}
+use std::{io, path::Path};
+
+use serde_json::{json, Map, Value};
+
+const ACL_ACCESS: &str = "system.posix_acl_access";
+const ACL_DEFAULT: &str = "system.posix_acl_default";
+const CAPABILITY: &str = "security.capability";
+
+// struct posix_acl_xattr_header and posix_acl_xattr_entry
+const ACL_VERSION: u32 = 2;
+const ACL_ENTRY_SIZE: usize = 8;
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+// struct vfs_cap_data
+const VFS_CAP_REVISION_MASK: u32 = 0xFF00_0000;
+const VFS_CAP_REVISION_1: u32 = 0x0100_0000;
+const VFS_CAP_REVISION_2: u32 = 0x0200_0000;
+const VFS_CAP_REVISION_3: u32 = 0x0300_0000;
+const VFS_CAP_FLAGS_EFFECTIVE: u32 = 0x0000_0001;
+
+// capability names, indexed by their number
+const CAPABILITIES: [&str; 41] = [
+    "chown",
+    "dac_override",
+    "dac_read_search",
+    "fowner",
+    "fsetid",
+    "kill",
+    "setgid",
+    "setuid",
+    "setpcap",
+    "linux_immutable",
+    "net_bind_service",
+    "net_broadcast",
+    "net_admin",
+    "net_raw",
+    "ipc_lock",
+    "ipc_owner",
+    "sys_module",
+    "sys_rawio",
+    "sys_chroot",
+    "sys_ptrace",
+    "sys_pacct",
+    "sys_admin",
+    "sys_boot",
+    "sys_nice",
+    "sys_resource",
+    "sys_time",
+    "sys_tty_config",
+    "mknod",
+    "lease",
+    "audit_write",
+    "audit_control",
+    "setfcap",
+    "mac_override",
+    "mac_admin",
+    "syslog",
+    "wake_alarm",
+    "block_suspend",
+    "audit_read",
+    "perfmon",
+    "bpf",
+    "checkpoint_restore",
+];
+
+// None if the file has no extended attributes. Symbolic links are not followed
+pub fn xattrs(path: &Path) -> io::Result<Option<Value>> {
+    let mut attributes = Map::new();
+    let mut acl = Map::new();
+    let mut capabilities = None;
+
+    for name in xattr::list(path)? {
+        let Some(value) = xattr::get(path, &name)? else {
+            continue;
+        };
+        let name = name.to_string_lossy().into_owned();
+
+        match name.as_str() {
+            ACL_ACCESS => _ = acl.insert("access".to_string(), posix_acl(&value).into()),
+            ACL_DEFAULT => _ = acl.insert("default".to_string(), posix_acl(&value).into()),
+            CAPABILITY => capabilities = file_capabilities(&value),
+            _ => (),
+        }
+
+        attributes.insert(name, raw_value(&value));
+    }
+
+    if attributes.is_empty() {
+        return Ok(None);
+    }
+
+    let mut xattrs = Map::new();
+    xattrs.insert("attributes".to_string(), attributes.into());
+    if !acl.is_empty() {
+        xattrs.insert("acl".to_string(), acl.into());
+    }
+    if let Some(capabilities) = capabilities {
+        xattrs.insert("capabilities".to_string(), capabilities);
+    }
+
+    Ok(Some(xattrs.into()))
+}
+
+// printable values are kept as text, others are hex encoded
+fn raw_value(value: &[u8]) -> Value {
+    // values set from the shell are often null terminated
+    let trimmed = value.strip_suffix(b"\0").unwrap_or(value);
+
+    match std::str::from_utf8(trimmed) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => {
+            json!({ "text": text })
+        }
+        _ => json!({ "hex": value.iter().map(|b| format!("{b:02x}")).collect::<String>() }),
+    }
+}
+
+// ACL entries as displayed by getfacl -n, e.g. user:1000:r-x
+fn posix_acl(value: &[u8]) -> Vec<String> {
+    if le_u32(value, 0) != Some(ACL_VERSION) {
+        return Vec::new();
+    }
+
+    value[4..]
+        .chunks_exact(ACL_ENTRY_SIZE)
+        .map(|entry| {
+            let tag = u16::from_le_bytes([entry[0], entry[1]]);
+            let perm = u16::from_le_bytes([entry[2], entry[3]]);
+            let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+
+            let tag = match tag {
+                0x01 | 0x02 => "user",
+                0x04 | 0x08 => "group",
+                0x10 => "mask",
+                0x20 => "other",
+                _ => "unknown",
+            };
+            let id = if id == ACL_UNDEFINED_ID {
+                String::new()
+            } else {
+                id.to_string()
+            };
+            let perm: String = [(4, 'r'), (2, 'w'), (1, 'x')]
+                .iter()
+                .map(|(bit, c)| if perm & bit != 0 { *c } else { '-' })
+                .collect();
+
+            format!("{tag}:{id}:{perm}")
+        })
+        .collect()
+}
+
+// decode struct vfs_cap_data, as displayed by getcap
+fn file_capabilities(value: &[u8]) -> Option<Value> {
+    let magic = le_u32(value, 0)?;
+    let (words, rootid) = match magic & VFS_CAP_REVISION_MASK {
+        VFS_CAP_REVISION_1 => (1, None),
+        VFS_CAP_REVISION_2 => (2, None),
+        VFS_CAP_REVISION_3 => (2, Some(le_u32(value, 20)?)),
+        _ => return None,
+    };
+
+    // permitted and inheritable sets are split into 32 bits words
+    let mut permitted = 0u64;
+    let mut inheritable = 0u64;
+    for i in 0..words {
+        permitted |= (le_u32(value, 4 + i * 8)? as u64) << (32 * i);
+        inheritable |= (le_u32(value, 8 + i * 8)? as u64) << (32 * i);
+    }
+
+    let mut capabilities = json!({
+        "effective": magic & VFS_CAP_FLAGS_EFFECTIVE != 0,
+        "permitted": capability_names(permitted),
+        "inheritable": capability_names(inheritable),
+    });
+    if let Some(rootid) = rootid {
+        capabilities["rootid"] = rootid.into();
+    }
+    Some(capabilities)
+}
+
+fn capability_names(set: u64) -> Vec<String> {
+    (0..64)
+        .filter(|bit| set & (1 << bit) != 0)
+        .map(|bit| match CAPABILITIES.get(bit) {
+            Some(name) => format!("cap_{name}"),
+            None => format!("cap_{bit}"),
+        })
+        .collect()
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let buf: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(u32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        // getfacl: user::rw-, user:1000:r-x, group::r--, mask::r-x, other::---
+        let mut acl = ACL_VERSION.to_le_bytes().to_vec();
+        for (tag, perm, id) in [
+            (0x01u16, 6u16, ACL_UNDEFINED_ID),
+            (0x02, 5, 1000),
+            (0x04, 4, ACL_UNDEFINED_ID),
+            (0x10, 5, ACL_UNDEFINED_ID),
+            (0x20, 0, ACL_UNDEFINED_ID),
+        ] {
+            acl.extend(tag.to_le_bytes());
+            acl.extend(perm.to_le_bytes());
+            acl.extend(id.to_le_bytes());
+        }
+        assert_eq!(
+            posix_acl(&acl),
+            [
+                "user::rw-",
+                "user:1000:r-x",
+                "group::r--",
+                "mask::r-x",
+                "other::---"
+            ]
+        );
+
+        // setcap cap_net_raw,cap_net_admin+ep
+        let cap = hex_literal::hex!("01000002 00300000 00000000 00000000 00000000");
+        assert_eq!(
+            file_capabilities(&cap),
+            Some(json!({
+                "effective": true,
+                "permitted": ["cap_net_admin", "cap_net_raw"],
+                "inheritable": [],
+            }))
+        );
+
+        assert_eq!(raw_value(b"bar\0"), json!({ "text": "bar" }));
+        assert_eq!(raw_value(&[1, 2]), json!({ "hex": "0102" }));
+    }
+}